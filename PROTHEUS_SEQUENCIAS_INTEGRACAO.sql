@@ -0,0 +1,41 @@
+-- =====================================================================
+-- Sequências da integração Portal → Protheus (ZC7010 / ZC8010)
+-- Executar no banco do Protheus antes de ligar INTEGRACAO_HABILITADA=true.
+-- O worker não inicia se alguma delas estiver ausente.
+--
+-- SEQ_API_ZC7_NUM    -> ZC7_NUM (6 dígitos), começa após o maior número existente
+-- SEQ_API_ZC7_RECNO  -> R_E_C_N_O_ de ZC7010
+-- SEQ_API_ZC8_RECNO  -> R_E_C_N_O_ de ZC8010
+--
+-- Script idempotente: sequências já existentes não são alteradas.
+-- =====================================================================
+
+DECLARE @inicio BIGINT;
+DECLARE @sql NVARCHAR(500);
+
+IF NOT EXISTS (SELECT 1 FROM sys.sequences WHERE name = 'SEQ_API_ZC7_NUM')
+BEGIN
+    -- Ignora números não numéricos gravados por outras rotinas
+    SELECT @inicio = ISNULL(MAX(TRY_CAST(ZC7_NUM AS BIGINT)), 0) + 1 FROM ZC7010;
+    SET @sql = N'CREATE SEQUENCE dbo.SEQ_API_ZC7_NUM AS INT START WITH ' + CAST(@inicio AS NVARCHAR(20))
+             + N' INCREMENT BY 1 MINVALUE 1 MAXVALUE 999999 NO CYCLE CACHE 20';
+    EXEC sp_executesql @sql;
+END
+
+IF NOT EXISTS (SELECT 1 FROM sys.sequences WHERE name = 'SEQ_API_ZC7_RECNO')
+BEGIN
+    SELECT @inicio = ISNULL(MAX(R_E_C_N_O_), 0) + 1 FROM ZC7010;
+    SET @sql = N'CREATE SEQUENCE dbo.SEQ_API_ZC7_RECNO AS INT START WITH ' + CAST(@inicio AS NVARCHAR(20))
+             + N' INCREMENT BY 1 NO CYCLE CACHE 50';
+    EXEC sp_executesql @sql;
+END
+
+IF NOT EXISTS (SELECT 1 FROM sys.sequences WHERE name = 'SEQ_API_ZC8_RECNO')
+BEGIN
+    SELECT @inicio = ISNULL(MAX(R_E_C_N_O_), 0) + 1 FROM ZC8010;
+    SET @sql = N'CREATE SEQUENCE dbo.SEQ_API_ZC8_RECNO AS INT START WITH ' + CAST(@inicio AS NVARCHAR(20))
+             + N' INCREMENT BY 1 NO CYCLE CACHE 50';
+    EXEC sp_executesql @sql;
+END
+
+SELECT name, current_value FROM sys.sequences WHERE name LIKE 'SEQ[_]API[_]%';
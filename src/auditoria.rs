@@ -0,0 +1,373 @@
+// src/auditoria.rs
+// Trilha de auditoria persistida no PostgreSQL FC (tabela `api_audit_log`)
+//
+// Diferente do `CardsLogger` (arquivo de debug), aqui ficam eventos de segurança
+// e de negócio que precisam ser consultados depois:
+// - toda requisição aos escopos protegidos (middleware `AuditoriaRequisicoes`), inclusive
+//   as barradas pelo `JwtMiddleware`: usuário, rota, parâmetros, resultado e duração
+// - SQL livre e pedidos afetados, anotados pelos handlers via `RegistroAuditoria`
+// - eventos de segurança avulsos (ex.: bloqueio de login)
+//
+// A gravação é assíncrona: falha ao auditar é logada, mas não derruba a requisição.
+
+use std::cell::RefCell;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::time::Instant;
+
+use actix_web::{
+    dev::{forward_ready, Payload, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error, FromRequest, HttpMessage, HttpRequest,
+};
+use deadpool_postgres::{Client, Pool};
+use futures_util::future::LocalBoxFuture;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use tokio_postgres::Row;
+
+use crate::config::Settings;
+use crate::errors::ApiError;
+use crate::protecao_login::ip_cliente;
+
+/// SQL maior que isso é truncado na trilha
+const SQL_TAMANHO_MAXIMO: usize = 20_000;
+
+/// Evento a ser gravado na trilha de auditoria
+#[derive(Debug, Clone, Default)]
+pub struct EventoAuditoria {
+    pub usuario: Option<String>,
+    pub evento: String,
+    pub metodo: Option<String>,
+    pub rota: Option<String>,
+    pub ip: Option<String>,
+    pub parametros: Option<Value>,
+    pub sql_texto: Option<String>,
+    pub pedido_ids: Vec<i32>,
+    pub resultado: String,
+    pub status_code: Option<i16>,
+    pub duracao_ms: Option<i32>,
+}
+
+impl EventoAuditoria {
+    pub fn novo(evento: &str, resultado: &str) -> Self {
+        EventoAuditoria {
+            evento: evento.to_string(),
+            resultado: resultado.to_string(),
+            ..Default::default()
+        }
+    }
+}
+
+/// Registro gravado, como retornado pela consulta administrativa
+#[derive(Debug, Serialize)]
+pub struct RegistroGravado {
+    pub id: i64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub usuario: Option<String>,
+    pub evento: String,
+    pub metodo: Option<String>,
+    pub rota: Option<String>,
+    pub ip: Option<String>,
+    pub parametros: Option<Value>,
+    pub sql_texto: Option<String>,
+    pub pedido_ids: Vec<i32>,
+    pub resultado: String,
+    pub status_code: Option<i16>,
+    pub duracao_ms: Option<i32>,
+}
+
+/// Filtros da consulta administrativa
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct FiltrosAuditoria {
+    pub usuario: Option<String>,
+    pub evento: Option<String>,
+    pub rota: Option<String>,
+    pub resultado: Option<String>,
+    pub pedido_id: Option<i32>,
+    /// Data/hora inicial (YYYY-MM-DD ou RFC 3339)
+    pub desde: Option<String>,
+    pub ate: Option<String>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+/// Cria a tabela `api_audit_log`
+pub async fn inicializar(client: &Client) -> Result<(), ApiError> {
+    client.batch_execute(r#"
+        CREATE TABLE IF NOT EXISTS api_audit_log (
+            id BIGSERIAL PRIMARY KEY,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            usuario VARCHAR(150),
+            evento VARCHAR(50) NOT NULL,
+            metodo VARCHAR(10),
+            rota TEXT,
+            ip VARCHAR(64),
+            parametros JSONB,
+            resultado VARCHAR(30) NOT NULL,
+            status_code SMALLINT,
+            sql_texto TEXT,
+            pedido_ids INT[] NOT NULL DEFAULT '{}',
+            duracao_ms INT
+        );
+        CREATE INDEX IF NOT EXISTS idx_api_audit_log_created_at ON api_audit_log (created_at);
+        CREATE INDEX IF NOT EXISTS idx_api_audit_log_usuario ON api_audit_log (usuario, created_at);
+        CREATE INDEX IF NOT EXISTS idx_api_audit_log_pedidos ON api_audit_log USING GIN (pedido_ids);
+    "#).await?;
+    Ok(())
+}
+
+/// Grava o evento em background
+pub fn registrar(pool: &Pool, evento: EventoAuditoria) {
+    let pool = pool.clone();
+    tokio::spawn(async move {
+        if let Err(e) = gravar(&pool, &evento).await {
+            log::error!("❌ Erro ao gravar auditoria '{}': {}", evento.evento, e);
+        }
+    });
+}
+
+async fn gravar(pool: &Pool, evento: &EventoAuditoria) -> Result<(), ApiError> {
+    let client = pool.get().await?;
+    client.execute(r#"
+        INSERT INTO api_audit_log (
+            usuario, evento, metodo, rota, ip, parametros,
+            sql_texto, pedido_ids, resultado, status_code, duracao_ms
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+    "#, &[
+        &evento.usuario, &evento.evento, &evento.metodo, &evento.rota, &evento.ip,
+        &evento.parametros, &evento.sql_texto, &evento.pedido_ids, &evento.resultado,
+        &evento.status_code, &evento.duracao_ms,
+    ]).await?;
+    Ok(())
+}
+
+/// Consulta a trilha, do mais recente para o mais antigo
+pub async fn consultar(client: &Client, filtros: &FiltrosAuditoria) -> Result<Vec<RegistroGravado>, ApiError> {
+    let mut sql = String::from(r#"
+        SELECT id, created_at, usuario, evento, metodo, rota, ip, parametros,
+               sql_texto, pedido_ids, resultado, status_code, duracao_ms
+        FROM api_audit_log
+        WHERE 1 = 1
+    "#);
+    let mut params: Vec<Box<dyn tokio_postgres::types::ToSql + Sync + Send>> = vec![];
+
+    if let Some(usuario) = &filtros.usuario {
+        params.push(Box::new(usuario.clone()));
+        sql.push_str(&format!(" AND usuario = ${}", params.len()));
+    }
+    if let Some(evento) = &filtros.evento {
+        params.push(Box::new(evento.clone()));
+        sql.push_str(&format!(" AND evento = ${}", params.len()));
+    }
+    if let Some(rota) = &filtros.rota {
+        params.push(Box::new(format!("%{}%", rota)));
+        sql.push_str(&format!(" AND rota ILIKE ${}", params.len()));
+    }
+    if let Some(resultado) = &filtros.resultado {
+        params.push(Box::new(resultado.clone()));
+        sql.push_str(&format!(" AND resultado = ${}", params.len()));
+    }
+    if let Some(pedido_id) = filtros.pedido_id {
+        params.push(Box::new(vec![pedido_id]));
+        sql.push_str(&format!(" AND pedido_ids @> ${}", params.len()));
+    }
+    if let Some(desde) = &filtros.desde {
+        params.push(Box::new(desde.clone()));
+        sql.push_str(&format!(" AND created_at >= ${}::text::timestamptz", params.len()));
+    }
+    if let Some(ate) = &filtros.ate {
+        params.push(Box::new(ate.clone()));
+        sql.push_str(&format!(" AND created_at <= ${}::text::timestamptz", params.len()));
+    }
+
+    let limit = filtros.limit.unwrap_or(100).clamp(1, 1000);
+    let offset = filtros.offset.unwrap_or(0).max(0);
+    sql.push_str(&format!(" ORDER BY id DESC LIMIT {} OFFSET {}", limit, offset));
+
+    let param_refs: Vec<&(dyn tokio_postgres::types::ToSql + Sync)> = params
+        .iter()
+        .map(|b| b.as_ref() as &(dyn tokio_postgres::types::ToSql + Sync))
+        .collect();
+
+    let rows = client.query(&sql, &param_refs[..]).await
+        .map_err(|e| match e.code() {
+            Some(_) => ApiError::BadRequest(format!("Filtro inválido: {}", e)),
+            None => e.into(),
+        })?;
+    Ok(rows.iter().map(registro_from_row).collect())
+}
+
+fn registro_from_row(row: &Row) -> RegistroGravado {
+    RegistroGravado {
+        id: row.get(0),
+        created_at: row.get(1),
+        usuario: row.get(2),
+        evento: row.get(3),
+        metodo: row.get(4),
+        rota: row.get(5),
+        ip: row.get(6),
+        parametros: row.get(7),
+        sql_texto: row.get(8),
+        pedido_ids: row.get(9),
+        resultado: row.get(10),
+        status_code: row.get(11),
+        duracao_ms: row.get(12),
+    }
+}
+
+#[derive(Debug, Default)]
+struct Detalhes {
+    usuario: Option<String>,
+    sql: Option<String>,
+    parametros: Option<Value>,
+    pedido_ids: Vec<i32>,
+}
+
+/// Anotações do handler para o registro da requisição atual (SQL executado, pedidos afetados).
+/// Fora do middleware de auditoria as anotações são descartadas.
+#[derive(Clone, Default)]
+pub struct RegistroAuditoria(Rc<RefCell<Detalhes>>);
+
+impl RegistroAuditoria {
+    /// Usuário identificado pelo `JwtMiddleware` (mesmo que depois barrado pelos papéis)
+    pub fn usuario(&self, usuario: &str) {
+        self.0.borrow_mut().usuario = Some(usuario.to_string());
+    }
+
+    /// SQL livre executado e seus parâmetros
+    pub fn sql(&self, sql: &str, parametros: Option<&[Value]>) {
+        let mut detalhes = self.0.borrow_mut();
+        detalhes.sql = Some(sql.chars().take(SQL_TAMANHO_MAXIMO).collect());
+        if let Some(parametros) = parametros {
+            detalhes.parametros = Some(json!(parametros));
+        }
+    }
+
+    /// Pedido criado ou alterado pela requisição
+    pub fn pedido(&self, pedido_id: i32) {
+        let mut detalhes = self.0.borrow_mut();
+        if !detalhes.pedido_ids.contains(&pedido_id) {
+            detalhes.pedido_ids.push(pedido_id);
+        }
+    }
+}
+
+impl FromRequest for RegistroAuditoria {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Ok(req.extensions().get::<RegistroAuditoria>().cloned().unwrap_or_default()))
+    }
+}
+
+/// Middleware que audita as requisições do escopo, inclusive as recusadas pelo `JwtMiddleware` (401/403).
+/// Registrar com `.wrap()` depois do `JwtMiddleware`, para ficar por fora dele.
+pub struct AuditoriaRequisicoes;
+
+impl<S, B> Transform<S, ServiceRequest> for AuditoriaRequisicoes
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AuditoriaRequisicoesService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(AuditoriaRequisicoesService { service: Rc::new(service) }))
+    }
+}
+
+/// Serviço do middleware de auditoria
+pub struct AuditoriaRequisicoesService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for AuditoriaRequisicoesService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+
+        Box::pin(async move {
+            let inicio = Instant::now();
+            let pool = req.app_data::<web::Data<Pool>>().cloned();
+            let settings = req.app_data::<web::Data<Settings>>().cloned();
+
+            let registro = RegistroAuditoria::default();
+            req.extensions_mut().insert(registro.clone());
+
+            let mut evento = EventoAuditoria {
+                metodo: Some(req.method().to_string()),
+                rota: Some(req.path().to_string()),
+                ip: settings.as_ref().map(|s| ip_cliente(req.request(), &s.login)),
+                ..Default::default()
+            };
+            let consulta = req.query_string().to_string();
+
+            let resultado = service.call(req).await;
+
+            let (status, caminho) = match &resultado {
+                Ok(res) => {
+                    let caminho: Map<String, Value> = res.request().match_info().iter()
+                        .map(|(nome, valor)| (nome.to_string(), json!(valor)))
+                        .collect();
+                    (res.status(), caminho)
+                }
+                Err(e) => (e.as_response_error().status_code(), Map::new()),
+            };
+
+            let detalhes = registro.0.take();
+            let mut parametros = Map::new();
+            if !caminho.is_empty() {
+                parametros.insert("path".to_string(), Value::Object(caminho));
+            }
+            if !consulta.is_empty() {
+                parametros.insert("query".to_string(), json!(consulta));
+            }
+            if let Some(sql_params) = detalhes.parametros {
+                parametros.insert("sql_params".to_string(), sql_params);
+            }
+
+            evento.usuario = detalhes.usuario;
+            evento.evento = if detalhes.sql.is_some() {
+                "sql_livre"
+            } else if evento.metodo.as_deref() == Some("GET") {
+                "consulta"
+            } else {
+                "mutacao"
+            }.to_string();
+            evento.resultado = match status.as_u16() {
+                401 | 403 => "negado",
+                429 => "limitado",
+                s if s >= 400 => "erro",
+                _ => "sucesso",
+            }.to_string();
+            evento.status_code = Some(status.as_u16() as i16);
+            evento.parametros = (!parametros.is_empty()).then_some(Value::Object(parametros));
+            evento.sql_texto = detalhes.sql;
+            evento.pedido_ids = detalhes.pedido_ids;
+            evento.duracao_ms = Some(inicio.elapsed().as_millis().min(i32::MAX as u128) as i32);
+
+            if let Some(pool) = pool {
+                registrar(&pool, evento);
+            }
+
+            resultado
+        })
+    }
+}
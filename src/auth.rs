@@ -1,5 +1,5 @@
 // src/auth.rs
-// Módulo de autenticação JWT
+// Módulo de autenticação JWT e chaves de API
 
 use actix_web::{
     dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
@@ -7,15 +7,52 @@ use actix_web::{
     error::ErrorUnauthorized,
 };
 use futures_util::future::LocalBoxFuture;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashMap,
     future::{ready, Ready},
     rc::Rc,
+    sync::{Arc, RwLock},
 };
 
-use crate::config::Settings;
+use crate::auditoria::RegistroAuditoria;
+use crate::chaves_jwt::ChavesJwt;
 use crate::errors::{ApiError, ApiResult};
+use crate::handlers::usuarios::chaves_api;
+
+/// Header das chaves de API (integrações máquina a máquina)
+pub const HEADER_CHAVE_API: &str = "X-API-Key";
+
+/// Papéis de acesso embutidos no token
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Papel {
+    Admin,
+    Analista,
+    Franqueado,
+    Integrador,
+}
+
+impl Papel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Papel::Admin => "admin",
+            Papel::Analista => "analista",
+            Papel::Franqueado => "franqueado",
+            Papel::Integrador => "integrador",
+        }
+    }
+    
+    pub fn from_nome(valor: &str) -> Option<Self> {
+        match valor.trim().to_lowercase().as_str() {
+            "admin" => Some(Papel::Admin),
+            "analista" => Some(Papel::Analista),
+            "franqueado" => Some(Papel::Franqueado),
+            "integrador" => Some(Papel::Integrador),
+            _ => None,
+        }
+    }
+}
 
 /// Claims do token JWT
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -23,44 +60,104 @@ pub struct Claims {
     pub sub: String, // subject (username)
     pub exp: i64,    // expiration timestamp
     pub iat: i64,    // issued at timestamp
+    #[serde(default)]
+    pub roles: Vec<Papel>, // papéis de acesso (tokens antigos sem papéis só acessam rotas abertas a qualquer token)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cnpjs: Vec<String>, // CNPJs (apenas dígitos) aos quais o token está vinculado
+    #[serde(default)]
+    pub jti: String, // id único do token, usado na revogação
 }
 
-/// Gera um novo token JWT
-#[allow(dead_code)]
-pub fn generate_token(username: &str, settings: &Settings) -> ApiResult<String> {
-    let now = chrono::Utc::now();
-    let expiration = now + chrono::Duration::hours(settings.jwt.expiration_hours);
+impl Claims {
+    pub fn tem_papel(&self, papel: Papel) -> bool {
+        self.roles.contains(&papel)
+    }
     
-    let claims = Claims {
-        sub: username.to_string(),
-        exp: expiration.timestamp(),
-        iat: now.timestamp(),
-    };
+    /// Tokens de franqueado (ou com CNPJs vinculados) só enxergam os próprios dados
+    pub fn restrito_a_cnpj(&self) -> bool {
+        !self.cnpjs.is_empty() || self.tem_papel(Papel::Franqueado)
+    }
     
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(settings.jwt.secret.as_bytes()),
-    )
-    .map_err(|e| ApiError::Auth(format!("Erro ao gerar token: {}", e)))}
-
-/// Valida um token JWT
-pub fn validate_token(token: &str, settings: &Settings) -> ApiResult<Claims> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(settings.jwt.secret.as_bytes()),
-        &Validation::default(),
-    )
-    .map_err(|e| match e.kind() {
-        jsonwebtoken::errors::ErrorKind::ExpiredSignature => ApiError::ExpiredToken,
-        _ => ApiError::InvalidToken,
-    })?;
+    /// Rejeita (403) CNPJ fora dos vinculados ao token; tokens sem restrição passam
+    pub fn exigir_cnpj(&self, cnpj: &str) -> Result<(), ApiError> {
+        if !self.restrito_a_cnpj() {
+            return Ok(());
+        }
+        
+        let cnpj = apenas_digitos(cnpj);
+        if self.cnpjs.contains(&cnpj) {
+            Ok(())
+        } else {
+            log::warn!("🚫 '{}' tentou acessar CNPJ {} fora do seu vínculo", self.sub, cnpj);
+            Err(ApiError::Unauthorized)
+        }
+    }
     
-    Ok(token_data.claims)
+    /// Rejeita (403) tokens vinculados a CNPJ em endpoints sem filtro por CNPJ (SQL livre, listagens da rede)
+    pub fn exigir_acesso_irrestrito(&self) -> Result<(), ApiError> {
+        if self.restrito_a_cnpj() {
+            log::warn!("🚫 Token vinculado a CNPJ de '{}' negado em endpoint irrestrito", self.sub);
+            return Err(ApiError::Unauthorized);
+        }
+        Ok(())
+    }
 }
 
-/// Middleware para validação JWT
-pub struct JwtMiddleware;
+/// Mantém apenas os dígitos do CNPJ (aceita com ou sem máscara)
+pub fn apenas_digitos(cnpj: &str) -> String {
+    cnpj.chars().filter(|c| c.is_ascii_digit()).collect()
+}
+
+/// Valida um token JWT (assinatura, algoritmo, `kid` e expiração)
+pub fn validate_token(token: &str, chaves: &ChavesJwt) -> ApiResult<Claims> {
+    chaves.verificar::<Claims>(token)
+        .map_err(|e| match e.kind() {
+            jsonwebtoken::errors::ErrorKind::ExpiredSignature => ApiError::ExpiredToken,
+            _ => ApiError::InvalidToken,
+        })
+}
+
+/// Tokens revogados antes de expirar (`jti` → `exp`), consultados pelo `JwtMiddleware`.
+/// A fonte é a tabela `api_revoked_tokens`; a cópia em memória evita ir ao banco a cada request.
+#[derive(Debug, Clone, Default)]
+pub struct TokensRevogados(Arc<RwLock<HashMap<String, i64>>>);
+
+impl TokensRevogados {
+    pub fn revogar(&self, jti: &str, exp: i64) {
+        if let Ok(mut mapa) = self.0.write() {
+            mapa.insert(jti.to_string(), exp);
+        }
+    }
+    
+    pub fn esta_revogado(&self, jti: &str) -> bool {
+        !jti.is_empty() && self.0.read().map(|mapa| mapa.contains_key(jti)).unwrap_or(false)
+    }
+    
+    /// Substitui o conteúdo pela lista carregada do banco (já sem expirados)
+    pub fn substituir(&self, revogados: HashMap<String, i64>) {
+        if let Ok(mut mapa) = self.0.write() {
+            *mapa = revogados;
+        }
+    }
+}
+
+/// Middleware para validação JWT ou chave de API (`X-API-Key`)
+/// Com `papeis` vazio aceita qualquer token válido; caso contrário exige ao menos um dos papéis (403)
+pub struct JwtMiddleware {
+    papeis: &'static [Papel],
+}
+
+impl JwtMiddleware {
+    /// Qualquer token válido
+    pub fn autenticado() -> Self {
+        JwtMiddleware { papeis: &[] }
+    }
+    
+    /// Token válido com ao menos um dos papéis informados
+    pub fn papeis(papeis: &'static [Papel]) -> Self {
+        JwtMiddleware { papeis }
+    }
+}
 
 impl<S, B> Transform<S, ServiceRequest> for JwtMiddleware
 where
@@ -77,12 +174,14 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(JwtMiddlewareService {
             service: Rc::new(service),
+            papeis: self.papeis,
         }))
     }
 }
 /// Serviço do middleware JWT
 pub struct JwtMiddlewareService<S> {
     service: Rc<S>,
+    papeis: &'static [Papel],
 }
 
 impl<S, B> Service<ServiceRequest> for JwtMiddlewareService<S>
@@ -99,54 +198,89 @@ where
 
     fn call(&self, req: ServiceRequest) -> Self::Future {
         let service = self.service.clone();
+        let papeis = self.papeis;
         
         Box::pin(async move {
-            // Extrair token do header Authorization
-            let auth_header = req.headers().get("Authorization");
+            // Chave de API (integrações) ou Bearer JWT (usuários)
+            let chave_api = req.headers().get(HEADER_CHAVE_API)
+                .and_then(|v| v.to_str().ok())
+                .map(|v| v.to_string());
             
-            if let Some(auth_value) = auth_header {
-                if let Ok(auth_str) = auth_value.to_str() {
-                    if auth_str.starts_with("Bearer ") {
-                        let token = &auth_str[7..];
-                        
-                        // Obter configurações
-                        if let Some(settings) = req.app_data::<actix_web::web::Data<Settings>>() {
-                            match validate_token(token, &settings) {
-                                Ok(claims) => {
-                                    // Adicionar claims ao request
-                                    req.extensions_mut().insert(claims);
-                                    return service.call(req).await;
-                                }
-                                Err(e) => {
-                                    return Err(ErrorUnauthorized(e.to_string()));
-                                }
-                            }
-                        }
-                    }
-                }
+            let claims = match chave_api {
+                Some(chave) => validar_chave_api(&req, &chave).await?,
+                None => validar_bearer(&req)?,
+            };
+            if let Some(registro) = req.extensions().get::<RegistroAuditoria>() {
+                registro.usuario(&claims.sub);
             }
             
-            Err(ErrorUnauthorized("Token não fornecido"))
+            // Verificar papéis exigidos pelo escopo
+            if !papeis.is_empty() && !papeis.iter().any(|p| claims.tem_papel(*p)) {
+                log::warn!("🚫 Acesso negado para '{}' em {} (papéis: {:?})",
+                    claims.sub, req.path(), claims.roles);
+                return Err(ApiError::Unauthorized.into());
+            }
+            
+            // Adicionar claims ao request
+            req.extensions_mut().insert(claims);
+            service.call(req).await
         })
     }
 }
 
-/// Função auxiliar para criar JWT (alias para generate_token)
-pub fn create_jwt(username: &str, secret: &str, expiration_hours: i64) -> Result<String, jsonwebtoken::errors::Error> {
+/// Valida o JWT do header `Authorization: Bearer` e a lista de revogação
+fn validar_bearer(req: &ServiceRequest) -> Result<Claims, Error> {
+    let token = req.headers().get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| ErrorUnauthorized("Token não fornecido"))?;
+    
+    let chaves = req.app_data::<actix_web::web::Data<ChavesJwt>>()
+        .ok_or_else(|| ErrorUnauthorized("Token não fornecido"))?;
+    
+    let claims = validate_token(token, chaves).map_err(|e| ErrorUnauthorized(e.to_string()))?;
+    
+    // Verificar lista de revogação (logout / revogação administrativa)
+    if let Some(revogados) = req.app_data::<actix_web::web::Data<TokensRevogados>>() {
+        if revogados.esta_revogado(&claims.jti) {
+            log::warn!("🚫 Token revogado usado por '{}' (jti {})", claims.sub, claims.jti);
+            return Err(ErrorUnauthorized("Token revogado"));
+        }
+    }
+    
+    Ok(claims)
+}
+
+/// Valida a chave do header `X-API-Key` na tabela `api_keys`
+async fn validar_chave_api(req: &ServiceRequest, chave: &str) -> Result<Claims, Error> {
+    let pool = req.app_data::<actix_web::web::Data<deadpool_postgres::Pool>>()
+        .ok_or_else(|| ErrorUnauthorized("Chave de API inválida"))?;
+    
+    let client = pool.get().await.map_err(ApiError::from)?;
+    match chaves_api::autenticar(&client, chave).await? {
+        Some(claims) => Ok(claims),
+        None => {
+            log::warn!("🚫 Chave de API inválida ou revogada em {}", req.path());
+            Err(ErrorUnauthorized("Chave de API inválida"))
+        }
+    }
+}
+
+/// Função auxiliar para criar JWT assinado com a chave ativa
+pub fn create_jwt(username: &str, roles: &[Papel], cnpjs: &[String], chaves: &ChavesJwt, expiration_minutes: i64) -> Result<String, jsonwebtoken::errors::Error> {
     let now = chrono::Utc::now();
-    let expiration = now + chrono::Duration::hours(expiration_hours);
+    let expiration = now + chrono::Duration::minutes(expiration_minutes);
     
     let claims = Claims {
         sub: username.to_string(),
         exp: expiration.timestamp(),
         iat: now.timestamp(),
+        roles: roles.to_vec(),
+        cnpjs: cnpjs.to_vec(),
+        jti: uuid::Uuid::new_v4().to_string(),
     };
     
-    encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(secret.as_bytes()),
-    )
+    chaves.assinar(&claims)
 }
 
 // Implementação de FromRequest para Claims
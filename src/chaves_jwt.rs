@@ -0,0 +1,201 @@
+// src/chaves_jwt.rs
+// Chaves de assinatura JWT (HS256, RS256 ou EdDSA) e JWKS público
+//
+// - `JWT_ALGORITHM=HS256` (padrão): segredo compartilhado `JWT_SECRET`, JWKS vazio;
+//   a API não inicia com segredo vazio ou com o valor de exemplo
+// - `JWT_ALGORITHM=RS256` ou `EdDSA`: pares de chave em `JWT_KEYS_DIR`
+//   - `<kid>.pem`: chave privada; só a de `JWT_ACTIVE_KID` assina tokens
+//   - `<kid>.pub.pem`: chave pública, publicada no JWKS e aceita na validação
+// - Rotação: gerar um novo par, apontar `JWT_ACTIVE_KID` para ele e manter a pública
+//   antiga no diretório até os tokens assinados com ela expirarem
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use simple_asn1::{from_der, ASN1Block};
+
+use crate::config::JwtConfig;
+use crate::errors::ApiError;
+
+/// Segredos HS256 publicados no código e no `.env.example`: com eles qualquer um forja tokens
+const SEGREDOS_PUBLICOS: [&str; 2] = [
+    "default_secret_change_in_production",
+    "your-secret-key-change-this-in-production",
+];
+
+/// Chaves carregadas na inicialização, compartilhadas via `web::Data`
+pub struct ChavesJwt {
+    algoritmo: Algorithm,
+    kid_ativo: Option<String>,
+    assinatura: EncodingKey,
+    /// kid → chave de verificação (HS256 usa kid vazio)
+    verificacao: HashMap<String, DecodingKey>,
+    jwks: Value,
+}
+
+impl ChavesJwt {
+    pub fn carregar(config: &JwtConfig) -> Result<Self, ApiError> {
+        match config.algorithm.trim().to_uppercase().as_str() {
+            "HS256" => Self::simetrica(&config.secret),
+            "RS256" => Self::assimetrica(Algorithm::RS256, config),
+            "EDDSA" => Self::assimetrica(Algorithm::EdDSA, config),
+            outro => Err(ApiError::Configuration(format!(
+                "JWT_ALGORITHM inválido: {} (use HS256, RS256 ou EdDSA)", outro
+            ))),
+        }
+    }
+
+    fn simetrica(secret: &str) -> Result<Self, ApiError> {
+        if secret.trim().is_empty() || SEGREDOS_PUBLICOS.contains(&secret) {
+            return Err(ApiError::Configuration(
+                "JWT_SECRET vazio ou com valor de exemplo; defina um segredo próprio ou use JWT_ALGORITHM=RS256/EdDSA".to_string()
+            ));
+        }
+
+        Ok(ChavesJwt {
+            algoritmo: Algorithm::HS256,
+            kid_ativo: None,
+            assinatura: EncodingKey::from_secret(secret.as_bytes()),
+            verificacao: HashMap::from([(String::new(), DecodingKey::from_secret(secret.as_bytes()))]),
+            jwks: json!({ "keys": [] }),
+        })
+    }
+
+    fn assimetrica(algoritmo: Algorithm, config: &JwtConfig) -> Result<Self, ApiError> {
+        let kid_ativo = config.active_kid.clone()
+            .ok_or_else(|| ApiError::Configuration("JWT_ACTIVE_KID é obrigatório com chaves assimétricas".to_string()))?;
+        let dir = Path::new(&config.keys_dir);
+
+        let privada = ler(&dir.join(format!("{}.pem", kid_ativo)))?;
+        let assinatura = match algoritmo {
+            Algorithm::EdDSA => EncodingKey::from_ed_pem(&privada),
+            _ => EncodingKey::from_rsa_pem(&privada),
+        }.map_err(|e| ApiError::Configuration(format!("Chave privada '{}' inválida: {}", kid_ativo, e)))?;
+
+        let mut verificacao = HashMap::new();
+        let mut jwks = Vec::new();
+
+        let entradas = std::fs::read_dir(dir)
+            .map_err(|e| ApiError::Configuration(format!("Erro ao ler JWT_KEYS_DIR '{}': {}", config.keys_dir, e)))?;
+        for entrada in entradas.flatten() {
+            let nome = entrada.file_name().to_string_lossy().to_string();
+            let Some(kid) = nome.strip_suffix(".pub.pem") else {
+                continue;
+            };
+
+            let publica = ler(&entrada.path())?;
+            let chave = match algoritmo {
+                Algorithm::EdDSA => DecodingKey::from_ed_pem(&publica),
+                _ => DecodingKey::from_rsa_pem(&publica),
+            }.map_err(|e| ApiError::Configuration(format!("Chave pública '{}' inválida: {}", kid, e)))?;
+
+            jwks.push(jwk(algoritmo, kid, &publica)?);
+            verificacao.insert(kid.to_string(), chave);
+        }
+
+        if !verificacao.contains_key(&kid_ativo) {
+            return Err(ApiError::Configuration(format!(
+                "Chave pública {}.pub.pem não encontrada em '{}'", kid_ativo, config.keys_dir
+            )));
+        }
+
+        log::info!("🔏 JWT {:?} com kid ativo '{}' ({} chaves públicas)", algoritmo, kid_ativo, verificacao.len());
+
+        Ok(ChavesJwt {
+            algoritmo,
+            kid_ativo: Some(kid_ativo),
+            assinatura,
+            verificacao,
+            jwks: json!({ "keys": jwks }),
+        })
+    }
+
+    /// Assina as claims com a chave ativa (o header leva o `kid`)
+    pub fn assinar<T: Serialize>(&self, claims: &T) -> Result<String, JwtError> {
+        let mut header = Header::new(self.algoritmo);
+        header.kid = self.kid_ativo.clone();
+        encode(&header, claims, &self.assinatura)
+    }
+
+    /// Valida assinatura e expiração; aceita apenas o algoritmo configurado e kids conhecidos
+    pub fn verificar<T: DeserializeOwned>(&self, token: &str) -> Result<T, JwtError> {
+        let kid = match self.kid_ativo {
+            Some(_) => decode_header(token)?.kid.ok_or_else(|| JwtError::from(ErrorKind::InvalidToken))?,
+            None => String::new(),
+        };
+        let chave = self.verificacao.get(&kid).ok_or_else(|| JwtError::from(ErrorKind::InvalidToken))?;
+
+        Ok(decode::<T>(token, chave, &Validation::new(self.algoritmo))?.claims)
+    }
+
+    /// JWKS com as chaves públicas (vazio com HS256)
+    pub fn jwks(&self) -> &Value {
+        &self.jwks
+    }
+}
+
+fn ler(caminho: &Path) -> Result<Vec<u8>, ApiError> {
+    std::fs::read(caminho)
+        .map_err(|e| ApiError::Configuration(format!("Erro ao ler {}: {}", caminho.display(), e)))
+}
+
+/// Monta o JWK a partir da chave pública PEM (SPKI ou PKCS#1 para RSA)
+fn jwk(algoritmo: Algorithm, kid: &str, publica_pem: &[u8]) -> Result<Value, ApiError> {
+    let invalida = || ApiError::Configuration(format!("Chave pública '{}' em formato não suportado", kid));
+
+    let pem = pem::parse(publica_pem).map_err(|_| invalida())?;
+    let blocos = from_der(pem.contents()).map_err(|_| invalida())?;
+
+    // SPKI: SEQUENCE { SEQUENCE { algoritmo }, BIT STRING { chave } }
+    let chave = match blocos.first() {
+        Some(ASN1Block::Sequence(_, itens)) if pem.tag() == "PUBLIC KEY" => match itens.get(1) {
+            Some(ASN1Block::BitString(_, _, bytes)) => bytes.clone(),
+            _ => return Err(invalida()),
+        },
+        _ => pem.contents().to_vec(),
+    };
+
+    match algoritmo {
+        Algorithm::EdDSA => {
+            if chave.len() != 32 {
+                return Err(invalida());
+            }
+            Ok(json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "use": "sig",
+                "alg": "EdDSA",
+                "kid": kid,
+                "x": URL_SAFE_NO_PAD.encode(&chave),
+            }))
+        }
+        _ => {
+            // RSAPublicKey: SEQUENCE { INTEGER n, INTEGER e }
+            let rsa = from_der(&chave).map_err(|_| invalida())?;
+            let (n, e) = match rsa.first() {
+                Some(ASN1Block::Sequence(_, itens)) => match (itens.first(), itens.get(1)) {
+                    (Some(ASN1Block::Integer(_, n)), Some(ASN1Block::Integer(_, e))) => {
+                        (n.to_bytes_be().1, e.to_bytes_be().1)
+                    }
+                    _ => return Err(invalida()),
+                },
+                _ => return Err(invalida()),
+            };
+            Ok(json!({
+                "kty": "RSA",
+                "use": "sig",
+                "alg": "RS256",
+                "kid": kid,
+                "n": URL_SAFE_NO_PAD.encode(n),
+                "e": URL_SAFE_NO_PAD.encode(e),
+            }))
+        }
+    }
+}
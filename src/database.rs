@@ -1,9 +1,12 @@
 use bb8_tiberius::ConnectionManager;
 #[allow(unused_imports)] // Usado em analytics_handlers com caminho completo bb8::PooledConnection
 use bb8::{Pool, PooledConnection};
-use deadpool_postgres::{Config as PgConfig, Pool as PgPool, Runtime};
+use deadpool_postgres::{Config as PgConfig, Pool as PgPool, PoolConfig, Runtime, Timeouts};
 use std::env;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
+use tokio::sync::Semaphore;
 use tokio_postgres::NoTls;
 
 #[derive(Error, Debug)]
@@ -19,6 +22,15 @@ pub enum DatabaseError {
 }
 
 pub type SqlServerPool = Pool<ConnectionManager>;
+
+/// Abre conexões dedicadas (fora do pool) para SQL livre: em caso de timeout a conexão
+/// é descartada e o SQL Server cancela a consulta, sem deixar conexão suja no pool.
+/// `vagas` limita as conexões simultâneas a `CONSULTA_LIVRE_MAX_CONEXOES`, como o pool do FC.
+#[derive(Clone)]
+pub struct SqlServerConexaoLeitura {
+    pub manager: Arc<ConnectionManager>,
+    pub vagas: Arc<Semaphore>,
+}
 // Removido SqlServerConnection type alias não usado
 
 #[derive(Clone)]
@@ -26,12 +38,28 @@ pub struct DatabasePools {
     pub postgres_fc: PgPool,
     pub sqlserver_portal: SqlServerPool,
     pub sqlserver_protheus: SqlServerPool,
+    /// Pool separado para SQL livre (`DATABASE_READONLY_URL`), para não esgotar o pool principal
+    pub postgres_fc_leitura: PgPool,
+    pub sqlserver_portal_leitura: SqlServerConexaoLeitura,
+    pub sqlserver_protheus_leitura: SqlServerConexaoLeitura,
 }
 
 impl DatabasePools {
     pub async fn new() -> Result<Self, DatabaseError> {
         // PostgreSQL FC Data
-        let postgres_fc = create_postgres_pool()?;
+        let database_url = env::var("DATABASE_URL")
+            .map_err(|_| DatabaseError::ConfigError("DATABASE_URL not set".to_string()))?;
+        let postgres_fc = create_postgres_pool(&database_url, None)?;
+        
+        // PostgreSQL FC - SQL livre (usuário somente leitura, se configurado)
+        let max_conexoes_leitura = env::var("CONSULTA_LIVRE_MAX_CONEXOES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(4);
+        let postgres_fc_leitura = create_postgres_pool(
+            &credencial_leitura("DATABASE_READONLY_URL", &database_url),
+            Some(max_conexoes_leitura),
+        )?;
         
         // SQL Server Portal
         let portal_conn_str = env::var("PORTAL_CONNECTION_STRING")
@@ -43,25 +71,62 @@ impl DatabasePools {
             .map_err(|_| DatabaseError::ConfigError("PROTHEUS_CONNECTION_STRING not set".to_string()))?;
         let sqlserver_protheus = create_sqlserver_pool(&protheus_conn_str).await?;
         
+        // SQL Server - SQL livre (login somente leitura, se configurado)
+        let sqlserver_portal_leitura = create_sqlserver_conexao_leitura(
+            &credencial_leitura("PORTAL_READONLY_CONNECTION_STRING", &portal_conn_str),
+            max_conexoes_leitura,
+        )?;
+        let sqlserver_protheus_leitura = create_sqlserver_conexao_leitura(
+            &credencial_leitura("PROTHEUS_READONLY_CONNECTION_STRING", &protheus_conn_str),
+            max_conexoes_leitura,
+        )?;
+        
         Ok(DatabasePools {
             postgres_fc,
             sqlserver_portal,
             sqlserver_protheus,
+            postgres_fc_leitura,
+            sqlserver_portal_leitura,
+            sqlserver_protheus_leitura,
         })
     }
 }
 
-fn create_postgres_pool() -> Result<PgPool, DatabaseError> {
-    let database_url = env::var("DATABASE_URL")
-        .map_err(|_| DatabaseError::ConfigError("DATABASE_URL not set".to_string()))?;
-    
+/// Credencial somente leitura da variável informada ou, na ausência, a principal
+fn credencial_leitura(variavel: &str, principal: &str) -> String {
+    env::var(variavel).unwrap_or_else(|_| {
+        log::warn!("⚠️ {} não definida; SQL livre usará a credencial principal", variavel);
+        principal.to_string()
+    })
+}
+
+/// Pool PostgreSQL; com `max_size`, limita o pool e a espera por conexão (SQL livre)
+fn create_postgres_pool(database_url: &str, max_size: Option<usize>) -> Result<PgPool, DatabaseError> {
     let mut cfg = PgConfig::new();
-    cfg.url = Some(database_url);
+    cfg.url = Some(database_url.to_string());
+    if let Some(max_size) = max_size {
+        let espera = Some(Duration::from_secs(5));
+        cfg.pool = Some(PoolConfig {
+            max_size,
+            timeouts: Timeouts { wait: espera, create: espera, recycle: espera },
+            ..Default::default()
+        });
+    }
     
     cfg.create_pool(Some(Runtime::Tokio1), NoTls)
         .map_err(|e| DatabaseError::ConfigError(format!("Failed to create PostgreSQL pool: {}", e)))
 }
 
+fn create_sqlserver_conexao_leitura(connection_string: &str, max_conexoes: usize) -> Result<SqlServerConexaoLeitura, DatabaseError> {
+    let config = tiberius::Config::from_ado_string(connection_string)
+        .map_err(|e| DatabaseError::ConfigError(format!("Invalid SQL Server connection string: {}", e)))?;
+    
+    Ok(SqlServerConexaoLeitura {
+        manager: Arc::new(ConnectionManager::new(config)),
+        vagas: Arc::new(Semaphore::new(max_conexoes.max(1))),
+    })
+}
+
 async fn create_sqlserver_pool(connection_string: &str) -> Result<SqlServerPool, DatabaseError> {
     let config = tiberius::Config::from_ado_string(connection_string)
         .map_err(|e| DatabaseError::ConfigError(format!("Invalid SQL Server connection string: {}", e)))?;
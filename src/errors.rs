@@ -35,6 +35,25 @@ pub enum ApiError {
     #[error("Requisição inválida: {0}")]
     BadRequest(String),
     
+    #[error("Conflito: {0}")]
+    Conflict(String),
+    
+    #[error("Login bloqueado temporariamente por excesso de tentativas; tente novamente em {segundos}s")]
+    LoginBloqueado { segundos: u64 },
+    
+    #[error("Limite de requisições excedido; tente novamente em {segundos}s")]
+    LimiteRequisicoes { segundos: u64 },
+    
+    #[error("Consulta cancelada após exceder o tempo limite de {segundos}s")]
+    TempoConsultaEsgotado { segundos: u64 },
+    
+    #[error("Operação '{operacao}' não permitida para pedido com status '{status_atual}'")]
+    TransicaoStatusInvalida {
+        operacao: String,
+        status_atual: String,
+        transicoes_permitidas: Vec<String>,
+    },
+    
     #[error("Erro interno do servidor: {0}")]
     InternalError(String),
     
@@ -47,11 +66,25 @@ impl ResponseError for ApiError {
         let status = self.status_code();
         let message = self.to_string();
         
-        HttpResponse::build(status).json(json!({
+        let mut body = json!({
             "error": true,
             "message": message,
             "code": status.as_u16()
-        }))
+        });
+        
+        if let ApiError::TransicaoStatusInvalida { operacao, status_atual, transicoes_permitidas } = self {
+            body["operacao"] = json!(operacao);
+            body["status_atual"] = json!(status_atual);
+            body["transicoes_permitidas"] = json!(transicoes_permitidas);
+        }
+        
+        let mut resposta = HttpResponse::build(status);
+        if let ApiError::LoginBloqueado { segundos } | ApiError::LimiteRequisicoes { segundos } = self {
+            body["retry_after"] = json!(segundos);
+            resposta.insert_header(("Retry-After", segundos.to_string()));
+        }
+        
+        resposta.json(body)
     }
     
     fn status_code(&self) -> StatusCode {
@@ -64,6 +97,11 @@ impl ResponseError for ApiError {
             ApiError::Unauthorized => StatusCode::FORBIDDEN,
             ApiError::NotFound => StatusCode::NOT_FOUND,
             ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::LoginBloqueado { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::LimiteRequisicoes { .. } => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::TempoConsultaEsgotado { .. } => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::TransicaoStatusInvalida { .. } => StatusCode::CONFLICT,
             ApiError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::Configuration(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
@@ -0,0 +1,145 @@
+// src/handlers/admin_handlers.rs
+// Endpoints administrativos (escopo /admin restrito ao papel admin)
+
+use actix_web::{web, HttpResponse};
+use deadpool_postgres::Pool;
+use futures_util::TryStreamExt;
+use serde_json::json;
+use tiberius::{Query, QueryItem};
+
+use crate::auditoria::{self, FiltrosAuditoria};
+use crate::auth::Claims;
+use crate::config::Settings;
+use crate::database::DatabasePools;
+use crate::errors::ApiError;
+use crate::jobs::integracao_protheus::{self, PENDENTES_WHERE};
+use crate::jobs::sincronizacao_status;
+use crate::limite_requisicoes::LimitadorRequisicoes;
+use crate::models::StatusPedido;
+
+/// Fila de integração Portal → Protheus
+/// Pedidos confirmados ainda não integrados, com tentativas, próxima tentativa
+/// e último erro registrado pelo worker
+pub async fn fila_integracao(
+    pools: web::Data<DatabasePools>,
+    settings: web::Data<Settings>,
+    estado: web::Data<integracao_protheus::EstadoCompartilhado>,
+    _claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    let config = &settings.integracao;
+
+    let mut conn = pools.sqlserver_portal.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
+
+    let proxima = integracao_protheus::proxima_tentativa_sql(config.backoff_base_minutos);
+    let sql = format!(r#"
+        SELECT
+            p.id,
+            p.codigo_cliente,
+            p.loja_cliente,
+            ISNULL(p.tentativas_integracao, 0) as tentativas,
+            CONVERT(varchar(19), p.updated_at, 120) as updated_at,
+            CONVERT(varchar(19), {proxima}, 120) as proxima_tentativa,
+            CASE
+                WHEN ISNULL(p.tentativas_integracao, 0) >= @P1 THEN 'esgotado'
+                WHEN {proxima} <= GETDATE() THEN 'pronto'
+                ELSE 'aguardando_backoff'
+            END as situacao
+        FROM pedidos p
+        WHERE {filtro}
+        ORDER BY p.id
+    "#, proxima = proxima, filtro = PENDENTES_WHERE);
+
+    let mut query = Query::new(sql);
+    query.bind(config.max_tentativas);
+    query.bind(StatusPedido::Confirmado.as_str());
+
+    let mut stream = query.query(&mut conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar fila de integração: {}", e)))?;
+
+    let estado = estado.lock()
+        .map(|e| e.clone())
+        .map_err(|_| ApiError::InternalError("Estado da integração indisponível".to_string()))?;
+
+    let mut fila = Vec::new();
+    while let Some(item) = stream.try_next().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler fila de integração: {}", e)))? {
+        if let QueryItem::Row(row) = item {
+            let id = row.get::<i32, _>(0).unwrap_or(0);
+            fila.push(json!({
+                "id": id,
+                "codigo_cliente": row.get::<&str, _>(1).map(|s| s.trim()),
+                "loja_cliente": row.get::<&str, _>(2).map(|s| s.trim()),
+                "tentativas_integracao": row.get::<i32, _>(3).unwrap_or(0),
+                "updated_at": row.get::<&str, _>(4),
+                "proxima_tentativa": row.get::<&str, _>(5),
+                "situacao": row.get::<&str, _>(6),
+                "ultimo_erro": estado.ultimos_erros.get(&id),
+            }));
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "data": fila,
+        "count": fila.len(),
+        "worker": {
+            "habilitada": estado.habilitada,
+            "em_execucao": estado.em_execucao,
+            "ultima_execucao": estado.ultima_execucao,
+            "integrados_total": estado.integrados_total,
+            "falhas_total": estado.falhas_total,
+            "intervalo_segundos": config.intervalo_segundos,
+            "max_tentativas": config.max_tentativas,
+            "backoff_base_minutos": config.backoff_base_minutos,
+        }
+    })))
+}
+
+/// Estado da sincronização Protheus → Portal
+/// Inclui o resultado da última execução (verificados, alterados, não encontrados, falhas)
+pub async fn status_sincronizacao(
+    settings: web::Data<Settings>,
+    estado: web::Data<sincronizacao_status::EstadoCompartilhado>,
+    _claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    let estado = estado.lock()
+        .map(|e| e.clone())
+        .map_err(|_| ApiError::InternalError("Estado da sincronização indisponível".to_string()))?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "data": estado,
+        "intervalo_segundos": settings.sincronizacao.intervalo_segundos,
+        "lote": settings.sincronizacao.lote,
+    })))
+}
+
+/// Métricas operacionais da API
+/// Contadores do limite de requisições: permitidas e limitadas por escopo e clientes mais limitados
+pub async fn metricas(
+    limitador: web::Data<LimitadorRequisicoes>,
+    _claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "limite_requisicoes": limitador.metricas(),
+    })))
+}
+
+/// Consulta a trilha de auditoria (mais recentes primeiro)
+/// Filtros: usuario, evento, rota, resultado, pedido_id, desde, ate, limit (máx. 1000), offset
+pub async fn consultar_auditoria(
+    pool: web::Data<Pool>,
+    filtros: web::Query<FiltrosAuditoria>,
+    _claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    let client = pool.get().await?;
+    let registros = auditoria::consultar(&client, &filtros).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "data": registros,
+        "count": registros.len()
+    })))
+}
@@ -22,8 +22,9 @@ pub async fn exportar_relatorio(
     card: web::Path<String>,
     params: web::Query<ExportParams>,
     _pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
+    claims.exigir_cnpj(&params.cnpj)?;
     let card_type = card.into_inner();
     let formato = params.formato.to_lowercase();
     
@@ -22,8 +22,9 @@ pub async fn analytics_cliente_360(
     cnpj: web::Path<String>,
     params: web::Query<AnalyticsParams>,
     _pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
+    claims.exigir_cnpj(&cnpj)?;
     log::info!("Gerando analytics 360° para CNPJ: {}", cnpj);
     
     let periodo = params.periodo.as_deref().unwrap_or("30d");
@@ -11,6 +11,7 @@ use futures_util::TryStreamExt;
 use crate::database::DatabasePools;
 use crate::errors::ApiError;
 use crate::auth::Claims;
+use crate::models::StatusPedido;
 use std::time::Instant;
 use crate::cards_log;
 
@@ -44,8 +45,9 @@ pub struct OportunidadeRede {
 pub async fn oportunidades_rede(
     params: web::Query<OportunidadesRedeParams>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
+    claims.exigir_cnpj(&params.cnpj)?;
     let _start_time = Instant::now();
     let card_name = "oportunidades-rede";
     
@@ -73,7 +75,7 @@ pub async fn oportunidades_rede(
     };
     
     // Query para classificar o franqueado em grupo ABC
-    let sql_classificacao = r#"
+    let sql_classificacao = format!(r#"
         WITH ranking_franqueados AS (
             SELECT 
                 c.cnpj,
@@ -82,7 +84,8 @@ pub async fn oportunidades_rede(
             FROM pedidos p
             INNER JOIN items i ON p.id = i.pedido_id
             INNER JOIN clientes c ON p.cliente_id = c.id
-            WHERE p.status_pedido IN ('integrado', 'Confirmado ERP', 'Faturado')
+            WHERE p.status_pedido IN ({status_efetivados})
+              AND p.deleted_at IS NULL
               AND p.created_at >= DATEADD(day, -@P1, GETDATE())
               AND c.deleted_at IS NULL
             GROUP BY c.cnpj
@@ -97,7 +100,7 @@ pub async fn oportunidades_rede(
             END as grupo_abc
         FROM ranking_franqueados
         WHERE cnpj = @P2
-    "#;
+    "#, status_efetivados = StatusPedido::efetivados_sql());
     
     // Normalizar CNPJ para formatação padrão
     let cnpj_formatado = if params.cnpj.len() == 14 && !params.cnpj.contains("/") {
@@ -185,7 +188,8 @@ pub async fn oportunidades_rede(
             
             WHERE 
                 c.cnpj = @P1
-                AND p.status_pedido IN ('integrado', 'Confirmado ERP', 'Faturado')
+                AND p.status_pedido IN ({status_efetivados})
+                AND p.deleted_at IS NULL
                 AND p.created_at >= DATEADD(day, -@P2, GETDATE())
                 AND c.deleted_at IS NULL
                 AND pr.status = 1
@@ -211,7 +215,8 @@ pub async fn oportunidades_rede(
                 INNER JOIN clientes c ON p.cliente_id = c.id
                 INNER JOIN produtos pr ON i.produto_id = pr.id
                 WHERE 
-                    p.status_pedido IN ('integrado', 'Confirmado ERP', 'Faturado')
+                    p.status_pedido IN ({status_efetivados})
+                    AND p.deleted_at IS NULL
                     AND p.created_at >= DATEADD(day, -@P2, GETDATE())
                     AND c.deleted_at IS NULL
                     AND pr.status = 1
@@ -227,7 +232,8 @@ pub async fn oportunidades_rede(
                 FROM pedidos p
                 INNER JOIN items i ON p.id = i.pedido_id
                 INNER JOIN clientes c ON p.cliente_id = c.id
-                WHERE p.status_pedido IN ('integrado', 'Confirmado ERP', 'Faturado')
+                WHERE p.status_pedido IN ({status_efetivados})
+                  AND p.deleted_at IS NULL
                   AND p.created_at >= DATEADD(day, -@P2, GETDATE())
                   AND c.deleted_at IS NULL
                 GROUP BY c.cnpj
@@ -328,7 +334,7 @@ pub async fn oportunidades_rede(
                  (impacto_financeiro_estimado / 100.0) * 0.3 + 
                  (CAST(franqueados_compraram AS FLOAT) / 81.0 * 100.0) * 0.2
                 ) DESC, impacto_financeiro_estimado DESC
-    "#, grupo_abc, grupo_abc, grupo_abc);
+    "#, grupo_abc, grupo_abc, grupo_abc, status_efetivados = StatusPedido::efetivados_sql());
     
     let mut query_oport = Query::new(sql_oportunidades);
     query_oport.bind(&cnpj_formatado);
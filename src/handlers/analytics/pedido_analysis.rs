@@ -67,8 +67,9 @@ pub struct ResumoOportunidades {
 pub async fn analisar_pedido_oportunidades(
     request: web::Json<AnalisarPedidoRequest>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
+    claims.exigir_cnpj(&request.cnpj)?;
     log::info!("Analisando oportunidades para pedido do CNPJ: {}", request.cnpj);
     
     let periodo_dias = request.periodo_dias.unwrap_or(90);
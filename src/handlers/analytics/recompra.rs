@@ -11,6 +11,7 @@ use futures_util::TryStreamExt;
 use crate::database::DatabasePools;
 use crate::errors::ApiError;
 use crate::auth::Claims;
+use crate::models::StatusPedido;
 use std::time::Instant;
 use crate::cards_log;
 
@@ -49,8 +50,9 @@ pub struct ProdutoRelacionado {
 pub async fn recompra_inteligente(
     params: web::Query<RecompraParams>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
+    claims.exigir_cnpj(&params.cnpj)?;
     let _start_time = Instant::now();
     let card_name = "recompra-inteligente";
     
@@ -79,7 +81,7 @@ pub async fn recompra_inteligente(
     };
     
     // Query otimizada baseada no algoritmo de IA
-    let sql_recompra = r#"
+    let sql_recompra = format!(r#"
         -- 🎯 CARD 01: RECOMPRA INTELIGENTE - ALGORITMO DE IA PARA SUGESTÕES
         -- Analisa padrão histórico de compras e sugere produtos para recompra
         -- Baseado em frequência de compra vs tempo desde última compra
@@ -112,7 +114,8 @@ pub async fn recompra_inteligente(
             
             WHERE 
                 c.cnpj = @P1
-                AND p.status_pedido IN ('integrado', 'Confirmado ERP', 'Faturado')
+                AND p.status_pedido IN ({status_efetivados})
+                AND p.deleted_at IS NULL
                 AND p.created_at >= DATEADD(day, -@P2, GETDATE())
                 AND c.deleted_at IS NULL
                 AND pr.status = 1
@@ -147,7 +150,7 @@ pub async fn recompra_inteligente(
 
         FROM produtos_recompra
         ORDER BY score_recompra DESC
-    "#;
+    "#, status_efetivados = StatusPedido::efetivados_sql());
     
     // Normalizar CNPJ para formatação padrão
     let cnpj_formatado = if params.cnpj.len() == 14 && !params.cnpj.contains("/") {
@@ -254,7 +257,7 @@ async fn buscar_produtos_relacionados(
     periodo_dias: i32,
 ) -> Result<Vec<ProdutoRelacionado>, ApiError> {
     
-    let sql_relacionados = r#"
+    let sql_relacionados = format!(r#"
         WITH pedidos_com_produto AS (
             SELECT DISTINCT p.id as pedido_id
             FROM pedidos p
@@ -262,7 +265,8 @@ async fn buscar_produtos_relacionados(
             INNER JOIN clientes c ON p.cliente_id = c.id
             WHERE c.cnpj = @P1
               AND i.codigo_produto = @P2
-              AND p.status_pedido IN ('integrado', 'Confirmado ERP', 'Faturado')
+              AND p.status_pedido IN ({status_efetivados})
+              AND p.deleted_at IS NULL
               AND p.created_at >= DATEADD(day, -@P3, GETDATE())
               AND c.deleted_at IS NULL
         )
@@ -278,7 +282,7 @@ async fn buscar_produtos_relacionados(
         HAVING COUNT(*) >= 2
         ORDER BY correlacao_percentual DESC
         OFFSET 0 ROWS FETCH NEXT 10 ROWS ONLY
-    "#;
+    "#, status_efetivados = StatusPedido::efetivados_sql());
     
     let mut query_rel = Query::new(sql_relacionados);
     query_rel.bind(cnpj);
@@ -1,14 +1,18 @@
 // src/handlers/auth_handlers.rs
-// Handlers de autenticação - Login e validação JWT
+// Handlers de autenticação - Login, refresh, logout e validação JWT
 
-use actix_web::{web, HttpResponse, Result};
+use actix_web::{web, HttpRequest, HttpResponse, Result};
 use deadpool_postgres::Pool;
 use serde::{Deserialize, Serialize};
 use bcrypt::{hash, verify, DEFAULT_COST};
 
-use crate::auth::{create_jwt, Claims};
+use crate::auditoria::{self, EventoAuditoria};
+use crate::auth::{create_jwt, Claims, Papel, TokensRevogados};
+use crate::chaves_jwt::ChavesJwt;
 use crate::config::Settings;
 use crate::errors::ApiError;
+use crate::handlers::usuarios::{repositorio, tokens};
+use crate::protecao_login::{ip_cliente, ProtecaoLogin};
 
 #[derive(Debug, Deserialize)]
 pub struct LoginRequest {
@@ -16,48 +20,92 @@ pub struct LoginRequest {
     password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LogoutRequest {
+    refresh_token: Option<String>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct LoginResponse {
     token: String,
     expires_in: i64,
     token_type: String,
+    refresh_token: String,
+    refresh_expires_in: i64,
 }
 
 /// Handler para login de usuário
-/// Valida credenciais e retorna JWT token
+/// Valida credenciais na tabela `api_users` (bcrypt) e retorna access token JWT + refresh token
+/// Falhas repetidas por usuário ou IP atrasam a resposta e geram bloqueio temporário (429)
 pub async fn login(
-    _pool: web::Data<Pool>,
+    req: HttpRequest,
+    pool: web::Data<Pool>,
     settings: web::Data<Settings>,
+    chaves: web::Data<ChavesJwt>,
+    protecao: web::Data<ProtecaoLogin>,
     credentials: web::Json<LoginRequest>,
 ) -> Result<HttpResponse> {
     log::info!("Tentativa de login para usuário: {}", credentials.username);
     
-    // Validar credenciais admin configuradas no .env
-    if credentials.username == settings.admin.username && credentials.password == settings.admin.password {
+    let ip = ip_cliente(&req, &settings.login);
+    if let Err(e) = protecao.verificar(&credentials.username, &ip) {
+        log::warn!("🔒 Login bloqueado para usuário '{}' (IP {})", credentials.username, ip);
+        return Err(e.into());
+    }
+    
+    let client = pool.get().await.map_err(ApiError::from)?;
+    let usuario = repositorio::buscar_por_username(&client, &credentials.username).await?;
+    
+    let usuario = match usuario {
+        Some(usuario) if usuario.active => {
+            let hash = usuario.password_hash.clone();
+            repositorio::verificar_hash(credentials.password.clone(), hash).await?
+                .then_some(usuario)
+        }
+        Some(_) => {
+            log::warn!("Login negado para usuário desativado: {}", credentials.username);
+            repositorio::verificar_hash(credentials.password.clone(), repositorio::HASH_FICTICIO.to_string()).await?;
+            None
+        }
+        None => {
+            repositorio::verificar_hash(credentials.password.clone(), repositorio::HASH_FICTICIO.to_string()).await?;
+            None
+        }
+    };
+    
+    if let Some(usuario) = usuario {
         log::info!("Login autorizado para usuário: {}", credentials.username);
+        protecao.registrar_sucesso(&credentials.username);
         
-        // Criar JWT token usando o mesmo padrão que funciona
-        match create_jwt(&credentials.username, &settings.jwt.secret, settings.jwt.expiration_hours) {
-            Ok(token) => {
-                log::info!("Token JWT gerado com sucesso");
-                
-                Ok(HttpResponse::Ok().json(LoginResponse {
-                    token,
-                    expires_in: settings.jwt.expiration_hours * 3600,
-                    token_type: "Bearer".to_string(),
-                }))
-            },
-            Err(e) => {
-                log::error!("Erro ao gerar token JWT: {:?}", e);
-                Ok(HttpResponse::InternalServerError().json(serde_json::json!({
-                    "success": false,
-                    "message": "Erro interno ao gerar token",
-                    "error": e.to_string()
-                })))
-            }
-        }
+        let (_, refresh_token) = tokens::emitir(&client, usuario.id, None, settings.jwt.refresh_expiration_days).await?;
+        let resposta = emitir_resposta(&usuario, refresh_token, &chaves, &settings)?;
+        log::info!("Token JWT gerado com sucesso");
+        
+        Ok(HttpResponse::Ok().json(resposta))
     } else {
-        log::warn!("Credenciais inválidas para usuário: {}", credentials.username);
+        log::warn!("Credenciais inválidas para usuário: {} (IP {})", credentials.username, ip);
+        
+        let falha = protecao.registrar_falha(&credentials.username, &ip);
+        if let Some((motivo, segundos)) = falha.bloqueio {
+            log::warn!("🔒 Bloqueio de login por {} ({}s): usuário '{}', IP {}", motivo, segundos, credentials.username, ip);
+            auditoria::registrar(&pool, EventoAuditoria {
+                usuario: Some(credentials.username.clone()),
+                metodo: Some(req.method().to_string()),
+                rota: Some(req.path().to_string()),
+                ip: Some(ip),
+                parametros: Some(serde_json::json!({ "motivo": motivo, "bloqueio_segundos": segundos })),
+                status_code: Some(429),
+                ..EventoAuditoria::novo("login_bloqueado", "bloqueado")
+            });
+            return Err(ApiError::LoginBloqueado { segundos }.into());
+        }
+        
+        tokio::time::sleep(falha.atraso).await;
         Ok(HttpResponse::Unauthorized().json(serde_json::json!({
             "success": false,
             "message": "Credenciais inválidas"
@@ -65,6 +113,78 @@ pub async fn login(
     }
 }
 
+/// Handler para renovar o access token
+/// O refresh token é de uso único: a resposta traz um novo, e o anterior deixa de valer.
+/// Papéis e CNPJs são relidos do banco, então alterações administrativas valem na renovação.
+pub async fn refresh(
+    pool: web::Data<Pool>,
+    settings: web::Data<Settings>,
+    chaves: web::Data<ChavesJwt>,
+    req: web::Json<RefreshRequest>,
+) -> Result<HttpResponse> {
+    let mut client = pool.get().await.map_err(ApiError::from)?;
+    let (usuario, refresh_token) = tokens::rotacionar(
+        &mut client,
+        &req.refresh_token,
+        settings.jwt.refresh_expiration_days,
+    ).await?;
+    
+    log::info!("Token renovado para usuário: {}", usuario.username);
+    
+    Ok(HttpResponse::Ok().json(emitir_resposta(&usuario, refresh_token, &chaves, &settings)?))
+}
+
+/// Handler para logout
+/// Revoga o access token atual (`jti`) e, se informado, encerra a sessão do refresh token
+pub async fn logout(
+    pool: web::Data<Pool>,
+    revogados: web::Data<TokensRevogados>,
+    claims: web::ReqData<Claims>,
+    req: Option<web::Json<LogoutRequest>>,
+) -> Result<HttpResponse> {
+    let claims = claims.into_inner();
+    let req = req.map(|r| r.into_inner()).unwrap_or_default();
+    let client = pool.get().await.map_err(ApiError::from)?;
+    
+    // Tokens emitidos antes da revogação não têm jti; esses só expiram
+    if !claims.jti.is_empty() {
+        tokens::revogar_jti(&client, &claims.jti, &claims.sub, claims.exp).await?;
+        revogados.revogar(&claims.jti, claims.exp);
+    }
+    
+    if let Some(refresh_token) = req.refresh_token.as_deref() {
+        tokens::revogar_sessao(&client, refresh_token).await?;
+    }
+    
+    log::info!("Logout do usuário: {}", claims.sub);
+    
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "success": true,
+        "message": "Logout realizado"
+    })))
+}
+
+fn emitir_resposta(usuario: &User, refresh_token: String, chaves: &ChavesJwt, settings: &Settings) -> Result<LoginResponse, ApiError> {
+    let token = create_jwt(
+        &usuario.username,
+        &usuario.papeis(),
+        &usuario.cnpjs,
+        chaves,
+        settings.jwt.access_expiration_minutes,
+    ).map_err(|e| {
+        log::error!("Erro ao gerar token JWT: {:?}", e);
+        ApiError::InternalError(format!("Erro interno ao gerar token: {}", e))
+    })?;
+    
+    Ok(LoginResponse {
+        token,
+        expires_in: settings.jwt.access_expiration_minutes * 60,
+        token_type: "Bearer".to_string(),
+        refresh_token,
+        refresh_expires_in: settings.jwt.refresh_expiration_days * 86400,
+    })
+}
+
 /// Handler para validar token JWT
 /// Retorna informações do token se válido
 pub async fn validate_token(
@@ -76,31 +196,50 @@ pub async fn validate_token(
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "valid": true,
         "username": claims.sub,
+        "roles": claims.roles,
+        "cnpjs": claims.cnpjs,
+        "jti": claims.jti,
         "expires_at": claims.exp,
         "issued_at": claims.iat
     })))
 }
 
-// Estrutura para futura implementação de usuários no banco
+/// JWKS público com as chaves de verificação dos tokens (vazio com HS256)
+/// Outros serviços internos validam nossos tokens sem compartilhar segredo
+pub async fn jwks(chaves: web::Data<ChavesJwt>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "public, max-age=300"))
+        .json(chaves.jwks())
+}
+
+// Usuário da API (tabela `api_users` no PostgreSQL FC)
 #[derive(Debug, Serialize, Deserialize)]
 pub struct User {
     pub id: uuid::Uuid,
     pub username: String,
+    #[serde(skip_serializing)]
     pub password_hash: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
     pub active: bool,
+    pub roles: Vec<String>,
+    pub cnpjs: Vec<String>,
+}
+
+impl User {
+    /// Papéis reconhecidos (valores desconhecidos na tabela são ignorados)
+    pub fn papeis(&self) -> Vec<Papel> {
+        self.roles.iter().filter_map(|r| Papel::from_nome(r)).collect()
+    }
 }
 
 // Função auxiliar para criar hash de senha
-#[allow(dead_code)]
 pub fn hash_password(password: &str) -> Result<String, ApiError> {
     hash(password, DEFAULT_COST)
         .map_err(|_| ApiError::InternalError("Erro ao gerar hash de senha".to_string()))
 }
 
 // Função auxiliar para verificar senha
-#[allow(dead_code)]
 pub fn verify_password(password: &str, hash: &str) -> Result<bool, ApiError> {
     verify(password, hash)
         .map_err(|_| ApiError::InternalError("Erro ao verificar senha".to_string()))
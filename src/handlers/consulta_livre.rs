@@ -0,0 +1,200 @@
+// src/handlers/consulta_livre.rs
+// ⏱️ Execução isolada de SQL livre: transação somente leitura (FC) e conexão dedicada com timeout (SQL Server)
+
+use std::time::Duration;
+
+use bb8::ManageConnection;
+use bb8_tiberius::ConnectionManager;
+use chrono::{NaiveDate, NaiveDateTime};
+use deadpool_postgres::{Client, Transaction};
+use futures_util::TryStreamExt;
+use rust_decimal::Decimal;
+use serde_json::Value;
+use tiberius::numeric::Numeric;
+use tiberius::{Query, QueryItem};
+use tokio_postgres::error::SqlState;
+use tokio::sync::OwnedSemaphorePermit;
+use tokio_postgres::types::{ToSql, Type};
+
+use crate::config::ConsultaLivreConfig;
+use crate::database::SqlServerConexaoLeitura;
+use crate::errors::ApiError;
+use crate::handlers::portal_handlers::{bind_json_params, row_to_json};
+
+/// Conexão tiberius aberta fora do pool
+pub type ConexaoSqlServer = <ConnectionManager as ManageConnection>::Connection;
+
+/// Parâmetro posicional já convertido (`$n` no PostgreSQL, `@Pn` no SQL Server).
+/// Nulos mantêm o tipo para que o banco saiba como declarar o parâmetro.
+#[derive(Debug, Clone)]
+pub enum ValorParametro {
+    Texto(Option<String>),
+    Inteiro(Option<i64>),
+    Real(Option<f64>),
+    Decimal(Option<Decimal>),
+    Booleano(Option<bool>),
+    Data(Option<NaiveDate>),
+    DataHora(Option<NaiveDateTime>),
+}
+
+impl ValorParametro {
+    /// Parâmetro JSON do SQL livre (mesmas regras de `bind_json_params`: outros tipos são ignorados)
+    pub fn do_json(valor: &Value) -> Option<Self> {
+        match valor {
+            Value::String(s) => Some(ValorParametro::Texto(Some(s.clone()))),
+            Value::Number(n) => n.as_i64()
+                .map(|i| ValorParametro::Inteiro(Some(i)))
+                .or_else(|| n.as_f64().map(|f| ValorParametro::Real(Some(f)))),
+            Value::Bool(b) => Some(ValorParametro::Booleano(Some(*b))),
+            _ => None,
+        }
+    }
+
+    /// Tipo declarado no `prepare_typed`, evita depender da inferência do PostgreSQL
+    pub fn tipo_postgres(&self) -> Type {
+        match self {
+            ValorParametro::Texto(_) => Type::TEXT,
+            ValorParametro::Inteiro(_) => Type::INT8,
+            ValorParametro::Real(_) => Type::FLOAT8,
+            ValorParametro::Decimal(_) => Type::NUMERIC,
+            ValorParametro::Booleano(_) => Type::BOOL,
+            ValorParametro::Data(_) => Type::DATE,
+            ValorParametro::DataHora(_) => Type::TIMESTAMP,
+        }
+    }
+
+    pub fn como_postgres(&self) -> &(dyn ToSql + Sync) {
+        match self {
+            ValorParametro::Texto(v) => v,
+            ValorParametro::Inteiro(v) => v,
+            ValorParametro::Real(v) => v,
+            ValorParametro::Decimal(v) => v,
+            ValorParametro::Booleano(v) => v,
+            ValorParametro::Data(v) => v,
+            ValorParametro::DataHora(v) => v,
+        }
+    }
+
+    pub fn vincular_sqlserver(&self, query_obj: &mut Query<'_>) {
+        match self.clone() {
+            ValorParametro::Texto(v) => query_obj.bind(v),
+            ValorParametro::Inteiro(v) => query_obj.bind(v),
+            ValorParametro::Real(v) => query_obj.bind(v),
+            ValorParametro::Decimal(v) => query_obj.bind(v.map(|d| Numeric::new_with_scale(d.mantissa(), d.scale() as u8))),
+            ValorParametro::Booleano(v) => query_obj.bind(v),
+            ValorParametro::Data(v) => query_obj.bind(v),
+            ValorParametro::DataHora(v) => query_obj.bind(v),
+        }
+    }
+}
+
+/// Parâmetros JSON (`params`) do SQL livre convertidos para o streaming
+pub fn parametros_livres(params: Option<&[Value]>) -> Vec<ValorParametro> {
+    params.unwrap_or_default().iter().filter_map(ValorParametro::do_json).collect()
+}
+
+/// Abre uma transação `READ ONLY` com `statement_timeout` e `lock_timeout` locais.
+/// A transação nunca é confirmada: ao sair do escopo é desfeita e a conexão volta limpa ao pool.
+pub async fn transacao_somente_leitura<'a>(
+    client: &'a mut Client,
+    config: &ConsultaLivreConfig,
+) -> Result<Transaction<'a>, tokio_postgres::Error> {
+    let tx = client.build_transaction().read_only(true).start().await?;
+    let ms = config.timeout_segundos * 1000;
+    tx.batch_execute(&format!(
+        "SET LOCAL statement_timeout = {ms}; SET LOCAL lock_timeout = {ms}; SET LOCAL idle_in_transaction_session_timeout = {ms}"
+    )).await?;
+    Ok(tx)
+}
+
+/// Consulta cancelada pelo `statement_timeout`/`lock_timeout`
+pub fn tempo_esgotado(erro: &tokio_postgres::Error) -> bool {
+    matches!(erro.code(), Some(c) if *c == SqlState::QUERY_CANCELED || *c == SqlState::LOCK_NOT_AVAILABLE)
+}
+
+/// Tentativa de escrita barrada pela transação `READ ONLY` (ex.: função que grava dados)
+pub fn escrita_bloqueada(erro: &tokio_postgres::Error) -> bool {
+    erro.code() == Some(&SqlState::READ_ONLY_SQL_TRANSACTION)
+}
+
+/// Converte o erro do PostgreSQL durante o SQL livre em `ApiError`
+pub fn erro_postgres(erro: tokio_postgres::Error, config: &ConsultaLivreConfig) -> ApiError {
+    if tempo_esgotado(&erro) {
+        ApiError::TempoConsultaEsgotado { segundos: config.timeout_segundos }
+    } else if escrita_bloqueada(&erro) {
+        ApiError::BadRequest(format!("A consulta tentou gravar dados: {}", erro))
+    } else {
+        ApiError::Database(format!("Erro ao executar query: {}", erro))
+    }
+}
+
+/// Abre a conexão dedicada ao SQL livre com isolamento e `LOCK_TIMEOUT` configurados.
+/// Espera até 5s por uma vaga (`CONSULTA_LIVRE_MAX_CONEXOES`); a vaga é liberada quando o permit é descartado.
+pub async fn abrir_sqlserver(
+    conexao: &SqlServerConexaoLeitura,
+    config: &ConsultaLivreConfig,
+) -> Result<(ConexaoSqlServer, OwnedSemaphorePermit), ApiError> {
+    let vaga = tokio::time::timeout(Duration::from_secs(5), conexao.vagas.clone().acquire_owned()).await
+        .map_err(|_| ApiError::Database("Limite de conexões de SQL livre atingido; tente novamente".to_string()))?
+        .map_err(|e| ApiError::Database(format!("Erro ao reservar conexão: {}", e)))?;
+
+    let mut conn = conexao.manager.connect().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no SQL Server: {}", e)))?;
+
+    conn.simple_query(format!(
+        "SET TRANSACTION ISOLATION LEVEL {}; SET LOCK_TIMEOUT {};",
+        config.isolamento_sqlserver,
+        config.timeout_segundos * 1000
+    )).await
+        .map_err(|e| ApiError::Database(format!("Erro ao configurar sessão: {}", e)))?
+        .into_results().await
+        .map_err(|e| ApiError::Database(format!("Erro ao configurar sessão: {}", e)))?;
+
+    Ok((conn, vaga))
+}
+
+/// Executa SQL livre no SQL Server em conexão dedicada e retorna até `max_linhas` linhas
+/// (o `bool` indica se o resultado foi truncado).
+/// Ao estourar o tempo a conexão é fechada, o que faz o servidor abortar a consulta e liberar os locks.
+pub async fn consultar_sqlserver(
+    conexao: &SqlServerConexaoLeitura,
+    sql: &str,
+    params: Option<&[Value]>,
+    config: &ConsultaLivreConfig,
+) -> Result<(Vec<Value>, bool), ApiError> {
+    let execucao = async {
+        let (mut conn, _vaga) = abrir_sqlserver(conexao, config).await?;
+
+        let mut query_obj = Query::new(sql);
+        if let Some(params) = params {
+            bind_json_params(&mut query_obj, params);
+        }
+
+        let mut stream = query_obj.query(&mut conn).await
+            .map_err(|e| {
+                log::error!("❌ Erro ao executar query SQL Server: {}", e);
+                ApiError::Database(format!("Erro ao executar query: {}", e))
+            })?;
+
+        let mut rows = Vec::new();
+        while let Some(item) = stream.try_next().await
+            .map_err(|e| ApiError::Database(format!("Erro ao ler resultados: {}", e)))? {
+            if let QueryItem::Row(row) = item {
+                if rows.len() == config.max_linhas {
+                    // Descartar o restante: a conexão dedicada é fechada ao sair
+                    return Ok((rows, true));
+                }
+                rows.push(row_to_json(&row));
+            }
+        }
+        Ok((rows, false))
+    };
+
+    match tokio::time::timeout(Duration::from_secs(config.timeout_segundos), execucao).await {
+        Ok(resultado) => resultado,
+        Err(_) => {
+            log::warn!("⏱️ SQL livre cancelado após {}s: {}", config.timeout_segundos, sql);
+            Err(ApiError::TempoConsultaEsgotado { segundos: config.timeout_segundos })
+        }
+    }
+}
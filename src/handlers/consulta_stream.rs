@@ -0,0 +1,461 @@
+// src/handlers/consulta_stream.rs
+// 🌊 Streaming do SQL livre: linhas escritas no corpo HTTP à medida que chegam do banco
+
+use std::future::Future;
+use std::time::Duration;
+
+use actix_web::{error::ErrorInternalServerError, web::Bytes, HttpRequest, HttpResponse};
+use deadpool_postgres::Client;
+use futures_util::TryStreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tiberius::{Query, QueryItem};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{timeout_at, Instant};
+
+use crate::config::ConsultaLivreConfig;
+use crate::database::SqlServerConexaoLeitura;
+use crate::errors::ApiError;
+use crate::handlers::consulta_livre::{abrir_sqlserver, erro_postgres, transacao_somente_leitura, ValorParametro};
+use crate::handlers::formatos_saida::{Coluna, EscritorCsv, EscritorParquet, TipoColuna};
+use crate::handlers::portal_handlers::linha_para_valores as valores_sqlserver;
+use crate::handlers::query_handlers::linha_para_valores as valores_postgres;
+
+/// Formato de saída do SQL livre (`?formato=` ou header `Accept`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FormatoSaida {
+    /// Resposta única (padrão, compatível com versões anteriores)
+    Json,
+    /// Uma linha JSON por registro (`application/x-ndjson`)
+    Ndjson,
+    /// Mesmo envelope do `json`, enviado em partes (`data` antes de `count`)
+    JsonStream,
+    /// CSV com cabeçalho (`text/csv`)
+    Csv,
+    /// Parquet com os tipos das colunas (`application/vnd.apache.parquet`)
+    Parquet,
+}
+
+impl FormatoSaida {
+    pub fn streaming(&self) -> bool {
+        *self != FormatoSaida::Json
+    }
+
+    fn content_type(&self) -> &'static str {
+        match self {
+            FormatoSaida::Ndjson => "application/x-ndjson",
+            FormatoSaida::Csv => "text/csv; charset=utf-8",
+            FormatoSaida::Parquet => "application/vnd.apache.parquet",
+            _ => "application/json",
+        }
+    }
+
+    fn arquivo(&self) -> Option<&'static str> {
+        match self {
+            FormatoSaida::Csv => Some("attachment; filename=\"consulta.csv\""),
+            FormatoSaida::Parquet => Some("attachment; filename=\"consulta.parquet\""),
+            _ => None,
+        }
+    }
+
+    fn do_accept(accept: &str) -> Self {
+        let accept = accept.to_lowercase();
+        if accept.contains("text/csv") {
+            FormatoSaida::Csv
+        } else if accept.contains("parquet") {
+            FormatoSaida::Parquet
+        } else if accept.contains("application/x-ndjson") {
+            FormatoSaida::Ndjson
+        } else {
+            FormatoSaida::Json
+        }
+    }
+}
+
+/// Separador decimal do CSV (`?decimal=`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SeparadorDecimal {
+    #[default]
+    Ponto,
+    Virgula,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpcoesSaida {
+    pub formato: Option<FormatoSaida>,
+    /// Delimitador do CSV (padrão `,`, ou `;` com `decimal=virgula`)
+    pub delimitador: Option<char>,
+    #[serde(default)]
+    pub decimal: SeparadorDecimal,
+}
+
+impl OpcoesSaida {
+    /// `?formato=` tem precedência; sem ele, o header `Accept` decide
+    pub fn formato(&self, req: &HttpRequest) -> FormatoSaida {
+        self.formato.unwrap_or_else(|| {
+            req.headers().get("Accept")
+                .and_then(|v| v.to_str().ok())
+                .map(FormatoSaida::do_accept)
+                .unwrap_or(FormatoSaida::Json)
+        })
+    }
+
+    fn codificador(&self, formato: FormatoSaida) -> Result<Codificador, ApiError> {
+        Ok(match formato {
+            FormatoSaida::Json | FormatoSaida::JsonStream => Codificador::JsonStream,
+            FormatoSaida::Ndjson => Codificador::Ndjson,
+            FormatoSaida::Parquet => Codificador::Parquet(None),
+            FormatoSaida::Csv => {
+                let decimal_virgula = self.decimal == SeparadorDecimal::Virgula;
+                let delimitador = self.delimitador.unwrap_or(if decimal_virgula { ';' } else { ',' });
+                if !delimitador.is_ascii() || matches!(delimitador, '"' | '\n' | '\r') {
+                    return Err(ApiError::BadRequest(format!("Delimitador CSV inválido: {:?}", delimitador)));
+                }
+                Codificador::Csv(EscritorCsv::new(delimitador as u8, decimal_virgula))
+            }
+        })
+    }
+}
+
+enum Codificador {
+    Ndjson,
+    JsonStream,
+    Csv(EscritorCsv),
+    /// Criado ao iniciar, quando as colunas já são conhecidas
+    Parquet(Option<Box<EscritorParquet>>),
+}
+
+/// Lado produtor do streaming, executado em task própria.
+/// A resposta HTTP só é iniciada na primeira linha (ou no fim): erros anteriores viram o status de erro normal.
+pub struct Saida {
+    codificador: Codificador,
+    colunas: Vec<Coluna>,
+    max_linhas: usize,
+    linhas: usize,
+    truncado: bool,
+    abortado: bool,
+    inicio: Option<oneshot::Sender<Result<(), ApiError>>>,
+    canal: mpsc::Sender<Result<Bytes, String>>,
+}
+
+impl Saida {
+    /// Colunas do resultado (nome e tipo); apenas o primeiro conjunto é considerado
+    pub fn colunas(&mut self, colunas: Vec<Coluna>) {
+        if self.colunas.is_empty() {
+            self.colunas = colunas;
+        }
+    }
+
+    /// Escreve uma linha; `false` quando o limite foi atingido ou o cliente desconectou
+    pub async fn linha(&mut self, valores: Vec<Value>) -> bool {
+        if self.linhas == self.max_linhas {
+            self.truncado = true;
+            return false;
+        }
+
+        let mut bytes = Vec::new();
+        if !self.iniciar(&mut bytes) {
+            return false;
+        }
+        match &mut self.codificador {
+            Codificador::Ndjson => {
+                bytes.extend(objeto(&self.colunas, valores).to_string().into_bytes());
+                bytes.push(b'\n');
+            }
+            Codificador::JsonStream => {
+                if self.linhas > 0 {
+                    bytes.push(b',');
+                }
+                bytes.extend(objeto(&self.colunas, valores).to_string().into_bytes());
+            }
+            Codificador::Csv(csv) => bytes.extend(csv.linha(&valores)),
+            Codificador::Parquet(parquet) => {
+                let grupo = parquet.as_mut().map(|p| p.linha(&valores));
+                match grupo {
+                    Some(Ok(grupo)) => bytes.extend(grupo),
+                    Some(Err(e)) => return self.abortar(e).await,
+                    None => return self.abortar("Parquet não iniciado".to_string()).await,
+                }
+            }
+        }
+
+        self.linhas += 1;
+        bytes.is_empty() || self.canal.send(Ok(Bytes::from(bytes))).await.is_ok()
+    }
+
+    /// Encerra a resposta. Antes da primeira linha, um erro vira a resposta HTTP de erro;
+    /// depois dela, é informado no fechamento (`error` no envelope ou na linha `_meta`)
+    /// ou, em CSV e Parquet, pela interrupção da transferência.
+    pub async fn finalizar(mut self, erro: Option<ApiError>) {
+        if self.abortado {
+            return;
+        }
+        let erro = match (self.inicio.take(), erro) {
+            (Some(inicio), Some(erro)) => {
+                let _ = inicio.send(Err(erro));
+                return;
+            }
+            (inicio, erro) => {
+                self.inicio = inicio;
+                erro
+            }
+        };
+
+        let mut bytes = Vec::new();
+        if !self.iniciar(&mut bytes) {
+            return;
+        }
+
+        let erro = erro.map(|e| {
+            log::warn!("⚠️ SQL livre interrompido após {} linhas: {}", self.linhas, e);
+            e.to_string()
+        });
+        let fim = json!({
+            "count": self.linhas,
+            "truncated": self.truncado,
+            "max_rows": self.max_linhas,
+            "success": erro.is_none(),
+            "error": erro,
+        });
+        match std::mem::replace(&mut self.codificador, Codificador::Ndjson) {
+            Codificador::Ndjson => {
+                bytes.extend(json!({ "_meta": fim }).to_string().into_bytes());
+                bytes.push(b'\n');
+            }
+            Codificador::JsonStream => {
+                // Fecha `data` e acrescenta os campos do envelope, sem a chave de abertura
+                bytes.extend_from_slice(b"],");
+                let fim = fim.to_string();
+                bytes.extend_from_slice(&fim.as_bytes()[1..]);
+            }
+            Codificador::Csv(_) | Codificador::Parquet(_) if erro.is_some() => {
+                // Sem onde registrar o erro no arquivo: a transferência é interrompida
+                self.abortar(erro.unwrap_or_default()).await;
+                return;
+            }
+            Codificador::Csv(_) => {}
+            Codificador::Parquet(parquet) => {
+                match parquet.map(|p| p.finalizar(self.truncado)) {
+                    Some(Ok(rodape)) => bytes.extend(rodape),
+                    Some(Err(e)) => {
+                        self.abortar(e).await;
+                        return;
+                    }
+                    None => {}
+                }
+            }
+        }
+        let _ = self.canal.send(Ok(Bytes::from(bytes))).await;
+    }
+
+    /// Libera a resposta 200 e escreve a abertura do formato; `false` se o formato não pôde ser iniciado
+    fn iniciar(&mut self, bytes: &mut Vec<u8>) -> bool {
+        let inicio = match self.inicio.take() {
+            Some(inicio) => inicio,
+            None => return !self.abortado,
+        };
+        match &mut self.codificador {
+            Codificador::JsonStream => bytes.extend_from_slice(br#"{"data":["#),
+            Codificador::Csv(csv) => bytes.extend(csv.cabecalho(&self.colunas)),
+            Codificador::Parquet(parquet) => match EscritorParquet::new(&self.colunas) {
+                Ok(escritor) => *parquet = Some(Box::new(escritor)),
+                Err(e) => {
+                    self.abortado = true;
+                    let _ = inicio.send(Err(ApiError::InternalError(e)));
+                    return false;
+                }
+            },
+            Codificador::Ndjson => {}
+        }
+        let _ = inicio.send(Ok(()));
+        true
+    }
+
+    /// Interrompe a transferência: o cliente recebe um corpo incompleto
+    async fn abortar(&mut self, erro: String) -> bool {
+        log::error!("❌ Streaming do SQL livre abortado após {} linhas: {}", self.linhas, erro);
+        self.abortado = true;
+        let _ = self.canal.send(Err(erro)).await;
+        false
+    }
+}
+
+/// Objeto JSON da linha (mesma forma da resposta `json`)
+fn objeto(colunas: &[Coluna], valores: Vec<Value>) -> Value {
+    Value::Object(colunas.iter().map(|c| c.nome.clone()).zip(valores).collect())
+}
+
+/// Executa o produtor em task própria e devolve a resposta em streaming.
+/// O canal limitado aplica contrapressão: o banco só é lido no ritmo em que o cliente consome.
+pub async fn responder<F, Fut>(
+    formato: FormatoSaida,
+    opcoes: &OpcoesSaida,
+    max_linhas: usize,
+    produtor: F,
+) -> Result<HttpResponse, ApiError>
+where
+    F: FnOnce(Saida) -> Fut,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let codificador = opcoes.codificador(formato)?;
+    let (inicio, inicio_rx) = oneshot::channel();
+    let (canal, rx) = mpsc::channel(64);
+
+    tokio::spawn(produtor(Saida {
+        codificador,
+        colunas: Vec::new(),
+        max_linhas,
+        linhas: 0,
+        truncado: false,
+        abortado: false,
+        inicio: Some(inicio),
+        canal,
+    }));
+
+    match inicio_rx.await {
+        Ok(Ok(())) => {
+            let corpo = futures_util::stream::unfold(rx, |mut rx| async move {
+                rx.recv().await.map(|parte| (parte.map_err(ErrorInternalServerError), rx))
+            });
+            let mut resposta = HttpResponse::Ok();
+            resposta
+                .content_type(formato.content_type())
+                .insert_header(("X-Max-Rows", max_linhas.to_string()));
+            if let Some(arquivo) = formato.arquivo() {
+                resposta.insert_header(("Content-Disposition", arquivo));
+            }
+            Ok(resposta.streaming(corpo))
+        }
+        Ok(Err(erro)) => Err(erro),
+        Err(_) => Err(ApiError::InternalError("Consulta interrompida antes da resposta".to_string())),
+    }
+}
+
+/// Linhas buscadas por `Execute` no portal do PostgreSQL
+const LOTE_POSTGRES: i32 = 1000;
+
+/// Prazo da resposta inteira: nunca menor que o tempo limite da consulta
+fn prazo_exportacao(config: &ConsultaLivreConfig) -> Instant {
+    Instant::now() + Duration::from_secs(config.timeout_exportacao_segundos.max(config.timeout_segundos))
+}
+
+fn exportacao_esgotada(config: &ConsultaLivreConfig) -> ApiError {
+    ApiError::TempoConsultaEsgotado { segundos: config.timeout_exportacao_segundos.max(config.timeout_segundos) }
+}
+
+/// Produtor PostgreSQL: portal de cursor na transação somente leitura, lido em lotes até `max_linhas + 1`.
+/// O `statement_timeout` vale para cada lote, então o tempo de download do cliente não conta como consulta;
+/// a resposta inteira é limitada por `CONSULTA_LIVRE_TIMEOUT_EXPORTACAO_SEGUNDOS`.
+pub async fn stream_postgres(
+    mut client: Client,
+    sql: String,
+    params: Vec<ValorParametro>,
+    config: ConsultaLivreConfig,
+    mut saida: Saida,
+) {
+    let prazo = prazo_exportacao(&config);
+    let tx = match transacao_somente_leitura(&mut client, &config).await {
+        Ok(tx) => tx,
+        Err(e) => return saida.finalizar(Some(erro_postgres(e, &config))).await,
+    };
+    // Entre um lote e outro a transação fica ociosa enquanto o cliente consome
+    let ociosa_ms = config.timeout_exportacao_segundos.max(config.timeout_segundos) * 1000;
+    if let Err(e) = tx.batch_execute(&format!("SET LOCAL idle_in_transaction_session_timeout = {ociosa_ms}")).await {
+        return saida.finalizar(Some(erro_postgres(e, &config))).await;
+    }
+
+    let tipos: Vec<_> = params.iter().map(ValorParametro::tipo_postgres).collect();
+    let stmt = match tx.prepare_typed(&sql, &tipos).await {
+        Ok(stmt) => stmt,
+        Err(e) => return saida.finalizar(Some(erro_postgres(e, &config))).await,
+    };
+    saida.colunas(stmt.columns().iter().map(|c| Coluna {
+        nome: c.name().to_string(),
+        tipo: TipoColuna::do_postgres(c),
+    }).collect());
+
+    let valores: Vec<_> = params.iter().map(ValorParametro::como_postgres).collect();
+    let portal = match tx.bind(&stmt, &valores).await {
+        Ok(portal) => portal,
+        Err(e) => return saida.finalizar(Some(erro_postgres(e, &config))).await,
+    };
+
+    let mut restantes = config.max_linhas.saturating_add(1);
+    while restantes > 0 {
+        let lote = i32::try_from(restantes).unwrap_or(i32::MAX).min(LOTE_POSTGRES);
+        let linhas = match timeout_at(prazo, tx.query_portal(&portal, lote)).await {
+            Ok(Ok(linhas)) => linhas,
+            Ok(Err(e)) => return saida.finalizar(Some(erro_postgres(e, &config))).await,
+            Err(_) => return saida.finalizar(Some(exportacao_esgotada(&config))).await,
+        };
+        let recebidas = linhas.len();
+        for row in linhas {
+            match timeout_at(prazo, saida.linha(valores_postgres(&row))).await {
+                Ok(true) => {}
+                Ok(false) => return saida.finalizar(None).await,
+                Err(_) => return saida.finalizar(Some(exportacao_esgotada(&config))).await,
+            }
+        }
+        if recebidas < lote as usize {
+            break;
+        }
+        restantes -= recebidas;
+    }
+    saida.finalizar(None).await
+}
+
+/// Produtor SQL Server: conexão dedicada; ao atingir o limite ou o tempo, a conexão é fechada.
+/// `CONSULTA_LIVRE_TIMEOUT_SEGUNDOS` vale até o primeiro resultado (metadados ou linha);
+/// a partir daí, o envio ao cliente é limitado por `CONSULTA_LIVRE_TIMEOUT_EXPORTACAO_SEGUNDOS`.
+pub async fn stream_sqlserver(
+    conexao: SqlServerConexaoLeitura,
+    sql: String,
+    params: Vec<ValorParametro>,
+    config: ConsultaLivreConfig,
+    mut saida: Saida,
+) {
+    let prazo_consulta = Instant::now() + Duration::from_secs(config.timeout_segundos);
+    let consulta_esgotada = || ApiError::TempoConsultaEsgotado { segundos: config.timeout_segundos };
+
+    let execucao = async {
+        let (mut conn, _vaga) = timeout_at(prazo_consulta, abrir_sqlserver(&conexao, &config)).await
+            .map_err(|_| consulta_esgotada())??;
+
+        let mut query_obj = Query::new(sql.as_str());
+        for param in &params {
+            param.vincular_sqlserver(&mut query_obj);
+        }
+
+        let mut stream = timeout_at(prazo_consulta, query_obj.query(&mut conn)).await
+            .map_err(|_| consulta_esgotada())?
+            .map_err(|e| ApiError::Database(format!("Erro ao executar query: {}", e)))?;
+
+        let mut proximo = timeout_at(prazo_consulta, stream.try_next()).await
+            .map_err(|_| consulta_esgotada())?
+            .map_err(|e| ApiError::Database(format!("Erro ao ler resultados: {}", e)))?;
+        while let Some(item) = proximo {
+            match item {
+                QueryItem::Metadata(meta) => saida.colunas(meta.columns().iter().map(|c| Coluna {
+                    nome: c.name().to_string(),
+                    tipo: TipoColuna::do_sqlserver(c.column_type()),
+                }).collect()),
+                QueryItem::Row(row) => {
+                    if !saida.linha(valores_sqlserver(&row)).await {
+                        break;
+                    }
+                }
+            }
+            proximo = stream.try_next().await
+                .map_err(|e| ApiError::Database(format!("Erro ao ler resultados: {}", e)))?;
+        }
+        Ok(())
+    };
+
+    let erro = match timeout_at(prazo_exportacao(&config), execucao).await {
+        Ok(resultado) => resultado.err(),
+        Err(_) => Some(exportacao_esgotada(&config)),
+    };
+    saida.finalizar(erro).await
+}
@@ -0,0 +1,229 @@
+//! 🚀 Execução de consultas salvas (`/queries`) e manutenção do catálogo (`/admin/consultas-salvas`)
+
+use actix_web::{web, HttpRequest, HttpResponse};
+use deadpool_postgres::Pool;
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use super::parametros::{converter_todos, validar_definicoes, TipoParametro};
+use super::repositorio::{self, BancoConsulta, ConsultaSalva, DadosConsulta};
+use crate::auditoria::RegistroAuditoria;
+use crate::auth::{Claims, Papel};
+use crate::config::Settings;
+use crate::database::DatabasePools;
+use crate::errors::ApiError;
+use crate::handlers::consulta_livre::ValorParametro;
+use crate::handlers::consulta_stream::{responder, stream_postgres, stream_sqlserver, OpcoesSaida};
+use crate::handlers::validacao_sql::{parametros_posicionais, validar_somente_leitura};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ExecutarConsultaRequest {
+    #[serde(default)]
+    pub params: Map<String, Value>,
+}
+
+/// Consultas que o token pode executar (sem o SQL)
+pub async fn listar_disponiveis(
+    pool: web::Data<Pool>,
+    claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    let client = pool.get().await?;
+    let consultas: Vec<Value> = repositorio::listar(&client).await?
+        .into_iter()
+        .filter(|c| pode_executar(c, &claims))
+        .map(|c| json!({
+            "name": c.name,
+            "description": c.description,
+            "database": c.database,
+            "parameters": c.parameters,
+        }))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "data": consultas,
+        "count": consultas.len()
+    })))
+}
+
+/// Executa a consulta salva com os parâmetros informados.
+/// Usa a mesma infraestrutura do SQL livre: conexão somente leitura, tempo limite, limite de linhas e formatos de saída.
+#[allow(clippy::too_many_arguments)]
+pub async fn executar_consulta(
+    pool: web::Data<Pool>,
+    pools: web::Data<DatabasePools>,
+    settings: web::Data<Settings>,
+    nome: web::Path<String>,
+    corpo: Option<web::Json<ExecutarConsultaRequest>>,
+    opcoes: web::Query<OpcoesSaida>,
+    req: HttpRequest,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
+) -> Result<HttpResponse, ApiError> {
+    let client = pool.get().await?;
+    let consulta = repositorio::buscar(&client, &nome).await?.ok_or(ApiError::NotFound)?;
+    drop(client);
+
+    if !pode_executar(&consulta, &claims) {
+        log::warn!("🚫 '{}' sem papel para executar a consulta salva '{}'", claims.sub, consulta.name);
+        return Err(ApiError::Unauthorized);
+    }
+
+    let enviados = corpo.map(|c| c.into_inner().params).unwrap_or_default();
+    let registrados: Vec<Value> = consulta.parameters.iter()
+        .map(|p| enviados.get(&p.name).cloned().unwrap_or(Value::Null))
+        .collect();
+    auditoria.sql(&consulta.sql, Some(&registrados));
+
+    // O catálogo pode ter sido alterado direto no banco: valida de novo antes de executar
+    validar_somente_leitura(&consulta.sql, consulta.database.dialeto())
+        .map_err(|motivo| ApiError::InternalError(format!("Consulta salva '{}' inválida: {}", consulta.name, motivo)))?;
+
+    let valores = converter_todos(&consulta.parameters, &enviados).map_err(ApiError::BadRequest)?;
+    exigir_cnpjs(&consulta, &valores, &claims)?;
+
+    log::info!("🔍 '{}' executando consulta salva '{}' ({})", claims.sub, consulta.name, consulta.database.as_str());
+
+    let formato = opcoes.formato(&req);
+    let (sql, config) = (consulta.sql, settings.consulta_livre.clone());
+    match consulta.database {
+        BancoConsulta::Fc => {
+            let client = pools.postgres_fc_leitura.get().await?;
+            responder(formato, &opcoes, config.max_linhas, move |saida| {
+                stream_postgres(client, sql, valores, config, saida)
+            }).await
+        }
+        BancoConsulta::Portal | BancoConsulta::Protheus => {
+            let conexao = if consulta.database == BancoConsulta::Portal {
+                pools.sqlserver_portal_leitura.clone()
+            } else {
+                pools.sqlserver_protheus_leitura.clone()
+            };
+            responder(formato, &opcoes, config.max_linhas, move |saida| {
+                stream_sqlserver(conexao, sql, valores, config, saida)
+            }).await
+        }
+    }
+}
+
+/// Lista o catálogo completo, com SQL e papéis
+pub async fn listar_consultas(
+    pool: web::Data<Pool>,
+    _claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    let client = pool.get().await?;
+    let consultas = repositorio::listar(&client).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "data": consultas,
+        "count": consultas.len()
+    })))
+}
+
+/// Cria ou substitui uma consulta salva depois de validar SQL, parâmetros e papéis
+pub async fn salvar_consulta(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    nome: web::Path<String>,
+    dados: web::Json<DadosConsulta>,
+) -> Result<HttpResponse, ApiError> {
+    let nome = nome.into_inner();
+    let mut dados = dados.into_inner();
+    dados.sql = dados.sql.trim().to_string();
+    validar_consulta(&nome, &dados)?;
+
+    let client = pool.get().await?;
+    let criada = repositorio::salvar(&client, &nome, &dados, &claims.sub).await?;
+    let consulta = repositorio::buscar(&client, &nome).await?.ok_or(ApiError::NotFound)?;
+
+    log::info!("📚 Consulta salva '{}' {} por '{}'", nome, if criada { "criada" } else { "alterada" }, claims.sub);
+
+    let mut resposta = if criada { HttpResponse::Created() } else { HttpResponse::Ok() };
+    Ok(resposta.json(json!({
+        "success": true,
+        "data": consulta
+    })))
+}
+
+/// Remove uma consulta salva do catálogo
+pub async fn remover_consulta(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    nome: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let nome = nome.into_inner();
+    let client = pool.get().await?;
+    if !repositorio::remover(&client, &nome).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    log::info!("🗑️ Consulta salva '{}' removida por '{}'", nome, claims.sub);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "message": "Consulta removida"
+    })))
+}
+
+/// Admin executa qualquer consulta; os demais precisam de um dos papéis da consulta
+fn pode_executar(consulta: &ConsultaSalva, claims: &Claims) -> bool {
+    claims.tem_papel(Papel::Admin) || consulta.roles.iter().any(|p| claims.tem_papel(*p))
+}
+
+/// Tokens vinculados a CNPJ só executam consultas filtradas por parâmetros `cnpj` dentro do vínculo
+fn exigir_cnpjs(consulta: &ConsultaSalva, valores: &[ValorParametro], claims: &Claims) -> Result<(), ApiError> {
+    if !claims.restrito_a_cnpj() {
+        return Ok(());
+    }
+
+    let cnpjs: Vec<Option<&String>> = consulta.parameters.iter()
+        .zip(valores)
+        .filter(|(p, _)| p.tipo == TipoParametro::Cnpj)
+        .map(|(_, v)| match v {
+            ValorParametro::Texto(cnpj) => cnpj.as_ref(),
+            _ => None,
+        })
+        .collect();
+    if cnpjs.is_empty() {
+        return claims.exigir_acesso_irrestrito();
+    }
+
+    for cnpj in cnpjs {
+        match cnpj {
+            Some(cnpj) => claims.exigir_cnpj(cnpj)?,
+            None => return claims.exigir_acesso_irrestrito(),
+        }
+    }
+    Ok(())
+}
+
+fn validar_consulta(nome: &str, dados: &DadosConsulta) -> Result<(), ApiError> {
+    let nome_valido = !nome.is_empty()
+        && nome.len() <= 100
+        && nome.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-');
+    if !nome_valido {
+        return Err(ApiError::BadRequest("name deve ter entre 1 e 100 caracteres (a-z, 0-9, _ e -)".to_string()));
+    }
+    if dados.roles.is_empty() {
+        return Err(ApiError::BadRequest("Informe ao menos um papel em roles".to_string()));
+    }
+
+    let dialeto = dados.database.dialeto();
+    validar_somente_leitura(&dados.sql, dialeto)
+        .map_err(|motivo| ApiError::BadRequest(format!("SQL rejeitado: {}", motivo)))?;
+    validar_definicoes(&dados.parameters).map_err(ApiError::BadRequest)?;
+
+    // Cada parâmetro declarado precisa aparecer no SQL, e o SQL não pode usar posições não declaradas
+    let usados = parametros_posicionais(&dados.sql, dialeto).map_err(ApiError::BadRequest)?;
+    let declarados: Vec<usize> = (1..=dados.parameters.len()).collect();
+    if !usados.iter().eq(declarados.iter()) {
+        let marcador = if dados.database == BancoConsulta::Fc { "$" } else { "@P" };
+        let usados: Vec<String> = usados.iter().map(|i| format!("{}{}", marcador, i)).collect();
+        return Err(ApiError::BadRequest(format!(
+            "O SQL usa {:?}, mas declara {} parâmetro(s): cada parâmetro deve aparecer como {}1, {}2, ... na ordem da lista",
+            usados, dados.parameters.len(), marcador, marcador,
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,12 @@
+//! 📚 Consultas salvas - catálogo de SQL parametrizado no PostgreSQL FC (tabela `api_named_queries`)
+//!
+//! - Repositório: criação da tabela e CRUD do catálogo (nome, banco, SQL, parâmetros e papéis)
+//! - Parâmetros: tipos declarados, conversão dos valores enviados e vínculo de CNPJ
+//! - Handlers: `POST /queries/{name}/run` para os clientes e manutenção em `/admin/consultas-salvas`
+
+pub mod repositorio;
+pub mod parametros;
+pub mod handlers;
+
+pub use repositorio::inicializar;
+pub use handlers::{listar_disponiveis, executar_consulta, listar_consultas, salvar_consulta, remover_consulta};
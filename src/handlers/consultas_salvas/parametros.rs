@@ -0,0 +1,239 @@
+//! 🧩 Parâmetros tipados das consultas salvas
+//!
+//! - A ordem da lista define a posição: o primeiro parâmetro é `$1` (FC) ou `@P1` (Portal/Protheus)
+//! - O valor enviado pelo cliente é convertido para o tipo declarado antes de chegar ao banco
+//! - `cnpj` aceita CNPJ com ou sem máscara e é conferido contra o vínculo do token
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::auth::apenas_digitos;
+use crate::handlers::consulta_livre::ValorParametro;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TipoParametro {
+    Texto,
+    Inteiro,
+    Decimal,
+    Booleano,
+    /// `AAAA-MM-DD`
+    Data,
+    /// `AAAA-MM-DDTHH:MM:SS` (ou com espaço no lugar do `T`)
+    DataHora,
+    /// 14 dígitos, gravado sem máscara
+    Cnpj,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefinicaoParametro {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub tipo: TipoParametro,
+    #[serde(default = "obrigatorio_padrao")]
+    pub required: bool,
+    /// Valor usado quando o cliente não envia o parâmetro
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<Value>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+}
+
+fn obrigatorio_padrao() -> bool {
+    true
+}
+
+impl DefinicaoParametro {
+    /// Converte o valor enviado (ou o `default`) para o tipo declarado
+    pub fn converter(&self, valor: Option<&Value>) -> Result<ValorParametro, String> {
+        let valor = match valor.filter(|v| !v.is_null()).or(self.default.as_ref()) {
+            Some(valor) => valor,
+            None if self.required => return Err(format!("Parâmetro '{}' é obrigatório", self.name)),
+            None => return Ok(self.nulo()),
+        };
+
+        let texto = valor.as_str().map(str::trim);
+        let convertido = match self.tipo {
+            TipoParametro::Texto => texto.map(|s| ValorParametro::Texto(Some(s.to_string()))),
+            TipoParametro::Inteiro => valor.as_i64()
+                .or_else(|| texto.and_then(|s| s.parse().ok()))
+                .map(|v| ValorParametro::Inteiro(Some(v))),
+            TipoParametro::Decimal => match valor {
+                Value::Number(n) => Decimal::from_str(&n.to_string()).ok(),
+                _ => texto.and_then(|s| Decimal::from_str(s).ok()),
+            }.map(|v| ValorParametro::Decimal(Some(v))),
+            TipoParametro::Booleano => valor.as_bool()
+                .or_else(|| texto.and_then(|s| s.parse().ok()))
+                .map(|v| ValorParametro::Booleano(Some(v))),
+            TipoParametro::Data => texto
+                .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+                .map(|v| ValorParametro::Data(Some(v))),
+            TipoParametro::DataHora => texto
+                .and_then(|s| {
+                    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+                        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f"))
+                        .ok()
+                })
+                .map(|v| ValorParametro::DataHora(Some(v))),
+            TipoParametro::Cnpj => texto
+                .map(apenas_digitos)
+                .filter(|cnpj| cnpj.len() == 14)
+                .map(|cnpj| ValorParametro::Texto(Some(cnpj))),
+        };
+
+        convertido.ok_or_else(|| format!("Parâmetro '{}' deve ser do tipo {}", self.name, self.tipo.nome()))
+    }
+
+    fn nulo(&self) -> ValorParametro {
+        match self.tipo {
+            TipoParametro::Texto | TipoParametro::Cnpj => ValorParametro::Texto(None),
+            TipoParametro::Inteiro => ValorParametro::Inteiro(None),
+            TipoParametro::Decimal => ValorParametro::Decimal(None),
+            TipoParametro::Booleano => ValorParametro::Booleano(None),
+            TipoParametro::Data => ValorParametro::Data(None),
+            TipoParametro::DataHora => ValorParametro::DataHora(None),
+        }
+    }
+}
+
+impl TipoParametro {
+    fn nome(&self) -> &'static str {
+        match self {
+            TipoParametro::Texto => "texto",
+            TipoParametro::Inteiro => "inteiro",
+            TipoParametro::Decimal => "decimal",
+            TipoParametro::Booleano => "booleano",
+            TipoParametro::Data => "data (AAAA-MM-DD)",
+            TipoParametro::DataHora => "data_hora (AAAA-MM-DDTHH:MM:SS)",
+            TipoParametro::Cnpj => "cnpj (14 dígitos)",
+        }
+    }
+}
+
+/// Confere nomes (minúsculas, dígitos e `_`, sem repetição) e se os `default` convertem para o tipo
+pub fn validar_definicoes(definicoes: &[DefinicaoParametro]) -> Result<(), String> {
+    let mut nomes = HashSet::new();
+    for definicao in definicoes {
+        let nome_valido = !definicao.name.is_empty()
+            && definicao.name.len() <= 63
+            && definicao.name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if !nome_valido {
+            return Err(format!("Nome de parâmetro inválido: '{}' (use a-z, 0-9 e _)", definicao.name));
+        }
+        if !nomes.insert(definicao.name.as_str()) {
+            return Err(format!("Parâmetro '{}' declarado mais de uma vez", definicao.name));
+        }
+        if let Some(padrao) = &definicao.default {
+            definicao.converter(Some(padrao))
+                .map_err(|e| format!("default inválido: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Converte os parâmetros enviados na ordem declarada; nomes não declarados são rejeitados
+pub fn converter_todos(
+    definicoes: &[DefinicaoParametro],
+    enviados: &Map<String, Value>,
+) -> Result<Vec<ValorParametro>, String> {
+    if let Some(desconhecido) = enviados.keys().find(|k| !definicoes.iter().any(|d| &d.name == *k)) {
+        return Err(format!("Parâmetro '{}' não existe nesta consulta", desconhecido));
+    }
+    definicoes.iter()
+        .map(|definicao| definicao.converter(enviados.get(&definicao.name)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn definicao(nome: &str, tipo: TipoParametro, required: bool, default: Option<Value>) -> DefinicaoParametro {
+        DefinicaoParametro { name: nome.to_string(), tipo, required, default, description: String::new() }
+    }
+
+    #[test]
+    fn obrigatorio_ausente_e_recusado() {
+        let erro = definicao("loja", TipoParametro::Texto, true, None).converter(None).unwrap_err();
+        assert!(erro.contains("obrigatório"));
+        assert!(definicao("loja", TipoParametro::Texto, true, None).converter(Some(&Value::Null)).is_err());
+    }
+
+    #[test]
+    fn opcional_ausente_usa_default_ou_nulo() {
+        let com_default = definicao("dias", TipoParametro::Inteiro, false, Some(json!(30)));
+        assert!(matches!(com_default.converter(None), Ok(ValorParametro::Inteiro(Some(30)))));
+        let sem_default = definicao("dias", TipoParametro::Inteiro, false, None);
+        assert!(matches!(sem_default.converter(None), Ok(ValorParametro::Inteiro(None))));
+    }
+
+    #[test]
+    fn converte_inteiro_decimal_e_booleano() {
+        let inteiro = definicao("n", TipoParametro::Inteiro, true, None);
+        assert!(matches!(inteiro.converter(Some(&json!(" 42 "))), Ok(ValorParametro::Inteiro(Some(42)))));
+        assert!(inteiro.converter(Some(&json!("4.2"))).is_err());
+
+        let decimal = definicao("preco", TipoParametro::Decimal, true, None);
+        match decimal.converter(Some(&json!("12.50"))) {
+            Ok(ValorParametro::Decimal(Some(v))) => assert_eq!(v, Decimal::from_str("12.50").unwrap()),
+            outro => panic!("esperava decimal: {:?}", outro),
+        }
+        assert!(matches!(decimal.converter(Some(&json!(3.5))), Ok(ValorParametro::Decimal(Some(_)))));
+
+        let booleano = definicao("ativo", TipoParametro::Booleano, true, None);
+        assert!(matches!(booleano.converter(Some(&json!("true"))), Ok(ValorParametro::Booleano(Some(true)))));
+    }
+
+    #[test]
+    fn converte_data_e_data_hora() {
+        let data = definicao("dia", TipoParametro::Data, true, None);
+        assert!(matches!(data.converter(Some(&json!("2024-02-29"))), Ok(ValorParametro::Data(Some(_)))));
+        assert!(data.converter(Some(&json!("29/02/2024"))).is_err());
+
+        let data_hora = definicao("desde", TipoParametro::DataHora, true, None);
+        assert!(matches!(data_hora.converter(Some(&json!("2024-01-31T08:30:00"))), Ok(ValorParametro::DataHora(Some(_)))));
+        assert!(matches!(data_hora.converter(Some(&json!("2024-01-31 08:30:00.5"))), Ok(ValorParametro::DataHora(Some(_)))));
+        assert!(data_hora.converter(Some(&json!("2024-01-31"))).is_err());
+    }
+
+    #[test]
+    fn cnpj_com_mascara_vira_quatorze_digitos() {
+        let cnpj = definicao("cnpj", TipoParametro::Cnpj, true, None);
+        match cnpj.converter(Some(&json!("12.345.678/0001-90"))) {
+            Ok(ValorParametro::Texto(Some(v))) => assert_eq!(v, "12345678000190"),
+            outro => panic!("esperava cnpj: {:?}", outro),
+        }
+        assert!(cnpj.converter(Some(&json!("123.456"))).is_err());
+    }
+
+    #[test]
+    fn converter_todos_segue_a_ordem_e_recusa_desconhecidos() {
+        let definicoes = vec![
+            definicao("loja", TipoParametro::Texto, true, None),
+            definicao("dias", TipoParametro::Inteiro, false, Some(json!(7))),
+        ];
+        let enviados = json!({ "loja": "centro" }).as_object().unwrap().clone();
+        let valores = converter_todos(&definicoes, &enviados).unwrap();
+        assert!(matches!(&valores[..], [ValorParametro::Texto(Some(_)), ValorParametro::Inteiro(Some(7))]));
+
+        let enviados = json!({ "loja": "centro", "filial": "01" }).as_object().unwrap().clone();
+        assert!(converter_todos(&definicoes, &enviados).unwrap_err().contains("filial"));
+    }
+
+    #[test]
+    fn validar_definicoes_confere_nomes_e_defaults() {
+        assert!(validar_definicoes(&[definicao("loja_1", TipoParametro::Texto, true, None)]).is_ok());
+        assert!(validar_definicoes(&[definicao("Loja", TipoParametro::Texto, true, None)]).is_err());
+        assert!(validar_definicoes(&[
+            definicao("loja", TipoParametro::Texto, true, None),
+            definicao("loja", TipoParametro::Inteiro, true, None),
+        ]).is_err());
+        assert!(validar_definicoes(&[definicao("dias", TipoParametro::Inteiro, false, Some(json!("sete")))]).is_err());
+    }
+}
@@ -0,0 +1,162 @@
+//! 🗄️ Repositório do catálogo de consultas salvas - PostgreSQL FC (tabela `api_named_queries`)
+
+use deadpool_postgres::Client;
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+
+use super::parametros::DefinicaoParametro;
+use crate::auth::Papel;
+use crate::errors::ApiError;
+use crate::handlers::validacao_sql::Dialeto;
+
+const CONSULTA_COLUNAS: &str = "name, description, target_db, sql_text, parameters, roles, updated_by, created_at, updated_at";
+
+/// Banco onde a consulta é executada
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BancoConsulta {
+    Fc,
+    Portal,
+    Protheus,
+}
+
+impl BancoConsulta {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BancoConsulta::Fc => "fc",
+            BancoConsulta::Portal => "portal",
+            BancoConsulta::Protheus => "protheus",
+        }
+    }
+
+    pub fn from_nome(valor: &str) -> Option<Self> {
+        match valor {
+            "fc" => Some(BancoConsulta::Fc),
+            "portal" => Some(BancoConsulta::Portal),
+            "protheus" => Some(BancoConsulta::Protheus),
+            _ => None,
+        }
+    }
+
+    pub fn dialeto(&self) -> Dialeto {
+        match self {
+            BancoConsulta::Fc => Dialeto::Postgres,
+            BancoConsulta::Portal | BancoConsulta::Protheus => Dialeto::SqlServer,
+        }
+    }
+}
+
+/// Campos editáveis da consulta (corpo do `PUT /admin/consultas-salvas/{name}`)
+#[derive(Debug, Deserialize)]
+pub struct DadosConsulta {
+    #[serde(default)]
+    pub description: String,
+    pub database: BancoConsulta,
+    pub sql: String,
+    #[serde(default)]
+    pub parameters: Vec<DefinicaoParametro>,
+    pub roles: Vec<Papel>,
+}
+
+/// Consulta do catálogo
+#[derive(Debug, Serialize)]
+pub struct ConsultaSalva {
+    pub name: String,
+    pub description: String,
+    pub database: BancoConsulta,
+    pub sql: String,
+    pub parameters: Vec<DefinicaoParametro>,
+    pub roles: Vec<Papel>,
+    pub updated_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Cria a tabela `api_named_queries`
+pub async fn inicializar(client: &Client) -> Result<(), ApiError> {
+    client.batch_execute(r#"
+        CREATE TABLE IF NOT EXISTS api_named_queries (
+            name VARCHAR(100) PRIMARY KEY,
+            description TEXT NOT NULL DEFAULT '',
+            target_db VARCHAR(20) NOT NULL,
+            sql_text TEXT NOT NULL,
+            parameters JSONB NOT NULL DEFAULT '[]',
+            roles TEXT[] NOT NULL DEFAULT '{}',
+            updated_by VARCHAR(100) NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    "#).await?;
+    Ok(())
+}
+
+/// Lista o catálogo; linhas inválidas (editadas direto no banco) são ignoradas com aviso no log,
+/// para não derrubar a listagem inteira. Só `buscar` falha, ao executar a consulta defeituosa.
+pub async fn listar(client: &Client) -> Result<Vec<ConsultaSalva>, ApiError> {
+    let sql = format!("SELECT {} FROM api_named_queries ORDER BY name", CONSULTA_COLUNAS);
+    let rows = client.query(&sql, &[]).await?;
+    Ok(rows.iter()
+        .filter_map(|row| match consulta_from_row(row) {
+            Ok(consulta) => Some(consulta),
+            Err(e) => {
+                let nome: String = row.get("name");
+                log::warn!("⚠️ Consulta salva '{}' ignorada na listagem: {}", nome, e);
+                None
+            }
+        })
+        .collect())
+}
+
+pub async fn buscar(client: &Client, nome: &str) -> Result<Option<ConsultaSalva>, ApiError> {
+    let sql = format!("SELECT {} FROM api_named_queries WHERE name = $1", CONSULTA_COLUNAS);
+    let row = client.query_opt(&sql, &[&nome]).await?;
+    row.as_ref().map(consulta_from_row).transpose()
+}
+
+/// Cria ou substitui a consulta. Retorna `true` se ela ainda não existia.
+pub async fn salvar(client: &Client, nome: &str, dados: &DadosConsulta, alterado_por: &str) -> Result<bool, ApiError> {
+    let parametros = serde_json::to_value(&dados.parameters)
+        .map_err(|e| ApiError::InternalError(format!("Erro ao serializar parâmetros: {}", e)))?;
+    let roles: Vec<String> = dados.roles.iter().map(|p| p.as_str().to_string()).collect();
+
+    let row = client.query_one(r#"
+        INSERT INTO api_named_queries (name, description, target_db, sql_text, parameters, roles, updated_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (name) DO UPDATE SET
+            description = EXCLUDED.description,
+            target_db = EXCLUDED.target_db,
+            sql_text = EXCLUDED.sql_text,
+            parameters = EXCLUDED.parameters,
+            roles = EXCLUDED.roles,
+            updated_by = EXCLUDED.updated_by,
+            updated_at = NOW()
+        RETURNING (xmax = 0) AS criada
+    "#, &[&nome, &dados.description, &dados.database.as_str(), &dados.sql, &parametros, &roles, &alterado_por]).await?;
+    Ok(row.get("criada"))
+}
+
+/// Remove a consulta. Retorna `false` se o nome não existir.
+pub async fn remover(client: &Client, nome: &str) -> Result<bool, ApiError> {
+    let removidas = client.execute("DELETE FROM api_named_queries WHERE name = $1", &[&nome]).await?;
+    Ok(removidas > 0)
+}
+
+fn consulta_from_row(row: &Row) -> Result<ConsultaSalva, ApiError> {
+    let banco: String = row.get("target_db");
+    let parametros: serde_json::Value = row.get("parameters");
+    let roles: Vec<String> = row.get("roles");
+
+    Ok(ConsultaSalva {
+        name: row.get("name"),
+        description: row.get("description"),
+        database: BancoConsulta::from_nome(&banco)
+            .ok_or_else(|| ApiError::InternalError(format!("Banco desconhecido no catálogo: {}", banco)))?,
+        sql: row.get("sql_text"),
+        parameters: serde_json::from_value(parametros)
+            .map_err(|e| ApiError::InternalError(format!("Parâmetros inválidos no catálogo: {}", e)))?,
+        roles: roles.iter().filter_map(|r| Papel::from_nome(r)).collect(),
+        updated_by: row.get("updated_by"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+    })
+}
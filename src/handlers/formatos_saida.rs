@@ -0,0 +1,401 @@
+// src/handlers/formatos_saida.rs
+// 📄 Codificação CSV e Parquet do SQL livre, com tipos derivados dos metadados das colunas
+
+use std::sync::Arc;
+
+use arrow_array::builder::{
+    BooleanBuilder, Date32Builder, Decimal128Builder, Float32Builder, Float64Builder, Int16Builder, Int32Builder,
+    Int64Builder, StringBuilder, TimestampMicrosecondBuilder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{NaiveDate, NaiveDateTime};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use parquet::format::KeyValue;
+use serde_json::Value;
+
+/// Linhas acumuladas por row group do Parquet (cada row group é enviado ao cliente ao fechar)
+const LINHAS_POR_GRUPO: usize = 8192;
+
+/// Tipo lógico da coluna, derivado do `Type` do PostgreSQL ou do `ColumnType` do tiberius
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TipoColuna {
+    Booleano,
+    Inteiro16,
+    Inteiro32,
+    Inteiro64,
+    Real32,
+    Real64,
+    /// NUMERIC/DECIMAL/MONEY (vem como texto no JSON para não perder precisão),
+    /// com precisão e escala quando os metadados as informam
+    Decimal(Option<(u8, i8)>),
+    Data,
+    DataHora,
+    DataHoraUtc,
+    Texto,
+}
+
+#[derive(Debug, Clone)]
+pub struct Coluna {
+    pub nome: String,
+    pub tipo: TipoColuna,
+}
+
+impl TipoColuna {
+    pub fn do_postgres(coluna: &tokio_postgres::Column) -> Self {
+        use tokio_postgres::types::Type;
+        match *coluna.type_() {
+            Type::BOOL => TipoColuna::Booleano,
+            Type::INT2 => TipoColuna::Inteiro16,
+            Type::INT4 => TipoColuna::Inteiro32,
+            Type::INT8 => TipoColuna::Inteiro64,
+            Type::FLOAT4 => TipoColuna::Real32,
+            Type::FLOAT8 => TipoColuna::Real64,
+            Type::NUMERIC => TipoColuna::Decimal(precisao_numeric(coluna.type_modifier())),
+            Type::DATE => TipoColuna::Data,
+            Type::TIMESTAMP => TipoColuna::DataHora,
+            Type::TIMESTAMPTZ => TipoColuna::DataHoraUtc,
+            _ => TipoColuna::Texto,
+        }
+    }
+
+    pub fn do_sqlserver(tipo: tiberius::ColumnType) -> Self {
+        use tiberius::ColumnType;
+        match tipo {
+            ColumnType::Bit | ColumnType::Bitn => TipoColuna::Booleano,
+            ColumnType::Int2 => TipoColuna::Inteiro16,
+            ColumnType::Int4 => TipoColuna::Inteiro32,
+            ColumnType::Int8 | ColumnType::Intn => TipoColuna::Inteiro64,
+            ColumnType::Float4 => TipoColuna::Real32,
+            ColumnType::Float8 | ColumnType::Floatn => TipoColuna::Real64,
+            ColumnType::Money => TipoColuna::Decimal(Some((19, 4))),
+            ColumnType::Money4 => TipoColuna::Decimal(Some((10, 4))),
+            // O tiberius não expõe precisão e escala de DECIMAL/NUMERIC
+            ColumnType::Decimaln | ColumnType::Numericn => TipoColuna::Decimal(None),
+            ColumnType::Daten => TipoColuna::Data,
+            ColumnType::Datetime
+            | ColumnType::Datetime2
+            | ColumnType::Datetime4
+            | ColumnType::Datetimen => TipoColuna::DataHora,
+            ColumnType::DatetimeOffsetn => TipoColuna::DataHoraUtc,
+            _ => TipoColuna::Texto,
+        }
+    }
+
+    fn numerico_fracionario(&self) -> bool {
+        matches!(self, TipoColuna::Real32 | TipoColuna::Real64 | TipoColuna::Decimal(_))
+    }
+
+    fn arrow(&self) -> DataType {
+        match self {
+            TipoColuna::Booleano => DataType::Boolean,
+            TipoColuna::Inteiro16 => DataType::Int16,
+            TipoColuna::Inteiro32 => DataType::Int32,
+            TipoColuna::Inteiro64 => DataType::Int64,
+            TipoColuna::Real32 => DataType::Float32,
+            TipoColuna::Real64 => DataType::Float64,
+            TipoColuna::Decimal(Some((precisao, escala))) => DataType::Decimal128(*precisao, *escala),
+            TipoColuna::Decimal(None) => DataType::Utf8,
+            TipoColuna::Data => DataType::Date32,
+            TipoColuna::DataHora => DataType::Timestamp(TimeUnit::Microsecond, None),
+            TipoColuna::DataHoraUtc => DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            TipoColuna::Texto => DataType::Utf8,
+        }
+    }
+}
+
+/// CSV com delimitador configurável e, opcionalmente, vírgula decimal (padrão brasileiro)
+pub struct EscritorCsv {
+    delimitador: u8,
+    decimal_virgula: bool,
+    tipos: Vec<TipoColuna>,
+}
+
+impl EscritorCsv {
+    pub fn new(delimitador: u8, decimal_virgula: bool) -> Self {
+        EscritorCsv { delimitador, decimal_virgula, tipos: Vec::new() }
+    }
+
+    pub fn cabecalho(&mut self, colunas: &[Coluna]) -> Vec<u8> {
+        self.tipos = colunas.iter().map(|c| c.tipo).collect();
+        let nomes: Vec<&str> = colunas.iter().map(|c| c.nome.as_str()).collect();
+        self.registro(nomes.iter().copied())
+    }
+
+    pub fn linha(&self, valores: &[Value]) -> Vec<u8> {
+        let campos: Vec<String> = valores.iter().enumerate().map(|(i, valor)| {
+            let texto = match valor {
+                Value::Null => String::new(),
+                Value::String(s) => s.clone(),
+                outro => outro.to_string(),
+            };
+            let fracionario = self.tipos.get(i).is_some_and(|t| t.numerico_fracionario());
+            if self.decimal_virgula && fracionario {
+                texto.replace('.', ",")
+            } else {
+                texto
+            }
+        }).collect();
+        self.registro(campos.iter().map(String::as_str))
+    }
+
+    /// Campos com delimitador, aspas ou quebra de linha vão entre aspas (RFC 4180)
+    fn registro<'a>(&self, campos: impl Iterator<Item = &'a str>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for (i, campo) in campos.enumerate() {
+            if i > 0 {
+                bytes.push(self.delimitador);
+            }
+            let precisa_aspas = campo.bytes().any(|b| b == self.delimitador || b == b'"' || b == b'\n' || b == b'\r');
+            if precisa_aspas {
+                bytes.push(b'"');
+                bytes.extend(campo.replace('"', "\"\"").into_bytes());
+                bytes.push(b'"');
+            } else {
+                bytes.extend_from_slice(campo.as_bytes());
+            }
+        }
+        bytes.extend_from_slice(b"\r\n");
+        bytes
+    }
+}
+
+/// Parquet (Snappy) escrito por row groups; os bytes de cada grupo fechado são liberados para o cliente
+pub struct EscritorParquet {
+    schema: SchemaRef,
+    construtores: Vec<Construtor>,
+    pendentes: usize,
+    writer: ArrowWriter<Vec<u8>>,
+}
+
+impl EscritorParquet {
+    pub fn new(colunas: &[Coluna]) -> Result<Self, String> {
+        let schema: SchemaRef = Arc::new(Schema::new(
+            colunas.iter()
+                .map(|c| Field::new(&c.nome, c.tipo.arrow(), true))
+                .collect::<Vec<_>>(),
+        ));
+        let propriedades = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let writer = ArrowWriter::try_new(Vec::new(), schema.clone(), Some(propriedades))
+            .map_err(|e| format!("Erro ao iniciar Parquet: {}", e))?;
+
+        Ok(EscritorParquet {
+            schema,
+            construtores: colunas.iter().map(|c| Construtor::new(c.tipo)).collect(),
+            pendentes: 0,
+            writer,
+        })
+    }
+
+    /// Acrescenta a linha; ao completar um row group, devolve os bytes já gravados
+    pub fn linha(&mut self, valores: &[Value]) -> Result<Vec<u8>, String> {
+        for (construtor, valor) in self.construtores.iter_mut().zip(valores) {
+            construtor.anexar(valor);
+        }
+        self.pendentes += 1;
+
+        if self.pendentes < LINHAS_POR_GRUPO {
+            return Ok(Vec::new());
+        }
+        self.gravar_grupo()?;
+        self.writer.flush().map_err(|e| format!("Erro ao gravar Parquet: {}", e))?;
+        Ok(std::mem::take(self.writer.inner_mut()))
+    }
+
+    /// Grava as linhas restantes e o rodapé (com `truncated` nos metadados do arquivo)
+    pub fn finalizar(mut self, truncado: bool) -> Result<Vec<u8>, String> {
+        self.gravar_grupo()?;
+        self.writer.append_key_value_metadata(KeyValue::new("truncated".to_string(), truncado.to_string()));
+        self.writer.into_inner().map_err(|e| format!("Erro ao finalizar Parquet: {}", e))
+    }
+
+    fn gravar_grupo(&mut self) -> Result<(), String> {
+        if self.pendentes == 0 {
+            return Ok(());
+        }
+        let arrays: Vec<ArrayRef> = self.construtores.iter_mut().map(Construtor::concluir).collect();
+        let lote = RecordBatch::try_new(self.schema.clone(), arrays)
+            .map_err(|e| format!("Erro ao montar lote Parquet: {}", e))?;
+        self.writer.write(&lote).map_err(|e| format!("Erro ao gravar Parquet: {}", e))?;
+        self.pendentes = 0;
+        Ok(())
+    }
+}
+
+/// Construtor Arrow por coluna; valores que não convertem para o tipo viram nulos
+enum Construtor {
+    Booleano(BooleanBuilder),
+    Inteiro16(Int16Builder),
+    Inteiro32(Int32Builder),
+    Inteiro64(Int64Builder),
+    Real32(Float32Builder),
+    Real64(Float64Builder),
+    /// Valor inteiro escalado, com a precisão e a escala da coluna
+    Decimal(Decimal128Builder, u8, i8),
+    Data(Date32Builder),
+    DataHora(TimestampMicrosecondBuilder),
+    Texto(StringBuilder),
+}
+
+impl Construtor {
+    fn new(tipo: TipoColuna) -> Self {
+        match tipo {
+            TipoColuna::Booleano => Construtor::Booleano(BooleanBuilder::new()),
+            TipoColuna::Inteiro16 => Construtor::Inteiro16(Int16Builder::new()),
+            TipoColuna::Inteiro32 => Construtor::Inteiro32(Int32Builder::new()),
+            TipoColuna::Inteiro64 => Construtor::Inteiro64(Int64Builder::new()),
+            TipoColuna::Real32 => Construtor::Real32(Float32Builder::new()),
+            TipoColuna::Real64 => Construtor::Real64(Float64Builder::new()),
+            TipoColuna::Decimal(Some((precisao, escala))) => Construtor::Decimal(
+                Decimal128Builder::new()
+                    .with_precision_and_scale(precisao, escala)
+                    .expect("precisão e escala validadas nos metadados"),
+                precisao,
+                escala,
+            ),
+            TipoColuna::Decimal(None) => Construtor::Texto(StringBuilder::new()),
+            TipoColuna::Data => Construtor::Data(Date32Builder::new()),
+            TipoColuna::DataHora => Construtor::DataHora(TimestampMicrosecondBuilder::new()),
+            TipoColuna::DataHoraUtc => {
+                Construtor::DataHora(TimestampMicrosecondBuilder::new().with_timezone("UTC"))
+            }
+            TipoColuna::Texto => Construtor::Texto(StringBuilder::new()),
+        }
+    }
+
+    fn anexar(&mut self, valor: &Value) {
+        match self {
+            Construtor::Booleano(b) => b.append_option(valor.as_bool()),
+            Construtor::Inteiro16(b) => b.append_option(valor.as_i64().and_then(|v| i16::try_from(v).ok())),
+            Construtor::Inteiro32(b) => b.append_option(valor.as_i64().and_then(|v| i32::try_from(v).ok())),
+            Construtor::Inteiro64(b) => b.append_option(valor.as_i64()),
+            Construtor::Real32(b) => b.append_option(real(valor).map(|v| v as f32)),
+            Construtor::Real64(b) => b.append_option(real(valor)),
+            Construtor::Decimal(b, precisao, escala) => b.append_option(decimal(valor, *precisao, *escala)),
+            Construtor::Data(b) => b.append_option(
+                valor.as_str()
+                    .and_then(|s| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok())
+                    .map(|d| (d - NaiveDate::default()).num_days() as i32),
+            ),
+            Construtor::DataHora(b) => b.append_option(
+                valor.as_str()
+                    .and_then(|s| NaiveDateTime::parse_from_str(s.trim_end_matches(" UTC"), "%Y-%m-%d %H:%M:%S%.f").ok())
+                    .map(|dt| dt.and_utc().timestamp_micros()),
+            ),
+            Construtor::Texto(b) => match valor {
+                Value::Null => b.append_null(),
+                Value::String(s) => b.append_value(s),
+                outro => b.append_value(outro.to_string()),
+            },
+        }
+    }
+
+    /// Esvazia o construtor (mantendo o tipo, inclusive o fuso) e devolve a coluna do lote
+    fn concluir(&mut self) -> ArrayRef {
+        match self {
+            Construtor::Booleano(b) => Arc::new(b.finish()),
+            Construtor::Inteiro16(b) => Arc::new(b.finish()),
+            Construtor::Inteiro32(b) => Arc::new(b.finish()),
+            Construtor::Inteiro64(b) => Arc::new(b.finish()),
+            Construtor::Real32(b) => Arc::new(b.finish()),
+            Construtor::Real64(b) => Arc::new(b.finish()),
+            Construtor::Decimal(b, _, _) => Arc::new(b.finish()),
+            Construtor::Data(b) => Arc::new(b.finish()),
+            Construtor::DataHora(b) => Arc::new(b.finish()),
+            Construtor::Texto(b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Números reais e decimais (que chegam como texto para não perder precisão)
+fn real(valor: &Value) -> Option<f64> {
+    valor.as_f64().or_else(|| valor.as_str().and_then(|s| s.parse().ok()))
+}
+
+/// `numeric(p, s)` no PostgreSQL: `atttypmod = ((p << 16) | s) + 4`; `-1` quando sem precisão declarada
+fn precisao_numeric(modificador: i32) -> Option<(u8, i8)> {
+    if modificador < 4 {
+        return None;
+    }
+    let precisao = ((modificador - 4) >> 16) & 0xffff;
+    let escala = (modificador - 4) & 0xffff;
+    if (1..=38).contains(&precisao) && escala <= precisao {
+        Some((precisao as u8, escala as i8))
+    } else {
+        None
+    }
+}
+
+/// Decimal (texto ou número) como inteiro escalado; `None` se não couber na precisão ou perder casas
+fn decimal(valor: &Value, precisao: u8, escala: i8) -> Option<i128> {
+    let texto = match valor {
+        Value::String(s) => s.trim().to_string(),
+        Value::Number(n) => n.to_string(),
+        _ => return None,
+    };
+    let (negativo, digitos) = match texto.strip_prefix('-') {
+        Some(resto) => (true, resto),
+        None => (false, texto.strip_prefix('+').unwrap_or(&texto)),
+    };
+    let (inteira, fracao) = digitos.split_once('.').unwrap_or((digitos, ""));
+    let escala = usize::try_from(escala).ok()?;
+    let todos_digitos = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    if (inteira.is_empty() && fracao.is_empty()) || !todos_digitos(inteira) || !todos_digitos(fracao) {
+        return None;
+    }
+    // Casas além da escala só são aceitas se forem zeros
+    if fracao.len() > escala && fracao[escala..].bytes().any(|b| b != b'0') {
+        return None;
+    }
+
+    let mut numero = String::with_capacity(inteira.len() + escala);
+    numero.push_str(inteira.trim_start_matches('0'));
+    numero.push_str(&fracao[..fracao.len().min(escala)]);
+    numero.extend(std::iter::repeat_n('0', escala.saturating_sub(fracao.len())));
+    let numero = numero.trim_start_matches('0');
+    if numero.len() > precisao as usize {
+        return None;
+    }
+    let absoluto: i128 = if numero.is_empty() { 0 } else { numero.parse().ok()? };
+    Some(if negativo { -absoluto } else { absoluto })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn precisao_numeric_le_o_typmod() {
+        assert_eq!(precisao_numeric(((12 << 16) | 2) + 4), Some((12, 2)));
+        assert_eq!(precisao_numeric(-1), None);
+        assert_eq!(precisao_numeric(((1000 << 16) | 2) + 4), None);
+    }
+
+    #[test]
+    fn decimal_escala_texto_e_numero() {
+        assert_eq!(decimal(&json!("12.50"), 12, 2), Some(1250));
+        assert_eq!(decimal(&json!("-0.5"), 12, 2), Some(-50));
+        assert_eq!(decimal(&json!("7"), 12, 2), Some(700));
+        assert_eq!(decimal(&json!(3.25), 12, 4), Some(32500));
+        assert_eq!(decimal(&json!("1.2300"), 12, 2), Some(123));
+    }
+
+    #[test]
+    fn decimal_fora_da_coluna_vira_nulo() {
+        assert_eq!(decimal(&json!("1.234"), 12, 2), None);
+        assert_eq!(decimal(&json!("123456"), 5, 2), None);
+        assert_eq!(decimal(&json!("abc"), 12, 2), None);
+        assert_eq!(decimal(&json!(null), 12, 2), None);
+    }
+
+    #[test]
+    fn decimal_sem_precisao_vira_texto_no_parquet() {
+        assert_eq!(TipoColuna::Decimal(None).arrow(), DataType::Utf8);
+        assert_eq!(TipoColuna::Decimal(Some((19, 4))).arrow(), DataType::Decimal128(19, 4));
+    }
+}
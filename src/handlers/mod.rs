@@ -7,10 +7,17 @@ pub mod query_handlers;
 pub mod portal_handlers;
 pub mod protheus_handlers;
 pub mod debug_handlers;
+pub mod admin_handlers;
+pub mod validacao_sql;   // SQL livre somente leitura (sqlparser)
+pub mod consulta_livre;  // Execução do SQL livre com tempo limite
+pub mod consulta_stream; // Streaming do SQL livre (NDJSON / JSON em partes / CSV / Parquet)
+pub mod formatos_saida;  // Codificação CSV e Parquet do SQL livre
 
 // 🎯 ESTRUTURA MODULAR - Arquivos < 500 linhas
 pub mod analytics;     // Novo: analytics modularizado
 pub mod pedidos;       // Novo: pedidos modularizado
+pub mod usuarios;      // Usuários da API (PostgreSQL FC)
+pub mod consultas_salvas; // Catálogo de consultas parametrizadas (PostgreSQL FC)
 
 // 🌐 NOVOS ENDPOINTS CRÍTICOS
 pub mod portal_endpoints;  // Endpoints básicos do portal
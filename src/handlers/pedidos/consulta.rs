@@ -0,0 +1,435 @@
+//! 🔎 Consulta Pedidos - Leitura de pedidos do Portal
+//!
+//! Carrega o pedido completo com itens, produtos, totais e as regras
+//! de frete e parcelamento associadas, e lista pedidos com filtros.
+
+use actix_web::{web, HttpResponse};
+use futures_util::TryStreamExt;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use serde_json::json;
+use tiberius::{Query, QueryItem, Row};
+use crate::database::DatabasePools;
+use crate::errors::ApiError;
+use crate::auth::Claims;
+use crate::handlers::portal_endpoints::normalizar_cnpj;
+use super::validacao::exigir_acesso_pedido;
+use crate::models::{Item, ItemDetalhado, Pedido, PedidoDetalhado, Produto, RegraFrete, RegraParcelamento, StatusPedido};
+
+/// Colunas do cabeçalho na ordem lida por `pedido_from_row`
+/// Datas são convertidas para texto no SQL (23 = yyyy-mm-dd, 120 = yyyy-mm-dd hh:mi:ss)
+pub const PEDIDO_COLUNAS: &str = r#"
+    p.id,
+    p.cliente_id,
+    p.codigo_cliente,
+    p.numero_pedido,
+    p.loja_cliente,
+    CONVERT(varchar(10), p.emissao, 23) as emissao,
+    CONVERT(varchar(19), p.dt_envio, 120) as dt_envio,
+    p.condicao_pagamento,
+    p.tipo_pedido,
+    p.tabela_precos,
+    p.tipo_frete,
+    p.mensagem,
+    p.natureza,
+    p.status_pedido,
+    p.numero_nota_fiscal,
+    p.transportadora,
+    p.rastreio_carga,
+    p.vendedor,
+    p.integrado,
+    p.confirmado,
+    p.status_liberacao,
+    p.tentativas_integracao,
+    p.regra_condicao_pagamento_id,
+    p.regra_frete_id,
+    p.nota,
+    CONVERT(varchar(19), p.created_at, 120) as created_at,
+    CONVERT(varchar(19), p.updated_at, 120) as updated_at
+"#;
+
+#[derive(Debug, Deserialize)]
+pub struct ListarPedidosParams {
+    pub cnpj: Option<String>,
+    pub status: Option<String>,
+    pub data_inicio: Option<String>, // YYYY-MM-DD (emissão)
+    pub data_fim: Option<String>,
+    pub integrado: Option<bool>,
+    pub confirmado: Option<bool>,
+    pub natureza: Option<String>,
+    pub limite: Option<i32>,
+    pub offset: Option<i32>,
+    pub apos_id: Option<i32>, // Keyset: retorna pedidos com id menor que o informado
+}
+
+/// Valor de filtro a ser ligado na query na mesma ordem das condições
+enum ParamFiltro {
+    Texto(String),
+    Booleano(bool),
+}
+
+/// GET /pedidos - Lista pedidos com filtros e paginação
+pub async fn listar_pedidos(
+    params: web::Query<ListarPedidosParams>,
+    pools: web::Data<DatabasePools>,
+    claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    log::info!("Listando pedidos com filtros: {:?}", params);
+    
+    let limite = params.limite.unwrap_or(50).clamp(1, 500); // Máximo 500
+    let offset = params.offset.unwrap_or(0).max(0);
+    
+    // 1. Montar filtros
+    let mut condicoes = vec!["p.deleted_at IS NULL".to_string()];
+    let mut valores = Vec::new();
+    
+    // Tokens vinculados a CNPJ só enxergam pedidos dos próprios clientes (comparação só com dígitos)
+    if claims.restrito_a_cnpj() {
+        if claims.cnpjs.is_empty() {
+            return Err(ApiError::Unauthorized);
+        }
+        let marcadores: Vec<String> = claims.cnpjs.iter()
+            .map(|cnpj| {
+                valores.push(ParamFiltro::Texto(cnpj.clone()));
+                format!("@P{}", valores.len())
+            })
+            .collect();
+        condicoes.push(format!(
+            "REPLACE(REPLACE(REPLACE(c.cnpj, '.', ''), '/', ''), '-', '') IN ({})",
+            marcadores.join(", ")
+        ));
+    }
+    
+    // Um status lógico reúne vários valores gravados (ex.: integrado, Confirmado ERP, Em Separação)
+    if let Some(status) = params.status.as_deref() {
+        let status = StatusPedido::from_banco(status).ok_or_else(|| ApiError::BadRequest(format!(
+            "Status inválido: '{}'. Use um de: {}",
+            status,
+            StatusPedido::todos().iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", ")
+        )))?;
+        condicoes.push(format!("p.status_pedido IN ({})", StatusPedido::lista_sql(&[status])));
+    }
+    
+    let mut filtrar = |condicao: &str, valor: ParamFiltro| {
+        valores.push(valor);
+        condicoes.push(condicao.replace("{}", &format!("@P{}", valores.len())));
+    };
+    
+    if let Some(cnpj) = params.cnpj.as_deref() {
+        claims.exigir_cnpj(cnpj)?;
+        filtrar("c.cnpj = {}", ParamFiltro::Texto(normalizar_cnpj(cnpj)));
+    }
+    if let Some(data) = params.data_inicio.as_deref() {
+        filtrar("p.emissao >= CONVERT(date, {})", ParamFiltro::Texto(validar_data(data)?));
+    }
+    if let Some(data) = params.data_fim.as_deref() {
+        filtrar("p.emissao <= CONVERT(date, {})", ParamFiltro::Texto(validar_data(data)?));
+    }
+    if let Some(integrado) = params.integrado {
+        filtrar("p.integrado = {}", ParamFiltro::Booleano(integrado));
+    }
+    if let Some(confirmado) = params.confirmado {
+        filtrar("p.confirmado = {}", ParamFiltro::Booleano(confirmado));
+    }
+    if let Some(natureza) = params.natureza.as_deref() {
+        filtrar("p.natureza = {}", ParamFiltro::Texto(natureza.to_string()));
+    }
+    
+    let from_where = format!(
+        "FROM pedidos p LEFT JOIN clientes c ON c.id = p.cliente_id WHERE {}",
+        condicoes.join(" AND ")
+    );
+    
+    let mut conn = pools.sqlserver_portal.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
+    
+    // 2. Total sem paginação
+    let mut count_query = Query::new(format!("SELECT COUNT(*) {}", from_where));
+    bind_filtros(&mut count_query, &valores);
+    
+    let total: i32 = count_query.query(&mut conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao contar pedidos: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao processar contagem: {}", e)))?
+        .and_then(|row| row.get::<i32, _>(0))
+        .unwrap_or(0);
+    
+    // 3. Página - keyset quando apos_id informado, senão OFFSET
+    let sql = match params.apos_id {
+        Some(_) => format!(
+            "SELECT TOP ({}) {} {} AND p.id < @P{} ORDER BY p.id DESC",
+            limite, PEDIDO_COLUNAS, from_where, valores.len() + 1
+        ),
+        None => format!(
+            "SELECT {} {} ORDER BY p.id DESC OFFSET {} ROWS FETCH NEXT {} ROWS ONLY",
+            PEDIDO_COLUNAS, from_where, offset, limite
+        ),
+    };
+    
+    let mut query = Query::new(sql);
+    bind_filtros(&mut query, &valores);
+    if let Some(apos_id) = params.apos_id {
+        query.bind(apos_id);
+    }
+    
+    let mut stream = query.query(&mut conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao listar pedidos: {}", e)))?;
+    
+    let mut pedidos = Vec::new();
+    
+    while let Some(item) = stream.try_next().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler pedidos: {}", e)))? {
+        if let QueryItem::Row(row) = item {
+            pedidos.push(pedido_from_row(&row));
+        }
+    }
+    
+    let proximo_cursor = if pedidos.len() == limite as usize {
+        pedidos.last().map(|p| p.id)
+    } else {
+        None
+    };
+    
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "pedidos": pedidos,
+        "count": pedidos.len(),
+        "total": total,
+        "limite": limite,
+        "offset": if params.apos_id.is_some() { None } else { Some(offset) },
+        "proximo_cursor": proximo_cursor
+    })))
+}
+
+fn validar_data(data: &str) -> Result<String, ApiError> {
+    NaiveDate::parse_from_str(data, "%Y-%m-%d")
+        .map(|d| d.to_string())
+        .map_err(|_| ApiError::BadRequest(format!("Data inválida '{}': use o formato YYYY-MM-DD", data)))
+}
+
+fn bind_filtros(query: &mut Query<'_>, valores: &[ParamFiltro]) {
+    for valor in valores {
+        match valor {
+            ParamFiltro::Texto(v) => query.bind(v.clone()),
+            ParamFiltro::Booleano(v) => query.bind(*v),
+        }
+    }
+}
+
+/// GET /pedidos/{id} - Pedido completo com itens e regras
+pub async fn buscar_pedido(
+    pedido_id: web::Path<i32>,
+    pools: web::Data<DatabasePools>,
+    claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    let pedido_id = pedido_id.into_inner();
+    log::info!("Buscando pedido ID: {}", pedido_id);
+    
+    let mut conn = pools.sqlserver_portal.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
+    
+    exigir_acesso_pedido(&mut conn, pedido_id, &claims).await?;
+    
+    // 1. Cabeçalho
+    let sql = format!("SELECT {} FROM pedidos p WHERE p.id = @P1 AND p.deleted_at IS NULL", PEDIDO_COLUNAS);
+    let mut query = Query::new(sql);
+    query.bind(pedido_id);
+    
+    let pedido = query.query(&mut conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar pedido: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao processar pedido: {}", e)))?
+        .map(|row| pedido_from_row(&row))
+        .ok_or(ApiError::NotFound)?;
+    
+    // 2. Itens com dados do produto
+    let items = buscar_items_detalhados(&mut conn, pedido_id).await?;
+    
+    // 3. Regras de frete e parcelamento
+    let regra_frete = buscar_regra_frete(&mut conn, pedido.regra_frete_id).await?;
+    let regra_parcelamento = buscar_regra_parcelamento(&mut conn, pedido.regra_condicao_pagamento_id).await?;
+    
+    let valor_total: Decimal = items.iter().map(|i| i.valor_total).sum();
+    let quantidade_total: i32 = items.iter().map(|i| i.item.quantidade).sum();
+    
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "data": PedidoDetalhado {
+            pedido,
+            total_itens: items.len(),
+            quantidade_total,
+            valor_total,
+            items,
+            regra_frete,
+            regra_parcelamento,
+        }
+    })))
+}
+
+/// Converte uma linha selecionada com `PEDIDO_COLUNAS` no model `Pedido`
+pub fn pedido_from_row(row: &Row) -> Pedido {
+    let texto = |i: usize| row.get::<&str, _>(i).map(|s| s.trim().to_string());
+    
+    Pedido {
+        id: row.get::<i32, _>(0).unwrap_or(0),
+        cliente_id: row.get::<i32, _>(1).unwrap_or(0),
+        codigo_cliente: texto(2).unwrap_or_default(),
+        numero_pedido: texto(3),
+        loja_cliente: texto(4).unwrap_or_default(),
+        emissao: texto(5).unwrap_or_default(),
+        dt_envio: texto(6),
+        condicao_pagamento: texto(7),
+        tipo_pedido: texto(8),
+        tabela_precos: texto(9),
+        tipo_frete: texto(10),
+        mensagem: texto(11),
+        natureza: texto(12).unwrap_or_default(),
+        status_pedido: texto(13).unwrap_or_default(),
+        numero_nota_fiscal: texto(14),
+        transportadora: texto(15),
+        rastreio_carga: texto(16),
+        vendedor: texto(17),
+        integrado: row.get::<bool, _>(18).unwrap_or(false),
+        confirmado: row.get::<bool, _>(19).unwrap_or(false),
+        status_liberacao: texto(20),
+        tentativas_integracao: row.get::<i32, _>(21),
+        regra_condicao_pagamento_id: row.get::<i32, _>(22).unwrap_or(0),
+        regra_frete_id: row.get::<i32, _>(23).unwrap_or(0),
+        nota: texto(24),
+        created_at: texto(25),
+        updated_at: texto(26),
+    }
+}
+
+async fn buscar_items_detalhados(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    pedido_id: i32
+) -> Result<Vec<ItemDetalhado>, ApiError> {
+    let mut query = Query::new(r#"
+        SELECT 
+            i.id,
+            i.pedido_id,
+            i.produto_id,
+            i.quantidade,
+            CAST(i.preco_unitario AS DECIMAL(18,4)) as preco_unitario,
+            CONVERT(varchar(19), i.created_at, 120) as created_at,
+            CONVERT(varchar(19), i.updated_at, 120) as updated_at,
+            pr.codigo,
+            pr.descricao,
+            pr.tipo,
+            pr.unidade_medida,
+            CAST(pr.preco_unitario AS DECIMAL(18,4)) as produto_preco,
+            pr.quantidade_minima_embalagem,
+            pr.saldo,
+            pr.estoque,
+            pr.categoria_id,
+            pr.b2_filial,
+            pr.status,
+            pr.foto,
+            pr.grupo_venda
+        FROM items i
+        INNER JOIN produtos pr ON pr.id = i.produto_id
+        WHERE i.pedido_id = @P1
+        ORDER BY i.id
+    "#);
+    query.bind(pedido_id);
+    
+    let mut stream = query.query(conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar itens do pedido: {}", e)))?;
+    
+    let mut items = Vec::new();
+    
+    while let Some(item) = stream.try_next().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler itens do pedido: {}", e)))? {
+        if let QueryItem::Row(row) = item {
+            let texto = |i: usize| row.get::<&str, _>(i).map(|s| s.trim().to_string());
+            
+            let item = Item {
+                id: row.get::<i32, _>(0).unwrap_or(0),
+                pedido_id: row.get::<i32, _>(1).unwrap_or(0),
+                produto_id: row.get::<i32, _>(2).unwrap_or(0),
+                quantidade: row.get::<i32, _>(3).unwrap_or(0),
+                preco_unitario: row.get::<Decimal, _>(4).unwrap_or(Decimal::ZERO),
+                created_at: texto(5),
+                updated_at: texto(6),
+            };
+            
+            let produto = Produto {
+                id: item.produto_id,
+                codigo: texto(7).unwrap_or_default(),
+                descricao: texto(8).unwrap_or_default(),
+                tipo: texto(9),
+                unidade_medida: texto(10).unwrap_or_default(),
+                preco_unitario: row.get::<Decimal, _>(11),
+                quantidade_minima_embalagem: row.get::<i32, _>(12).unwrap_or(1),
+                saldo: row.get::<i32, _>(13).unwrap_or(0),
+                estoque: row.get::<i32, _>(14),
+                categoria_id: row.get::<i32, _>(15),
+                b2_filial: texto(16),
+                status: row.get::<bool, _>(17).unwrap_or(false),
+                foto: texto(18),
+                grupo_venda: texto(19),
+            };
+            
+            let valor_total = item.preco_unitario * Decimal::from(item.quantidade);
+            items.push(ItemDetalhado { item, produto, valor_total });
+        }
+    }
+    
+    Ok(items)
+}
+
+async fn buscar_regra_frete(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    regra_id: i32
+) -> Result<Option<RegraFrete>, ApiError> {
+    let mut query = Query::new(r#"
+        SELECT id, CAST(valor_minimo AS DECIMAL(18,2)), descricao, codigo_protheus
+        FROM regra_frete
+        WHERE id = @P1
+    "#);
+    query.bind(regra_id);
+    
+    let result = query.query(conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar regra de frete: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao processar regra de frete: {}", e)))?;
+    
+    Ok(result.map(|row| RegraFrete {
+        id: row.get::<i32, _>(0).unwrap_or(0),
+        valor_minimo: row.get::<Decimal, _>(1).unwrap_or(Decimal::ZERO),
+        descricao: row.get::<&str, _>(2).unwrap_or("").trim().to_string(),
+        codigo_protheus: row.get::<&str, _>(3).map(|s| s.trim().to_string()),
+    }))
+}
+
+async fn buscar_regra_parcelamento(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    regra_id: i32
+) -> Result<Option<RegraParcelamento>, ApiError> {
+    let mut query = Query::new(r#"
+        SELECT 
+            id, 
+            CAST(valor_minimo AS DECIMAL(18,2)), 
+            CAST(valor_maximo AS DECIMAL(18,2)), 
+            codigo_protheus, 
+            descricao
+        FROM regras_parcelamento
+        WHERE id = @P1
+    "#);
+    query.bind(regra_id);
+    
+    let result = query.query(conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar regra de parcelamento: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao processar regra de parcelamento: {}", e)))?;
+    
+    Ok(result.map(|row| RegraParcelamento {
+        id: row.get::<i32, _>(0).unwrap_or(0),
+        valor_minimo: row.get::<Decimal, _>(1).unwrap_or(Decimal::ZERO),
+        valor_maximo: row.get::<Decimal, _>(2),
+        codigo_protheus: row.get::<&str, _>(3).unwrap_or("").trim().to_string(),
+        descricao: row.get::<&str, _>(4).unwrap_or("").trim().to_string(),
+    }))
+}
@@ -1,22 +1,26 @@
 //! 🛒 CRUD Pedidos - Operações básicas de pedidos
 //!
-//! Implementa as operações básicas de criação, leitura, atualização
+//! Implementa as operações básicas de criação, confirmação
 //! e exclusão de pedidos no Portal.
 
 use actix_web::{web, HttpResponse};
-use rust_decimal::Decimal;
 use serde_json::json;
 use tiberius::Query;
 use crate::database::DatabasePools;
 use crate::errors::ApiError;
 use crate::auth::Claims;
-use crate::models::{CriarPedidoRequest, CriarPedidoResponse};
+use crate::auditoria::RegistroAuditoria;
+use crate::models::{CriarPedidoRequest, CriarPedidoResponse, StatusPedido};
+use super::status::{exigir_edicao, exigir_transicao};
+use super::transacao;
+use super::validacao::{decimal_para_sql, exigir_acesso_pedido, validar_cliente, validar_items, ItemValidado};
 
 /// Criar novo pedido no Portal
 pub async fn criar_pedido(
     pedido_req: web::Json<CriarPedidoRequest>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
 ) -> Result<HttpResponse, ApiError> {
     log::info!("Criando novo pedido para cliente: {}", pedido_req.codigo_cliente);
     
@@ -24,13 +28,26 @@ pub async fn criar_pedido(
         .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
     
     // 1. Validar cliente
-    let cliente_id = validar_cliente(&mut conn, &pedido_req).await?;
+    let (cliente_id, grupo_venda) = validar_cliente(&mut conn, &pedido_req, &claims).await?;
     
     // 2. Validar e calcular itens
-    let (items_validados, valor_total) = validar_items(&mut conn, &pedido_req, cliente_id).await?;
+    let (items_validados, valor_total, erros) = validar_items(&mut conn, &pedido_req.items, &grupo_venda).await?;
+    
+    if !erros.is_empty() {
+        log::warn!("Pedido para cliente {} recusado: {} itens inválidos", pedido_req.codigo_cliente, erros.len());
+        return Ok(HttpResponse::BadRequest().json(CriarPedidoResponse {
+            success: false,
+            pedido_id: None,
+            numero_pedido: None,
+            total: None,
+            message: "Pedido possui itens inválidos".to_string(),
+            errors: Some(erros),
+        }));
+    }
     
     // 3. Criar pedido em transação
     let pedido_id = criar_pedido_com_transacao(&mut conn, &pedido_req, cliente_id, items_validados).await?;
+    auditoria.pedido(pedido_id);
     
     Ok(HttpResponse::Ok().json(CriarPedidoResponse {
         success: true,
@@ -46,7 +63,8 @@ pub async fn criar_pedido(
 pub async fn confirmar_pedido(
     pedido_id: web::Path<i32>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
 ) -> Result<HttpResponse, ApiError> {
     log::info!("Confirmando pedido ID: {}", pedido_id);
     
@@ -54,9 +72,11 @@ pub async fn confirmar_pedido(
         .map_err(|e| ApiError::Database(format!("Erro ao conectar: {}", e)))?;
     
     let pedido_id_value = pedido_id.into_inner();
+    auditoria.pedido(pedido_id_value);
+    exigir_acesso_pedido(&mut conn, pedido_id_value, &claims).await?;
     
     // Verificar status atual
-    let mut query = Query::new("SELECT status_pedido FROM pedidos WHERE id = @P1");
+    let mut query = Query::new("SELECT status_pedido FROM pedidos WHERE id = @P1 AND deleted_at IS NULL");
     query.bind(pedido_id_value);
     
     let result = query.query(&mut conn).await
@@ -72,143 +92,221 @@ pub async fn confirmar_pedido(
         })))
     };
     
-    if status != "a confirmar" {
-        return Ok(HttpResponse::BadRequest().json(json!({
-            "success": false,
-            "message": match status.as_str() {
-                "confirmado" => "Pedido já está confirmado",
-                _ => "Pedido não pode ser confirmado neste status"
-            }
-        })));
-    }
+    exigir_transicao(&status, StatusPedido::Confirmado, "confirmar")?;
     
-    // Confirmar pedido
+    // Confirmar pedido - o filtro por status evita confirmar duas vezes em paralelo
     let mut update = Query::new(r#"
         UPDATE pedidos 
-        SET status_pedido = 'confirmado',
+        SET status_pedido = @P2,
             emissao = CONVERT(date, GETDATE()),
             updated_at = GETDATE()
-        WHERE id = @P1
+        WHERE id = @P1 AND status_pedido = @P3
     "#);
     update.bind(pedido_id_value);
-    update.execute(&mut conn).await
+    update.bind(StatusPedido::Confirmado.as_str());
+    update.bind(&status as &str);
+    let result = update.execute(&mut conn).await
         .map_err(|e| ApiError::Database(format!("Erro ao confirmar: {}", e)))?;
     
+    if result.total() == 0 {
+        return Err(ApiError::TransicaoStatusInvalida {
+            operacao: "confirmar".to_string(),
+            status_atual: status,
+            transicoes_permitidas: Vec::new(),
+        });
+    }
+    
     Ok(HttpResponse::Ok().json(json!({
         "success": true,
         "message": "Pedido confirmado com sucesso!"
     })))
 }
 
-/// Buscar pedido específico
-pub async fn buscar_pedido(
+/// Deletar pedido (soft delete)
+/// Apenas pedidos não confirmados podem ser excluídos
+pub async fn deletar_pedido(
     pedido_id: web::Path<i32>,
-    _pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    pools: web::Data<DatabasePools>,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
 ) -> Result<HttpResponse, ApiError> {
-    log::info!("Buscando pedido ID: {}", pedido_id);
+    let pedido_id = pedido_id.into_inner();
+    auditoria.pedido(pedido_id);
+    log::info!("Deletando pedido ID: {} (usuário: {})", pedido_id, claims.sub);
     
-    // TODO: Implementar busca completa do pedido
-    // Para não exceder limite de linhas, implementação simplificada
+    let mut conn = pools.sqlserver_portal.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
     
-    Ok(HttpResponse::Ok().json(json!({
-        "success": true,
-        "pedido_id": pedido_id.into_inner(),
-        "status": "Implementação em andamento",
-        "data": {}
-    })))
-}
-
-/// Atualizar pedido existente  
-pub async fn atualizar_pedido(
-    pedido_id: web::Path<i32>,
-    _pedido_req: web::Json<CriarPedidoRequest>,
-    _pools: web::Data<DatabasePools>,
-    _claims: Claims,
-) -> Result<HttpResponse, ApiError> {
-    log::info!("Atualizando pedido ID: {}", pedido_id);
+    exigir_acesso_pedido(&mut conn, pedido_id, &claims).await?;
+    
+    let mut query = Query::new(r#"
+        SELECT status_pedido, integrado, confirmado
+        FROM pedidos
+        WHERE id = @P1 AND deleted_at IS NULL
+    "#);
+    query.bind(pedido_id);
+    
+    let row = query.query(&mut conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar pedido: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao processar pedido: {}", e)))?
+        .ok_or(ApiError::NotFound)?;
+    
+    let status = row.get::<&str, _>(0).unwrap_or("").trim().to_string();
+    let integrado = row.get::<bool, _>(1).unwrap_or(false);
+    let confirmado = row.get::<bool, _>(2).unwrap_or(false);
+    
+    exigir_edicao(&status, integrado, confirmado, "excluir")?;
+    
+    // Repete as guardas no UPDATE: o pedido pode ter sido confirmado ou integrado após a leitura
+    let mut update = Query::new(r#"
+        UPDATE pedidos
+        SET deleted_at = GETDATE(),
+            updated_at = GETDATE()
+        WHERE id = @P1
+          AND status_pedido = @P2
+          AND integrado = 0
+          AND confirmado = 0
+          AND deleted_at IS NULL
+    "#);
+    update.bind(pedido_id);
+    update.bind(StatusPedido::Rascunho.as_str());
+    let result = update.execute(&mut conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao excluir pedido: {}", e)))?;
+    
+    if result.total() == 0 {
+        return Err(ApiError::Conflict("Pedido deixou de ser rascunho antes da exclusão".to_string()));
+    }
     
-    // TODO: Implementar atualização completa
     Ok(HttpResponse::Ok().json(json!({
         "success": true,
-        "message": "Implementação em andamento"
+        "pedido_id": pedido_id,
+        "message": "Pedido excluído com sucesso"
     })))
 }
 
-/// Deletar pedido
-pub async fn deletar_pedido(
+/// POST /pedidos/{id}/restaurar - Desfaz o soft delete
+pub async fn restaurar_pedido(
     pedido_id: web::Path<i32>,
-    _pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    pools: web::Data<DatabasePools>,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
 ) -> Result<HttpResponse, ApiError> {
-    log::info!("Deletando pedido ID: {}", pedido_id);
+    let pedido_id = pedido_id.into_inner();
+    auditoria.pedido(pedido_id);
+    log::info!("Restaurando pedido ID: {} (usuário: {})", pedido_id, claims.sub);
+    
+    let mut conn = pools.sqlserver_portal.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
+    
+    exigir_acesso_pedido(&mut conn, pedido_id, &claims).await?;
+    
+    let mut update = Query::new(r#"
+        UPDATE pedidos
+        SET deleted_at = NULL,
+            updated_at = GETDATE()
+        WHERE id = @P1 AND deleted_at IS NOT NULL
+    "#);
+    update.bind(pedido_id);
+    
+    let result = update.execute(&mut conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao restaurar pedido: {}", e)))?;
+    
+    if result.total() == 0 {
+        return Err(ApiError::NotFound);
+    }
     
-    // TODO: Implementar soft delete
     Ok(HttpResponse::Ok().json(json!({
         "success": true,
-        "message": "Implementação em andamento"
+        "pedido_id": pedido_id,
+        "message": "Pedido restaurado com sucesso"
     })))
 }
 
 // Helper functions para não exceder 500 linhas no arquivo principal
 
-async fn validar_cliente(
+async fn criar_pedido_com_transacao(
     conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
-    pedido_req: &CriarPedidoRequest
+    pedido_req: &CriarPedidoRequest,
+    cliente_id: i32,
+    items_validados: Vec<ItemValidado>
 ) -> Result<i32, ApiError> {
-    let mut query = Query::new(r#"
-        SELECT id, grupo_venda, ativo 
-        FROM clientes 
-        WHERE codigo = @P1 AND loja = @P2
-    "#);
-    query.bind(&pedido_req.codigo_cliente as &str);
-    query.bind(&pedido_req.loja_cliente as &str);
-    
-    let result = query.query(conn).await
-        .map_err(|e| ApiError::Database(format!("Erro ao buscar cliente: {}", e)))?
-        .into_row().await
-        .map_err(|e| ApiError::Database(format!("Erro ao processar cliente: {}", e)))?;
+    transacao::iniciar(conn).await?;
     
-    match result {
-        Some(row) => {
-            let cliente_id: i32 = row.get(0).unwrap_or(0);
-            let ativo: bool = row.get(2).unwrap_or(false);
-            
-            if !ativo {
-                return Err(ApiError::BadRequest("Cliente inativo".to_string()));
-            }
+    match inserir_pedido_e_items(conn, pedido_req, cliente_id, &items_validados).await {
+        Ok(pedido_id) => {
+            transacao::confirmar(conn).await?;
             
-            Ok(cliente_id)
+            log::info!("Pedido {} criado com {} itens", pedido_id, items_validados.len());
+            Ok(pedido_id)
         },
-        None => Err(ApiError::NotFound)
+        Err(e) => {
+            log::error!("Erro ao criar pedido, desfazendo transação: {}", e);
+            transacao::desfazer(conn).await;
+            Err(e)
+        }
     }
 }
 
-async fn validar_items(
-    _conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+async fn inserir_pedido_e_items(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
     pedido_req: &CriarPedidoRequest,
-    _cliente_id: i32
-) -> Result<(Vec<(i32, i32, f64)>, Decimal), ApiError> {
-    // TODO: Implementar validação completa de itens
-    let mut items_validados = Vec::new();
-    let mut valor_total = Decimal::ZERO;
-    
-    for item in &pedido_req.items {
-        // Validação simplificada
-        items_validados.push((item.produto_id, item.quantidade, 10.0)); // Preço mockado
-        valor_total += Decimal::from(item.quantidade) * Decimal::try_from(10.0).unwrap();
+    cliente_id: i32,
+    items_validados: &[ItemValidado]
+) -> Result<i32, ApiError> {
+    // 1. Cabeçalho do pedido - sempre criado como rascunho ("a confirmar")
+    let mut insert_pedido = Query::new(r#"
+        INSERT INTO pedidos (
+            cliente_id, codigo_cliente, loja_cliente, emissao, natureza, mensagem,
+            status_pedido, integrado, confirmado, tentativas_integracao,
+            regra_condicao_pagamento_id, regra_frete_id,
+            created_at, updated_at
+        ) VALUES (
+            @P1, @P2, @P3, CONVERT(date, @P4), @P5, @P6,
+            @P9, 0, 0, 0,
+            @P7, @P8,
+            GETDATE(), GETDATE()
+        );
+        SELECT CAST(SCOPE_IDENTITY() AS INT) AS id;
+    "#);
+    insert_pedido.bind(cliente_id);
+    insert_pedido.bind(&pedido_req.codigo_cliente as &str);
+    insert_pedido.bind(&pedido_req.loja_cliente as &str);
+    insert_pedido.bind(&pedido_req.emissao as &str);
+    insert_pedido.bind(pedido_req.natureza.as_deref().unwrap_or("10212"));
+    insert_pedido.bind(pedido_req.mensagem.as_deref());
+    insert_pedido.bind(pedido_req.regra_condicao_pagamento_id);
+    insert_pedido.bind(pedido_req.regra_frete_id);
+    insert_pedido.bind(StatusPedido::Rascunho.as_str());
+    
+    let row = insert_pedido.query(conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao inserir pedido: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao obter ID do pedido: {}", e)))?;
+    
+    let pedido_id: i32 = row
+        .and_then(|r| r.get::<i32, _>(0))
+        .ok_or_else(|| ApiError::Database("SCOPE_IDENTITY() não retornou o ID do pedido".to_string()))?;
+    
+    // 2. Itens do pedido - qualquer falha derruba o pedido inteiro
+    for item in items_validados {
+        let mut insert_item = Query::new(r#"
+            INSERT INTO items (
+                pedido_id, produto_id, quantidade, preco_unitario,
+                created_at, updated_at
+            ) VALUES (
+                @P1, @P2, @P3, @P4,
+                GETDATE(), GETDATE()
+            )
+        "#);
+        insert_item.bind(pedido_id);
+        insert_item.bind(item.produto_id);
+        insert_item.bind(item.quantidade);
+        insert_item.bind(decimal_para_sql(item.preco_unitario));
+        
+        insert_item.execute(conn).await
+            .map_err(|e| ApiError::Database(format!("Erro ao inserir item (produto {}): {}", item.produto_id, e)))?;
     }
     
-    Ok((items_validados, valor_total))
-}
-
-async fn criar_pedido_com_transacao(
-    _conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
-    _pedido_req: &CriarPedidoRequest,
-    _cliente_id: i32,
-    _items_validados: Vec<(i32, i32, f64)>
-) -> Result<i32, ApiError> {
-    // TODO: Implementar criação completa com transação
-    // Por ora retorna ID mockado
-    Ok(12345)
+    Ok(pedido_id)
 }
@@ -0,0 +1,248 @@
+//! ✏️ Edição Pedidos - Alteração de pedidos em rascunho
+//!
+//! Substitui o cabeçalho e sincroniza os itens (inclui, remove e altera
+//! quantidades) enquanto o pedido ainda está "a confirmar".
+
+use actix_web::{web, HttpResponse};
+use futures_util::TryStreamExt;
+use rust_decimal::Decimal;
+use serde_json::json;
+use tiberius::{Query, QueryItem};
+use crate::database::DatabasePools;
+use crate::errors::ApiError;
+use crate::auth::Claims;
+use crate::auditoria::RegistroAuditoria;
+use crate::models::{CriarPedidoRequest, CriarPedidoResponse, StatusPedido};
+use super::status::exigir_edicao;
+use super::transacao;
+use super::validacao::{decimal_para_sql, exigir_acesso_pedido, validar_cliente, validar_items, ItemValidado};
+
+/// Item já gravado no pedido
+struct ItemAtual {
+    id: i32,
+    produto_id: i32,
+    quantidade: i32,
+    preco_unitario: Decimal,
+}
+
+/// Resumo das alterações aplicadas nos itens
+#[derive(Debug, Default)]
+struct AlteracoesItems {
+    adicionados: usize,
+    removidos: usize,
+    alterados: usize,
+}
+
+/// PUT /pedidos/{id} - Atualizar pedido em rascunho
+pub async fn atualizar_pedido(
+    pedido_id: web::Path<i32>,
+    pedido_req: web::Json<CriarPedidoRequest>,
+    pools: web::Data<DatabasePools>,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
+) -> Result<HttpResponse, ApiError> {
+    let pedido_id = pedido_id.into_inner();
+    auditoria.pedido(pedido_id);
+    log::info!("Atualizando pedido ID: {}", pedido_id);
+    
+    let mut conn = pools.sqlserver_portal.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
+    
+    exigir_acesso_pedido(&mut conn, pedido_id, &claims).await?;
+    
+    // 1. Só rascunhos podem ser editados
+    let mut query = Query::new("SELECT status_pedido, integrado, confirmado FROM pedidos WHERE id = @P1 AND deleted_at IS NULL");
+    query.bind(pedido_id);
+    
+    let row = query.query(&mut conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar pedido: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao processar pedido: {}", e)))?
+        .ok_or(ApiError::NotFound)?;
+    
+    let status = row.get::<&str, _>(0).unwrap_or("").trim().to_string();
+    let integrado = row.get::<bool, _>(1).unwrap_or(false);
+    let confirmado = row.get::<bool, _>(2).unwrap_or(false);
+    
+    exigir_edicao(&status, integrado, confirmado, "editar")?;
+    
+    // 2. Mesmas validações da criação
+    let (cliente_id, grupo_venda) = validar_cliente(&mut conn, &pedido_req, &claims).await?;
+    let (items_validados, valor_total, erros) = validar_items(&mut conn, &pedido_req.items, &grupo_venda).await?;
+    
+    if !erros.is_empty() {
+        log::warn!("Atualização do pedido {} recusada: {} itens inválidos", pedido_id, erros.len());
+        return Ok(HttpResponse::BadRequest().json(CriarPedidoResponse {
+            success: false,
+            pedido_id: Some(pedido_id),
+            numero_pedido: None,
+            total: None,
+            message: "Pedido possui itens inválidos".to_string(),
+            errors: Some(erros),
+        }));
+    }
+    
+    // 3. Aplicar cabeçalho e diferença de itens em transação
+    let items_atuais = buscar_items_atuais(&mut conn, pedido_id).await?;
+    
+    transacao::iniciar(&mut conn).await?;
+    
+    let alteracoes = match aplicar_alteracoes(&mut conn, pedido_id, &pedido_req, cliente_id, &items_validados, &items_atuais).await {
+        Ok(alteracoes) => {
+            transacao::confirmar(&mut conn).await?;
+            alteracoes
+        },
+        Err(e) => {
+            log::error!("Erro ao atualizar pedido {}, desfazendo transação: {}", pedido_id, e);
+            transacao::desfazer(&mut conn).await;
+            return Err(e);
+        }
+    };
+    
+    log::info!("Pedido {} atualizado: {:?}", pedido_id, alteracoes);
+    
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "pedido_id": pedido_id,
+        "total": valor_total,
+        "items": {
+            "adicionados": alteracoes.adicionados,
+            "removidos": alteracoes.removidos,
+            "alterados": alteracoes.alterados
+        },
+        "message": "Pedido atualizado com sucesso"
+    })))
+}
+
+async fn buscar_items_atuais(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    pedido_id: i32
+) -> Result<Vec<ItemAtual>, ApiError> {
+    let mut query = Query::new(r#"
+        SELECT id, produto_id, quantidade, CAST(preco_unitario AS DECIMAL(18,4))
+        FROM items
+        WHERE pedido_id = @P1
+    "#);
+    query.bind(pedido_id);
+    
+    let mut stream = query.query(conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar itens do pedido: {}", e)))?;
+    
+    let mut items = Vec::new();
+    
+    while let Some(item) = stream.try_next().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler itens do pedido: {}", e)))? {
+        if let QueryItem::Row(row) = item {
+            items.push(ItemAtual {
+                id: row.get::<i32, _>(0).unwrap_or(0),
+                produto_id: row.get::<i32, _>(1).unwrap_or(0),
+                quantidade: row.get::<i32, _>(2).unwrap_or(0),
+                preco_unitario: row.get::<Decimal, _>(3).unwrap_or(Decimal::ZERO),
+            });
+        }
+    }
+    
+    Ok(items)
+}
+
+async fn aplicar_alteracoes(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    pedido_id: i32,
+    pedido_req: &CriarPedidoRequest,
+    cliente_id: i32,
+    items_validados: &[ItemValidado],
+    items_atuais: &[ItemAtual]
+) -> Result<AlteracoesItems, ApiError> {
+    let mut update = Query::new(r#"
+        UPDATE pedidos
+        SET cliente_id = @P2,
+            codigo_cliente = @P3,
+            loja_cliente = @P4,
+            emissao = CONVERT(date, @P5),
+            natureza = @P6,
+            mensagem = @P7,
+            regra_condicao_pagamento_id = @P8,
+            regra_frete_id = @P9,
+            updated_at = GETDATE()
+        WHERE id = @P1
+          AND status_pedido = @P10
+          AND integrado = 0
+          AND confirmado = 0
+          AND deleted_at IS NULL
+    "#);
+    update.bind(pedido_id);
+    update.bind(cliente_id);
+    update.bind(&pedido_req.codigo_cliente as &str);
+    update.bind(&pedido_req.loja_cliente as &str);
+    update.bind(&pedido_req.emissao as &str);
+    update.bind(pedido_req.natureza.as_deref().unwrap_or("10212"));
+    update.bind(pedido_req.mensagem.as_deref());
+    update.bind(pedido_req.regra_condicao_pagamento_id);
+    update.bind(pedido_req.regra_frete_id);
+    update.bind(StatusPedido::Rascunho.as_str());
+    
+    // Confirmação ou integração concorrente entre a leitura e a transação
+    let result = update.execute(conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao atualizar pedido: {}", e)))?;
+    
+    if result.total() == 0 {
+        return Err(ApiError::Conflict("Pedido deixou de ser rascunho durante a edição".to_string()));
+    }
+    
+    let mut alteracoes = AlteracoesItems::default();
+    
+    // Itens que saíram do pedido
+    for atual in items_atuais {
+        if items_validados.iter().any(|item| item.produto_id == atual.produto_id) {
+            continue;
+        }
+        
+        let mut delete = Query::new("DELETE FROM items WHERE id = @P1");
+        delete.bind(atual.id);
+        delete.execute(conn).await
+            .map_err(|e| ApiError::Database(format!("Erro ao remover item (produto {}): {}", atual.produto_id, e)))?;
+        alteracoes.removidos += 1;
+    }
+    
+    // Itens novos ou alterados - o preço é sempre o vigente para o grupo de venda
+    for item in items_validados {
+        match items_atuais.iter().find(|atual| atual.produto_id == item.produto_id) {
+            Some(atual) if atual.quantidade == item.quantidade && atual.preco_unitario == item.preco_unitario => {}
+            Some(atual) => {
+                let mut update_item = Query::new(r#"
+                    UPDATE items
+                    SET quantidade = @P2,
+                        preco_unitario = @P3,
+                        updated_at = GETDATE()
+                    WHERE id = @P1
+                "#);
+                update_item.bind(atual.id);
+                update_item.bind(item.quantidade);
+                update_item.bind(decimal_para_sql(item.preco_unitario));
+                update_item.execute(conn).await
+                    .map_err(|e| ApiError::Database(format!("Erro ao alterar item (produto {}): {}", item.produto_id, e)))?;
+                alteracoes.alterados += 1;
+            }
+            None => {
+                let mut insert_item = Query::new(r#"
+                    INSERT INTO items (
+                        pedido_id, produto_id, quantidade, preco_unitario,
+                        created_at, updated_at
+                    ) VALUES (
+                        @P1, @P2, @P3, @P4,
+                        GETDATE(), GETDATE()
+                    )
+                "#);
+                insert_item.bind(pedido_id);
+                insert_item.bind(item.produto_id);
+                insert_item.bind(item.quantidade);
+                insert_item.bind(decimal_para_sql(item.preco_unitario));
+                insert_item.execute(conn).await
+                    .map_err(|e| ApiError::Database(format!("Erro ao inserir item (produto {}): {}", item.produto_id, e)))?;
+                alteracoes.adicionados += 1;
+            }
+        }
+    }
+    
+    Ok(alteracoes)
+}
@@ -53,8 +53,9 @@ pub struct ProdutoIncluido {
 pub async fn gerar_pedido_com_oportunidades(
     request: web::Json<GerarPedidoRequest>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
+    claims.exigir_cnpj(&request.cnpj)?;
     log::info!("Gerando pedido com oportunidades para CNPJ: {}", request.cnpj);
     
     if request.oportunidades_selecionadas.is_empty() {
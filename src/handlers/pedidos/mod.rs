@@ -2,15 +2,24 @@
 //!
 //! Sistema completo de pedidos:
 //! - CRUD básico de pedidos
+//! - Consulta e listagem de pedidos
+//! - Edição de pedidos em rascunho
 //! - Geração com oportunidades (Card integration)
 //! - Tracking de sugestões aceitas/rejeitadas
 
 // Modules  
 pub mod crud;
+pub mod consulta;
+pub mod edicao;
+pub mod validacao;
+pub mod status;
+pub mod transacao;
 pub mod geracao;
 pub mod tracking;
 
 // Re-exports for main.rs compatibility  
-pub use crud::{criar_pedido, buscar_pedido, atualizar_pedido, deletar_pedido, confirmar_pedido};
+pub use crud::{criar_pedido, deletar_pedido, restaurar_pedido, confirmar_pedido};
+pub use consulta::{buscar_pedido, listar_pedidos};
+pub use edicao::atualizar_pedido;
 pub use geracao::gerar_pedido_com_oportunidades;
 pub use tracking::marcar_item_sugestao;
@@ -0,0 +1,35 @@
+//! 🚦 Status Pedidos - Máquina de estados do pedido
+//!
+//! Guardas usadas por todo handler que altera `pedidos`. Transições
+//! inválidas viram `ApiError::TransicaoStatusInvalida` (409).
+
+use crate::errors::ApiError;
+use crate::models::StatusPedido;
+
+/// Garante que o pedido pode ir de `status_atual` para `destino`
+pub fn exigir_transicao(status_atual: &str, destino: StatusPedido, operacao: &str) -> Result<StatusPedido, ApiError> {
+    match StatusPedido::from_banco(status_atual) {
+        Some(atual) if atual.pode_transitar_para(destino) => Ok(atual),
+        atual => Err(conflito(status_atual, atual, operacao)),
+    }
+}
+
+/// Garante que o pedido ainda é um rascunho editável
+pub fn exigir_edicao(status_atual: &str, integrado: bool, confirmado: bool, operacao: &str) -> Result<(), ApiError> {
+    match StatusPedido::from_banco(status_atual) {
+        Some(atual) if atual.permite_edicao() && !integrado && !confirmado => Ok(()),
+        atual => Err(conflito(status_atual, atual, operacao)),
+    }
+}
+
+fn conflito(status_atual: &str, atual: Option<StatusPedido>, operacao: &str) -> ApiError {
+    log::warn!("Operação '{}' recusada para pedido com status '{}'", operacao, status_atual);
+    
+    ApiError::TransicaoStatusInvalida {
+        operacao: operacao.to_string(),
+        status_atual: status_atual.trim().to_string(),
+        transicoes_permitidas: atual
+            .map(|s| s.transicoes().iter().map(|t| t.as_str().to_string()).collect())
+            .unwrap_or_default(),
+    }
+}
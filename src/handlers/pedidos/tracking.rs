@@ -10,6 +10,8 @@ use tiberius::Query;
 use crate::database::DatabasePools;
 use crate::errors::ApiError;
 use crate::auth::Claims;
+use crate::auditoria::RegistroAuditoria;
+use super::validacao::exigir_acesso_pedido;
 
 #[derive(Debug, Deserialize)]
 pub struct MarcarSugestaoRequest {
@@ -39,9 +41,11 @@ pub async fn marcar_item_sugestao(
     pedido_id: web::Path<i32>,
     request: web::Json<MarcarSugestaoRequest>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
 ) -> Result<HttpResponse, ApiError> {
     let pedido_id_val = pedido_id.into_inner();
+    auditoria.pedido(pedido_id_val);
     
     // USAR pedido_id do request para validação adicional
     if request.pedido_id != pedido_id_val {
@@ -69,6 +73,8 @@ pub async fn marcar_item_sugestao(
     let mut conn = pools.sqlserver_portal.get().await
         .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
     
+    exigir_acesso_pedido(&mut conn, pedido_id_val, &claims).await?;
+    
     // 1. Verificar se pedido e item existem
     let item_valido = verificar_pedido_item(&mut conn, pedido_id_val, &request.item_id).await?;
     if !item_valido {
@@ -0,0 +1,31 @@
+//! 🔒 Transações - BEGIN/COMMIT/ROLLBACK em conexões SQL Server (Portal e Protheus)
+//!
+//! O tiberius não tem API de transação; os comandos são enviados
+//! diretamente na mesma conexão do pool.
+
+use crate::errors::ApiError;
+
+type Conexao<'a> = bb8::PooledConnection<'a, bb8_tiberius::ConnectionManager>;
+
+pub async fn iniciar(conn: &mut Conexao<'_>) -> Result<(), ApiError> {
+    executar(conn, "BEGIN TRANSACTION").await
+        .map_err(|e| ApiError::Database(format!("Erro ao iniciar transação: {}", e)))
+}
+
+pub async fn confirmar(conn: &mut Conexao<'_>) -> Result<(), ApiError> {
+    executar(conn, "COMMIT TRANSACTION").await
+        .map_err(|e| ApiError::Database(format!("Erro ao confirmar transação: {}", e)))
+}
+
+/// Desfaz a transação aberta. Falhas são apenas logadas: o erro que
+/// interessa ao cliente é o que causou o rollback.
+pub async fn desfazer(conn: &mut Conexao<'_>) {
+    if let Err(e) = executar(conn, "IF @@TRANCOUNT > 0 ROLLBACK TRANSACTION").await {
+        log::error!("Erro ao desfazer transação: {}", e);
+    }
+}
+
+async fn executar(conn: &mut Conexao<'_>, sql: &str) -> Result<(), tiberius::error::Error> {
+    conn.simple_query(sql).await?.into_results().await?;
+    Ok(())
+}
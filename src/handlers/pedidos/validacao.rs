@@ -0,0 +1,196 @@
+//! ✅ Validação Pedidos - Regras compartilhadas por criação e edição
+//!
+//! Valida cliente e itens conforme docs/REGRAS_NEGOCIO_PEDIDOS.md:
+//! produto ativo, preço no grupo de venda, embalagem mínima e saldo.
+
+use rust_decimal::Decimal;
+use tiberius::{numeric::Numeric, Query};
+use crate::auth::Claims;
+use crate::errors::ApiError;
+use crate::models::{CriarPedidoRequest, ItemPedidoRequest};
+
+/// Item já validado, com o preço do grupo de venda do cliente
+#[derive(Debug)]
+pub struct ItemValidado {
+    pub produto_id: i32,
+    pub quantidade: i32,
+    pub preco_unitario: Decimal,
+}
+
+/// Valida o cliente do pedido e retorna `(cliente_id, grupo_venda)`
+/// Tokens vinculados a CNPJ só podem usar os próprios clientes (403)
+pub async fn validar_cliente(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    pedido_req: &CriarPedidoRequest,
+    claims: &Claims,
+) -> Result<(i32, String), ApiError> {
+    let mut query = Query::new(r#"
+        SELECT id, grupo_venda, ativo, cnpj
+        FROM clientes 
+        WHERE codigo = @P1 AND loja = @P2
+    "#);
+    query.bind(&pedido_req.codigo_cliente as &str);
+    query.bind(&pedido_req.loja_cliente as &str);
+    
+    let result = query.query(conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar cliente: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao processar cliente: {}", e)))?;
+    
+    match result {
+        Some(row) => {
+            let cliente_id: i32 = row.get(0).unwrap_or(0);
+            let grupo_venda = row.get::<&str, _>(1).unwrap_or("").trim().to_string();
+            let ativo: bool = row.get(2).unwrap_or(false);
+            
+            claims.exigir_cnpj(row.get::<&str, _>(3).unwrap_or(""))?;
+            
+            if !ativo {
+                return Err(ApiError::BadRequest("Cliente inativo".to_string()));
+            }
+            
+            if grupo_venda.is_empty() {
+                return Err(ApiError::BadRequest("Cliente sem grupo de venda definido".to_string()));
+            }
+            
+            Ok((cliente_id, grupo_venda))
+        },
+        None => Err(ApiError::NotFound)
+    }
+}
+
+/// Garante que tokens vinculados a CNPJ só acessem pedidos dos próprios clientes.
+/// Inclui pedidos excluídos (necessário para restaurar).
+pub async fn exigir_acesso_pedido(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    pedido_id: i32,
+    claims: &Claims,
+) -> Result<(), ApiError> {
+    if !claims.restrito_a_cnpj() {
+        return Ok(());
+    }
+    
+    let mut query = Query::new(r#"
+        SELECT c.cnpj
+        FROM pedidos p
+        INNER JOIN clientes c ON c.id = p.cliente_id
+        WHERE p.id = @P1
+    "#);
+    query.bind(pedido_id);
+    
+    let row = query.query(conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar cliente do pedido: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao processar cliente do pedido: {}", e)))?
+        .ok_or(ApiError::NotFound)?;
+    
+    claims.exigir_cnpj(row.get::<&str, _>(0).unwrap_or(""))
+}
+
+/// Valida cada item contra `produtos`/`precos_produtos` usando o grupo de venda do cliente.
+/// Retorna os itens válidos, o total calculado e a lista de erros por item.
+pub async fn validar_items(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    items: &[ItemPedidoRequest],
+    grupo_venda: &str
+) -> Result<(Vec<ItemValidado>, Decimal, Vec<String>), ApiError> {
+    let mut items_validados = Vec::new();
+    let mut valor_total = Decimal::ZERO;
+    let mut erros = Vec::new();
+    
+    if items.is_empty() {
+        erros.push("Pedido deve conter pelo menos um item".to_string());
+        return Ok((items_validados, valor_total, erros));
+    }
+    
+    for (index, item) in items.iter().enumerate() {
+        let posicao = index + 1;
+        
+        if items[..index].iter().any(|anterior| anterior.produto_id == item.produto_id) {
+            erros.push(format!("Item {} (produto {}): produto informado mais de uma vez", posicao, item.produto_id));
+            continue;
+        }
+        
+        if item.quantidade <= 0 {
+            erros.push(format!("Item {} (produto {}): quantidade deve ser maior que zero", posicao, item.produto_id));
+            continue;
+        }
+        
+        let mut query = Query::new(r#"
+            SELECT 
+                p.codigo,
+                p.status,
+                p.saldo,
+                p.quantidade_minima_embalagem,
+                CAST(pp.preco AS DECIMAL(18,4)) as preco
+            FROM produtos p
+            LEFT JOIN precos_produtos pp ON pp.codigo_produto = p.codigo 
+                AND pp.grupo_venda = @P2
+            WHERE p.id = @P1
+        "#);
+        query.bind(item.produto_id);
+        query.bind(grupo_venda);
+        
+        let result = query.query(conn).await
+            .map_err(|e| ApiError::Database(format!("Erro ao buscar produto {}: {}", item.produto_id, e)))?
+            .into_row().await
+            .map_err(|e| ApiError::Database(format!("Erro ao processar produto {}: {}", item.produto_id, e)))?;
+        
+        let row = match result {
+            Some(row) => row,
+            None => {
+                erros.push(format!("Item {} (produto {}): produto não encontrado", posicao, item.produto_id));
+                continue;
+            }
+        };
+        
+        let codigo = row.get::<&str, _>(0).unwrap_or("").trim().to_string();
+        let status: bool = row.get(1).unwrap_or(false);
+        let saldo: i32 = row.get(2).unwrap_or(0);
+        let embalagem: i32 = row.get::<i32, _>(3).unwrap_or(1).max(1);
+        let preco: Option<Decimal> = row.get(4);
+        
+        if !status {
+            erros.push(format!("Item {} ({}): produto inativo", posicao, codigo));
+            continue;
+        }
+        
+        if item.quantidade % embalagem != 0 {
+            erros.push(format!(
+                "Item {} ({}): quantidade {} não é múltipla da embalagem mínima de {}",
+                posicao, codigo, item.quantidade, embalagem
+            ));
+            continue;
+        }
+        
+        if item.quantidade > saldo {
+            erros.push(format!(
+                "Item {} ({}): quantidade {} excede o saldo disponível de {}",
+                posicao, codigo, item.quantidade, saldo
+            ));
+            continue;
+        }
+        
+        let preco_unitario = match preco {
+            Some(p) if p > Decimal::ZERO => p,
+            _ => {
+                erros.push(format!("Item {} ({}): sem preço para o grupo de venda {}", posicao, codigo, grupo_venda));
+                continue;
+            }
+        };
+        
+        valor_total += preco_unitario * Decimal::from(item.quantidade);
+        items_validados.push(ItemValidado {
+            produto_id: item.produto_id,
+            quantidade: item.quantidade,
+            preco_unitario,
+        });
+    }
+    
+    Ok((items_validados, valor_total, erros))
+}
+
+/// O tiberius só aceita `Decimal` por referência; `bind` precisa de um `Numeric` owned
+pub fn decimal_para_sql(valor: Decimal) -> Numeric {
+    Numeric::new_with_scale(valor.mantissa(), valor.scale() as u8)
+}
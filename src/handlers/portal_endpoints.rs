@@ -57,9 +57,10 @@ pub struct Produto {
 pub async fn buscar_franqueado(
     path: web::Path<String>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
     let cnpj = path.into_inner();
+    claims.exigir_cnpj(&cnpj)?;
     log::info!("Buscando franqueado por CNPJ: {}", cnpj);
     
     let mut conn = pools.sqlserver_portal.get().await
@@ -123,8 +124,10 @@ pub async fn buscar_franqueado(
 pub async fn listar_franqueados(
     query_params: web::Query<ListarFranqueadosParams>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
+    // Listagem da rede inteira: não disponível para tokens vinculados a CNPJ
+    claims.exigir_acesso_irrestrito()?;
     log::info!("Listando franqueados");
     
     let limite = query_params.limite.unwrap_or(100).min(500); // Máximo 500
@@ -186,8 +189,10 @@ pub async fn listar_franqueados(
 pub async fn buscar_franqueados(
     query_params: web::Query<BuscarFranqueadosParams>,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
+    // Busca na rede inteira: não disponível para tokens vinculados a CNPJ
+    claims.exigir_acesso_irrestrito()?;
     let termo = query_params.q.as_deref().unwrap_or("");
     let limite = query_params.limite.unwrap_or(20).min(100);
     
@@ -380,7 +385,7 @@ pub async fn buscar_produtos(
 }
 
 // Helper function
-fn normalizar_cnpj(cnpj: &str) -> String {
+pub fn normalizar_cnpj(cnpj: &str) -> String {
     if cnpj.len() == 14 && !cnpj.contains("/") {
         // Adicionar formatação se vier apenas dígitos
         format!("{}.{}.{}/{}-{}", 
@@ -1,11 +1,16 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
 use tiberius::{Query, Row, QueryItem};
 use futures_util::TryStreamExt;
+use crate::config::Settings;
 use crate::database::DatabasePools;
 use crate::errors::ApiError;
 use crate::auth::Claims;
+use crate::auditoria::RegistroAuditoria;
+use crate::handlers::consulta_livre::{consultar_sqlserver, parametros_livres};
+use crate::handlers::consulta_stream::{responder, stream_sqlserver, OpcoesSaida};
+use crate::handlers::validacao_sql::{resposta_restricao, validar_somente_leitura, Dialeto};
 
 #[derive(Debug, Deserialize)]
 pub struct QueryRequest {
@@ -37,98 +42,93 @@ pub struct ProdutoPortal {
 
 /// Executa query customizada no banco do Portal
 /// 🔧 MELHORIA: Feedback detalhado e suporte aprimorado para SELECT *
-/// 🛡️ SEGURANÇA: Mantém validações de segurança (apenas SELECT implícito via tiberius)
+/// 🛡️ SEGURANÇA: Apenas SELECT permitido (mesma validação das queries FC e Protheus)
 pub async fn query_portal(
     query: web::Json<QueryRequest>,
+    opcoes: web::Query<OpcoesSaida>,
+    req: HttpRequest,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    settings: web::Data<Settings>,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
 ) -> Result<HttpResponse, ApiError> {
+    // SQL livre não respeita vínculo de CNPJ
+    claims.exigir_acesso_irrestrito()?;
     let sql = query.sql.trim();
-    log::info!("🔍 Executando query no Portal SQL Server: {}", sql);
-    
-    // 🔌 OBTER CONEXÃO DO POOL SQL SERVER
-    let mut conn = match pools.sqlserver_portal.get().await {
-        Ok(conn) => conn,
-        Err(e) => {
-            log::error!("❌ Erro ao conectar no Portal SQL Server: {}", e);
-            return Ok(HttpResponse::InternalServerError().json(json!({
-                "success": false,
-                "error": "CONNECTION_POOL_ERROR",
-                "message": "Erro na conexão com banco Portal (SQL Server)",
-                "details": e.to_string()
-            })));
-        }
-    };
+    auditoria.sql(sql, query.params.as_deref());
     
-    // 📝 PREPARAR QUERY
-    let mut query_obj = Query::new(sql);
-    
-    // 📎 ADICIONAR PARÂMETROS se fornecidos
-    if let Some(params) = &query.params {
-        log::debug!("📎 Adicionando {} parâmetros à query", params.len());
-        for (i, param) in params.iter().enumerate() {
-            match param {
-                serde_json::Value::String(s) => {
-                    query_obj.bind(s);
-                    log::debug!("  Param {}: String = '{}'", i, s);
-                },
-                serde_json::Value::Number(n) => {
-                    if let Some(i_val) = n.as_i64() {
-                        query_obj.bind(i_val);
-                        log::debug!("  Param {}: Int64 = {}", i, i_val);
-                    } else if let Some(f_val) = n.as_f64() {
-                        query_obj.bind(f_val);
-                        log::debug!("  Param {}: Float64 = {}", i, f_val);
-                    }
-                },
-                serde_json::Value::Bool(b) => {
-                    query_obj.bind(*b);
-                    log::debug!("  Param {}: Bool = {}", i, b);
-                },
-                _ => {
-                    log::warn!("  Param {}: Tipo não suportado, ignorado", i);
-                },
-            }
-        }
+    // 🔍 VALIDAÇÃO DE SEGURANÇA: Apenas uma consulta SELECT, sem efeitos colaterais
+    if let Err(motivo) = validar_somente_leitura(sql, Dialeto::SqlServer) {
+        log::warn!("Consulta rejeitada no Portal ({}): {}", motivo, sql);
+        return Ok(resposta_restricao(&motivo, &[
+            "SELECT * FROM pedidos WHERE id = @P1",
+            "SELECT TOP 10 codigo, descricao FROM produtos WHERE status = 1"
+        ]));
     }
     
-    // 🚀 EXECUTAR QUERY
-    let result = query_obj.query(&mut conn).await
-        .map_err(|e| {
-            log::error!("❌ Erro ao executar query SQL Server: {}", e);
-            ApiError::Database(format!("Erro ao executar query: {}", e))
-        })?;
-    
-    let mut rows = Vec::new();
+    log::info!("🔍 Executando query no Portal SQL Server: {}", sql);
     
-    // 🔄 CONSUMIR STREAM e coletar resultados
-    let mut stream = result;
-    while let Some(item) = stream.try_next().await
-        .map_err(|e| {
-            log::error!("❌ Erro ao ler resultados SQL Server: {}", e);
-            ApiError::Database(format!("Erro ao ler resultados: {}", e))
-        })? {
-        match item {
-            QueryItem::Row(row) => {
-                // 🎯 USAR NOVA CONVERSÃO ROBUSTA
-                rows.push(row_to_json(&row));
-            }
-            _ => {} // Ignorar outros tipos de itens (metadata, etc.)
-        }
+    // 🌊 STREAMING (NDJSON / JSON em partes / CSV / Parquet): linhas enviadas à medida que chegam do banco
+    let formato = opcoes.formato(&req);
+    if formato.streaming() {
+        let conexao = pools.sqlserver_portal_leitura.clone();
+        let (sql, params, config) = (sql.to_string(), parametros_livres(query.params.as_deref()), settings.consulta_livre.clone());
+        return responder(formato, &opcoes, config.max_linhas, move |saida| {
+            stream_sqlserver(conexao, sql, params, config, saida)
+        }).await;
     }
     
+    // 🚀 EXECUTAR em conexão dedicada (login somente leitura, isolamento e tempo limite configurados)
+    let (rows, truncado) = consultar_sqlserver(
+        &pools.sqlserver_portal_leitura,
+        sql,
+        query.params.as_deref(),
+        &settings.consulta_livre,
+    ).await?;
+    
     log::info!("✅ Query SQL Server executada com sucesso. {} registros retornados", rows.len());
     
     Ok(HttpResponse::Ok().json(json!({
         "success": true,
         "data": rows,
         "count": rows.len(),
+        "truncated": truncado,
         "message": format!("Query executada com sucesso no Portal. {} registros retornados.", rows.len()),
         "database": "SQL Server (Portal de Pedidos)"
     })))
 }
 
 
+/// Vincula parâmetros JSON (@P1, @P2, ...) a uma query tiberius
+/// Strings, números e booleanos são suportados; demais tipos são ignorados
+pub(crate) fn bind_json_params<'a>(query_obj: &mut Query<'a>, params: &'a [serde_json::Value]) {
+    log::debug!("📎 Adicionando {} parâmetros à query", params.len());
+    for (i, param) in params.iter().enumerate() {
+        match param {
+            serde_json::Value::String(s) => {
+                query_obj.bind(s.as_str());
+                log::debug!("  Param {}: String = '{}'", i, s);
+            },
+            serde_json::Value::Number(n) => {
+                if let Some(i_val) = n.as_i64() {
+                    query_obj.bind(i_val);
+                    log::debug!("  Param {}: Int64 = {}", i, i_val);
+                } else if let Some(f_val) = n.as_f64() {
+                    query_obj.bind(f_val);
+                    log::debug!("  Param {}: Float64 = {}", i, f_val);
+                }
+            },
+            serde_json::Value::Bool(b) => {
+                query_obj.bind(*b);
+                log::debug!("  Param {}: Bool = {}", i, b);
+            },
+            _ => {
+                log::warn!("  Param {}: Tipo não suportado, ignorado", i);
+            },
+        }
+    }
+}
+
 /// Lista produtos com preços por grupo de venda
 pub async fn listar_produtos_por_grupo(
     params: web::Query<ProdutosParams>,
@@ -209,11 +209,18 @@ pub async fn listar_produtos_por_grupo(
     })))
 }
 
+/// Valores da linha na ordem das colunas (streaming: nomes e tipos vêm dos metadados)
+pub(crate) fn linha_para_valores(row: &Row) -> Vec<serde_json::Value> {
+    row.columns().iter().enumerate()
+        .map(|(i, col)| convert_sqlserver_value_to_json(row, i, col.column_type()))
+        .collect()
+}
+
 /// Converte uma Row do tiberius para JSON com suporte robusto a todos os tipos SQL Server
 /// 🔧 MELHORIA CRÍTICA: Suporta SELECT * e todos os tipos SQL Server comuns  
 /// 🛡️ COMPATIBILIDADE: 100% compatível com código anterior
 /// 📊 PROBLEMA RESOLVIDO: SELECT * FROM clientes agora funciona perfeitamente
-fn row_to_json(row: &Row) -> serde_json::Value {
+pub(crate) fn row_to_json(row: &Row) -> serde_json::Value {
     let mut obj = serde_json::Map::new();
     
     for (i, col) in row.columns().iter().enumerate() {
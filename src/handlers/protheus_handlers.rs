@@ -1,49 +1,257 @@
-use actix_web::{web, HttpResponse};
-use serde::Deserialize;
+use actix_web::{web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tiberius::{Query, QueryItem};
+use crate::config::Settings;
 use crate::database::DatabasePools;
 use crate::errors::ApiError;
 use crate::auth::Claims;
+use crate::auditoria::RegistroAuditoria;
+use crate::models::StatusPedido;
+use crate::handlers::consulta_livre::{consultar_sqlserver, parametros_livres};
+use crate::handlers::consulta_stream::{responder, stream_sqlserver, OpcoesSaida};
+use crate::handlers::validacao_sql::{resposta_restricao, validar_somente_leitura, Dialeto};
 
 #[derive(Debug, Deserialize)]
 pub struct QueryRequest {
     #[serde(alias = "query")]  // Aceita tanto "sql" quanto "query"
     pub sql: String,
-    #[allow(dead_code)] // Usado em portal_handlers.rs através de pattern matching
     pub params: Option<Vec<serde_json::Value>>,
 }
 
+/// Pedido no Protheus (ZC7010 cabeçalho / ZC8010 itens)
+/// Campos de faturamento e entrega com os mesmos nomes do `Pedido` do Portal
+#[derive(Debug, Serialize)]
+pub struct PedidoProtheus {
+    pub numero_pedido: String,
+    pub filial: String,
+    pub codigo_cliente: String,
+    pub loja_cliente: String,
+    pub emissao: Option<String>,
+    pub status_protheus: String,
+    pub status_liberacao: Option<String>,
+    pub numero_nota_fiscal: Option<String>,
+    pub serie_nota_fiscal: Option<String>,
+    pub data_faturamento: Option<String>,
+    pub transportadora: Option<String>,
+    pub rastreio_carga: Option<String>,
+    pub items: Vec<ItemPedidoProtheus>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ItemPedidoProtheus {
+    pub item: String,
+    pub codigo_produto: String,
+    pub quantidade: f64,
+    pub preco_unitario: f64,
+    pub valor_total: f64,
+}
+
+impl PedidoProtheus {
+    /// Status equivalente no Portal, derivado do faturamento e da expedição
+    pub fn status_portal(&self) -> StatusPedido {
+        if self.rastreio_carga.is_some() {
+            StatusPedido::Expedido
+        } else if self.numero_nota_fiscal.is_some() {
+            StatusPedido::Faturado
+        } else {
+            StatusPedido::Integrado
+        }
+    }
+}
+
 /// Executa query customizada no banco do Protheus
+/// 🛡️ SEGURANÇA: Apenas SELECT permitido (mesma validação das queries FC e Portal)
 pub async fn query_protheus(
     query: web::Json<QueryRequest>,
+    opcoes: web::Query<OpcoesSaida>,
+    req: HttpRequest,
     pools: web::Data<DatabasePools>,
-    _claims: Claims,
+    settings: web::Data<Settings>,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
 ) -> Result<HttpResponse, ApiError> {
-    log::info!("Executando query no Protheus: {}", query.sql);
+    // SQL livre não respeita vínculo de CNPJ
+    claims.exigir_acesso_irrestrito()?;
+    let sql = query.sql.trim();
+    auditoria.sql(sql, query.params.as_deref());
     
-    let _conn = pools.sqlserver_protheus.get().await
-        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Protheus: {}", e)))?;
+    // 🔍 VALIDAÇÃO DE SEGURANÇA: Apenas uma consulta SELECT, sem efeitos colaterais
+    if let Err(motivo) = validar_somente_leitura(sql, Dialeto::SqlServer) {
+        log::warn!("Consulta rejeitada no Protheus ({}): {}", motivo, sql);
+        return Ok(resposta_restricao(&motivo, &[
+            "SELECT * FROM ZC7010 WHERE ZC7_NUM = @P1",
+            "SELECT B1_COD, B1_DESC FROM SB1010 WHERE D_E_L_E_T_ = ' '"
+        ]));
+    }
+    
+    log::info!("🔍 Executando query no Protheus: {}", sql);
+    
+    // 🌊 STREAMING (NDJSON / JSON em partes / CSV / Parquet): linhas enviadas à medida que chegam do banco
+    let formato = opcoes.formato(&req);
+    if formato.streaming() {
+        let conexao = pools.sqlserver_protheus_leitura.clone();
+        let (sql, params, config) = (sql.to_string(), parametros_livres(query.params.as_deref()), settings.consulta_livre.clone());
+        return responder(formato, &opcoes, config.max_linhas, move |saida| {
+            stream_sqlserver(conexao, sql, params, config, saida)
+        }).await;
+    }
+    
+    // 🚀 EXECUTAR em conexão dedicada (login somente leitura, isolamento e tempo limite configurados)
+    let (rows, truncado) = consultar_sqlserver(
+        &pools.sqlserver_protheus_leitura,
+        sql,
+        query.params.as_deref(),
+        &settings.consulta_livre,
+    ).await?;
+    
+    log::info!("✅ Query Protheus executada com sucesso. {} registros retornados", rows.len());
     
-    // Por enquanto apenas retornamos uma resposta de placeholder
     Ok(HttpResponse::Ok().json(json!({
         "success": true,
-        "message": "Handler Protheus em desenvolvimento",
-        "database": "SIGAOFC"
+        "data": rows,
+        "count": rows.len(),
+        "truncated": truncado,
+        "message": format!("Query executada com sucesso no Protheus. {} registros retornados.", rows.len()),
+        "database": "SQL Server (Protheus ERP)"
     })))
 }
 
 /// Busca status de pedido no Protheus
 pub async fn status_pedido_protheus(
     numero: web::Path<String>,
-    _pools: web::Data<DatabasePools>,
+    pools: web::Data<DatabasePools>,
+    settings: web::Data<Settings>,
     _claims: Claims,
 ) -> Result<HttpResponse, ApiError> {
+    let numero = numero.into_inner();
     log::info!("Buscando status do pedido {} no Protheus", numero);
     
-    // TODO: Implementar query real nas tabelas ZC7010/ZC8010
+    let mut conn = pools.sqlserver_protheus.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Protheus: {}", e)))?;
+    
+    let pedido = buscar_pedido_protheus(&mut conn, &settings.integracao.filial_protheus, &numero).await?
+        .ok_or(ApiError::NotFound)?;
+    
+    let status_portal = pedido.status_portal();
+    
     Ok(HttpResponse::Ok().json(json!({
         "success": true,
-        "numero_pedido": numero.into_inner(),
-        "status": "em desenvolvimento"
+        "numero_pedido": pedido.numero_pedido.clone(),
+        "status": status_portal.as_str(),
+        "pedido": pedido
     })))
 }
+
+/// Carrega cabeçalho, nota, transportadora e itens de um pedido do Protheus na filial informada
+/// Retorna `None` se o pedido não existir (ou estiver deletado)
+pub async fn buscar_pedido_protheus(
+    conn: &mut bb8::PooledConnection<'_, bb8_tiberius::ConnectionManager>,
+    filial: &str,
+    numero: &str,
+) -> Result<Option<PedidoProtheus>, ApiError> {
+    // Datas no Protheus são CHAR(8) YYYYMMDD e campos vazios vêm com espaços
+    let mut query = Query::new(r#"
+        SELECT TOP 1
+            zc7.ZC7_NUM,
+            zc7.ZC7_FILIAL,
+            zc7.ZC7_CLIENT,
+            zc7.ZC7_LOJA,
+            zc7.ZC7_EMISSA,
+            zc7.ZC7_STATUS,
+            zc7.ZC7_LIBER,
+            sf2.F2_DOC,
+            sf2.F2_SERIE,
+            sf2.F2_EMISSAO,
+            COALESCE(
+                NULLIF(RTRIM(sa4.A4_NOME), ''),
+                NULLIF(RTRIM(sf2.F2_TRANSP), ''),
+                NULLIF(RTRIM(zc7.ZC7_TRANSP), '')
+            ) as transportadora,
+            zc7.ZC7_RASTRE
+        FROM ZC7010 zc7
+        LEFT JOIN SF2010 sf2 ON sf2.F2_FILIAL = zc7.ZC7_FILIAL
+            AND sf2.F2_DOC = zc7.ZC7_NOTA
+            AND sf2.F2_SERIE = zc7.ZC7_SERIE
+            AND sf2.D_E_L_E_T_ = ' '
+        LEFT JOIN SA4010 sa4 ON sa4.A4_COD = COALESCE(NULLIF(RTRIM(sf2.F2_TRANSP), ''), NULLIF(RTRIM(zc7.ZC7_TRANSP), ''))
+            AND sa4.D_E_L_E_T_ = ' '
+        WHERE zc7.ZC7_FILIAL = @P1
+          AND zc7.ZC7_NUM = @P2
+          AND zc7.D_E_L_E_T_ = ' '
+        ORDER BY zc7.R_E_C_N_O_ DESC
+    "#);
+    query.bind(filial);
+    query.bind(numero);
+    
+    let row = match query.query(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar pedido no Protheus: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao processar pedido do Protheus: {}", e)))? {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+    
+    let texto = |i: usize| row.get::<&str, _>(i)
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string());
+    
+    let mut pedido = PedidoProtheus {
+        numero_pedido: texto(0).unwrap_or_default(),
+        filial: texto(1).unwrap_or_default(),
+        codigo_cliente: texto(2).unwrap_or_default(),
+        loja_cliente: texto(3).unwrap_or_default(),
+        emissao: texto(4).map(|d| data_protheus(&d)),
+        status_protheus: texto(5).unwrap_or_default(),
+        status_liberacao: texto(6),
+        numero_nota_fiscal: texto(7),
+        serie_nota_fiscal: texto(8),
+        data_faturamento: texto(9).map(|d| data_protheus(&d)),
+        transportadora: texto(10),
+        rastreio_carga: texto(11),
+        items: Vec::new(),
+    };
+    
+    let mut query_items = Query::new(r#"
+        SELECT 
+            ZC8_ITEM,
+            ZC8_PRODUT,
+            ZC8_QUANT,
+            ZC8_PRCVEN,
+            ZC8_VALOR
+        FROM ZC8010
+        WHERE ZC8_FILIAL = @P1
+          AND ZC8_NUM = @P2
+          AND D_E_L_E_T_ = ' '
+        ORDER BY ZC8_ITEM
+    "#);
+    query_items.bind(pedido.filial.as_str());
+    query_items.bind(pedido.numero_pedido.as_str());
+    
+    let mut stream = query_items.query(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar itens no Protheus: {}", e)))?;
+    
+    while let Some(item) = stream.try_next().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler itens do Protheus: {}", e)))? {
+        if let QueryItem::Row(row) = item {
+            pedido.items.push(ItemPedidoProtheus {
+                item: row.get::<&str, _>(0).unwrap_or("").trim().to_string(),
+                codigo_produto: row.get::<&str, _>(1).unwrap_or("").trim().to_string(),
+                quantidade: row.get::<f64, _>(2).unwrap_or(0.0),
+                preco_unitario: row.get::<f64, _>(3).unwrap_or(0.0),
+                valor_total: row.get::<f64, _>(4).unwrap_or(0.0),
+            });
+        }
+    }
+    
+    Ok(Some(pedido))
+}
+
+/// Converte data CHAR(8) do Protheus (YYYYMMDD) para YYYY-MM-DD
+fn data_protheus(valor: &str) -> String {
+    chrono::NaiveDate::parse_from_str(valor, "%Y%m%d")
+        .map(|d| d.to_string())
+        .unwrap_or_else(|_| valor.to_string())
+}
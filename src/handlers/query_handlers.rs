@@ -1,12 +1,20 @@
 // src/handlers/query_handlers.rs
 // Handler para consultas SQL personalizadas
 
-use actix_web::{web, HttpResponse, Result};
-use deadpool_postgres::Pool;
+use actix_web::{web, HttpRequest, HttpResponse, Result};
 use serde::Deserialize;
 use serde_json::{json, Value};
 use tokio_postgres::Row;
 
+use crate::auditoria::RegistroAuditoria;
+use crate::auth::Claims;
+use crate::config::Settings;
+use crate::database::DatabasePools;
+use crate::errors::ApiError;
+use crate::handlers::consulta_stream::{responder, stream_postgres, OpcoesSaida};
+use crate::handlers::consulta_livre::{escrita_bloqueada, tempo_esgotado, transacao_somente_leitura};
+use crate::handlers::validacao_sql::{resposta_restricao, validar_somente_leitura, Dialeto};
+
 /// Estrutura para receber consultas SQL personalizadas
 #[derive(Debug, Deserialize)]
 pub struct CustomQueryRequest {
@@ -19,30 +27,33 @@ pub struct CustomQueryRequest {
 /// 🛡️ SEGURANÇA: Mantém validações rigorosas - apenas SELECT permitido
 /// 📊 USABILIDADE: Mensagens de erro mais claras e informativas
 pub async fn execute_custom_query(
-    pool: web::Data<Pool>,
+    pools: web::Data<DatabasePools>,
+    settings: web::Data<Settings>,
     query_req: web::Json<CustomQueryRequest>,
+    opcoes: web::Query<OpcoesSaida>,
+    req: HttpRequest,
+    claims: Claims,
+    auditoria: RegistroAuditoria,
 ) -> Result<HttpResponse> {
+    // SQL livre não respeita vínculo de CNPJ
+    claims.exigir_acesso_irrestrito()?;
     let query = query_req.query.trim();
+    auditoria.sql(query, None);
     
-    // 🔍 VALIDAÇÃO DE SEGURANÇA: Apenas consultas SELECT
-    if !is_select_query(query) {
-        log::warn!("Tentativa de execução de query não-SELECT: {}", query);
-        return Ok(HttpResponse::BadRequest().json(json!({
-            "success": false,
-            "error": "SECURITY_RESTRICTION",
-            "message": "Apenas consultas SELECT são permitidas por motivos de segurança",
-            "allowed_examples": [
-                "SELECT * FROM clientes",
-                "SELECT nome, email FROM clientes WHERE ativo = true",
-                "SELECT COUNT(*) FROM pedidos WHERE data >= '2024-01-01'"
-            ]
-        })));
+    // 🔍 VALIDAÇÃO DE SEGURANÇA: Apenas uma consulta SELECT, sem efeitos colaterais
+    if let Err(motivo) = validar_somente_leitura(query, Dialeto::Postgres) {
+        log::warn!("Consulta rejeitada ({}): {}", motivo, query);
+        return Ok(resposta_restricao(&motivo, &[
+            "SELECT * FROM clientes",
+            "SELECT nome, email FROM clientes WHERE ativo = true",
+            "SELECT COUNT(*) FROM pedidos WHERE data >= '2024-01-01'"
+        ]));
     }
     
     log::info!("🔍 Executando consulta personalizada: {}", query);
 
-    // 🔌 OBTER CONEXÃO DO POOL
-    let client = match pool.get().await {
+    // 🔌 OBTER CONEXÃO DO POOL DE SQL LIVRE (separado do pool principal)
+    let mut client = match pools.postgres_fc_leitura.get().await {
         Ok(client) => client,
         Err(e) => {
             log::error!("Erro no pool de conexões PostgreSQL: {}", e);
@@ -55,8 +66,31 @@ pub async fn execute_custom_query(
         }
     };
     
+    // 🌊 STREAMING (NDJSON / JSON em partes / CSV / Parquet): linhas enviadas à medida que chegam do banco
+    let formato = opcoes.formato(&req);
+    if formato.streaming() {
+        let (sql, config) = (query.to_string(), settings.consulta_livre.clone());
+        return Ok(responder(formato, &opcoes, config.max_linhas, move |saida| {
+            stream_postgres(client, sql, Vec::new(), config, saida)
+        }).await?);
+    }
+    
+    // 🔒 TRANSAÇÃO READ ONLY com tempo limite (desfeita ao final)
+    let tx = match transacao_somente_leitura(&mut client, &settings.consulta_livre).await {
+        Ok(tx) => tx,
+        Err(e) => {
+            log::error!("Erro ao abrir transação somente leitura: {}", e);
+            return Ok(HttpResponse::InternalServerError().json(json!({
+                "success": false,
+                "error": "CONNECTION_POOL_ERROR",
+                "message": "Erro na conexão com banco de dados PostgreSQL",
+                "details": e.to_string()
+            })));
+        }
+    };
+    
     // 📝 PREPARAR QUERY
-    let stmt = match client.prepare(query).await {
+    let stmt = match tx.prepare(query).await {
         Ok(stmt) => {
             log::debug!("✅ Query preparada com sucesso");
             stmt
@@ -78,12 +112,25 @@ pub async fn execute_custom_query(
         }
     };
     
-    // 🚀 EXECUTAR QUERY  
-    let rows = match client.query(&stmt, &[]).await {
+    // 🚀 EXECUTAR QUERY via portal: o servidor devolve no máximo `max_linhas + 1` linhas
+    let max_linhas = settings.consulta_livre.max_linhas;
+    let resultado = match tx.bind(&stmt, &[]).await {
+        Ok(portal) => tx.query_portal(&portal, i32::try_from(max_linhas + 1).unwrap_or(i32::MAX)).await,
+        Err(e) => Err(e),
+    };
+    let mut rows = match resultado {
         Ok(rows) => {
             log::info!("✅ Query executada com sucesso. {} registros retornados", rows.len());
             rows
         },
+        Err(e) if tempo_esgotado(&e) => {
+            log::warn!("⏱️ Consulta personalizada cancelada após {}s: {}", settings.consulta_livre.timeout_segundos, query);
+            return Err(ApiError::TempoConsultaEsgotado { segundos: settings.consulta_livre.timeout_segundos }.into());
+        },
+        Err(e) if escrita_bloqueada(&e) => {
+            log::warn!("Escrita barrada pela transação somente leitura: {}", query);
+            return Ok(resposta_restricao(&format!("A consulta tentou gravar dados: {}", e), &[]));
+        },
         Err(e) => {
             log::error!("❌ Erro ao executar query: {}", e);
             return Ok(HttpResponse::InternalServerError().json(json!({
@@ -102,77 +149,56 @@ pub async fn execute_custom_query(
         }
     };
     
+    let truncado = rows.len() > max_linhas;
+    rows.truncate(max_linhas);
+    
     // 🎯 CONVERTER RESULTADOS USANDO NOVA FUNÇÃO ROBUSTA
-    let result = rows_to_json(rows);
+    let result = rows_to_json(rows, truncado);
     
     log::info!("🎉 Consulta personalizada concluída com sucesso");
     Ok(HttpResponse::Ok().json(result))
 }
 
-/// Verifica se a consulta é do tipo SELECT
-fn is_select_query(query: &str) -> bool {
-    let normalized = query.trim().to_lowercase();
-    
-    // Verificar se começa com SELECT
-    if !normalized.starts_with("select") {
-        return false;
-    }
-    
-    // Verificar palavras-chave proibidas que indicam modificação de dados
-    let prohibited_keywords = [
-        " insert ", " update ", " delete ", " drop ", " alter ", 
-        " truncate ", " create ", " replace ", " exec ", " execute ",
-        ";insert", ";update", ";delete", ";drop", ";alter",
-        ";truncate", ";create", ";replace", ";exec", ";execute"
-    ];
-    
-    for keyword in prohibited_keywords.iter() {
-        if normalized.contains(keyword) {
-            return false;
-        }
-    }
-    
-    true
-}
-
 /// Converte linhas de resultado em JSON com suporte robusto a tipos PostgreSQL
 /// 🔧 MELHORIA: Suporta SELECT * e todos os tipos PostgreSQL comuns
 /// 🛡️ SEGURANÇA: Mantém todas as validações de segurança existentes
-/// 📊 COMPATIBILIDADE: 100% compatível com código anterior
-fn rows_to_json(rows: Vec<Row>) -> Value {
-    if rows.is_empty() {
-        return json!({ 
-            "success": true,
-            "count": 0, 
-            "data": [] 
-        });
-    }
-    
-    let columns = rows[0].columns();
-    let column_info: Vec<(String, &tokio_postgres::types::Type)> = columns.iter()
-        .map(|col| (col.name().to_string(), col.type_()))
-        .collect();
-    
-    let data: Vec<Value> = rows.iter().map(|row| {
-        let mut obj = serde_json::Map::new();
-        
-        for (i, (col_name, col_type)) in column_info.iter().enumerate() {
-            // 🎯 ESTRATÉGIA INTELIGENTE: Usar tipo PostgreSQL para conversão precisa
-            let value = convert_postgres_value_to_json(row, i, col_type);            
-            obj.insert(col_name.clone(), value);
-        }
-        
-        json!(obj)
-    }).collect();
+/// 📊 COMPATIBILIDADE: 100% compatível com código anterior (+ `truncated`)
+fn rows_to_json(rows: Vec<Row>, truncado: bool) -> Value {
+    let data: Vec<Value> = rows.iter().map(linha_para_json).collect();
     
     json!({
         "success": true,
         "count": rows.len(),
         "data": data,
+        "truncated": truncado,
         "message": format!("Query executada com sucesso. {} registros retornados.", rows.len())
     })
 }
 
+/// Valores da linha na ordem das colunas (streaming: nomes e tipos vêm dos metadados)
+pub(crate) fn linha_para_valores(row: &Row) -> Vec<Value> {
+    row.columns().iter().enumerate()
+        .map(|(i, col)| convert_postgres_value_to_json(row, i, col.type_()))
+        .collect()
+}
+
+/// Converte uma linha em objeto JSON (coluna → valor)
+fn linha_para_json(row: &Row) -> Value {
+    let mut obj = serde_json::Map::new();
+    
+    for (i, col) in row.columns().iter().enumerate() {
+        // 🎯 ESTRATÉGIA INTELIGENTE: Usar tipo PostgreSQL para conversão precisa
+        obj.insert(col.name().to_string(), convert_postgres_value_to_json(row, i, col.type_()));
+    }
+    
+    Value::Object(obj)
+}
+
+/// NUMERIC como texto, para não perder precisão (mesmo formato do DECIMAL do SQL Server)
+fn numeric_para_json(valor: Option<rust_decimal::Decimal>) -> Value {
+    valor.map_or(json!(null), |v| json!(v.to_string()))
+}
+
 /// 🔧 NOVA FUNÇÃO: Conversão inteligente baseada no tipo PostgreSQL real
 /// Resolve o problema do SELECT * ao usar informações precisas de tipo
 fn convert_postgres_value_to_json(row: &Row, col_index: usize, pg_type: &tokio_postgres::types::Type) -> Value {
@@ -201,16 +227,11 @@ fn convert_postgres_value_to_json(row: &Row, col_index: usize, pg_type: &tokio_p
             .map(|v| v.map_or(json!(null), |val| json!(val)))
             .unwrap_or_else(|_| json!(null)),
             
-        Type::NUMERIC => {
-            // 🔄 Para NUMERIC, tentamos múltiplas abordagens
-            if let Ok(v) = row.try_get::<_, Option<f64>>(col_index) {
-                v.map_or(json!(null), |val| json!(val))
-            } else if let Ok(v) = row.try_get::<_, Option<String>>(col_index) {
-                v.map_or(json!(null), |val| json!(val))
-            } else {
-                json!(null)
-            }
-        },
+        // 🔄 NUMERIC só é aceito como Decimal (f64 e String são recusados pelo driver);
+        // valores fora do alcance do rust_decimal (mais de 28 dígitos, NaN) viram null
+        Type::NUMERIC => row.try_get::<_, Option<rust_decimal::Decimal>>(col_index)
+            .map(numeric_para_json)
+            .unwrap_or_else(|_| json!(null)),
             
         // 📝 TIPOS TEXTO
         Type::TEXT | Type::VARCHAR | Type::CHAR | Type::NAME => 
@@ -287,3 +308,55 @@ fn convert_postgres_value_to_json(row: &Row, col_index: usize, pg_type: &tokio_p
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use actix_web::web::{Bytes, BytesMut};
+    use arrow_array::{Array, Decimal128Array};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use rust_decimal::Decimal;
+    use tokio_postgres::types::{FromSql, ToSql, Type};
+
+    use super::*;
+    use crate::handlers::formatos_saida::{Coluna, EscritorCsv, EscritorParquet, TipoColuna};
+
+    /// NUMERIC como chega do PostgreSQL (formato binário), lido pelo mesmo caminho de `linha_para_valores`
+    fn numeric_do_banco(texto: &str) -> Value {
+        let mut bytes = BytesMut::new();
+        Decimal::from_str(texto).unwrap().to_sql(&Type::NUMERIC, &mut bytes).unwrap();
+        let lido = <Option<Decimal> as FromSql>::from_sql_nullable(&Type::NUMERIC, Some(&bytes)).unwrap();
+        numeric_para_json(lido)
+    }
+
+    #[test]
+    fn numeric_vira_texto_sem_perder_casas() {
+        assert!(!<f64 as FromSql>::accepts(&Type::NUMERIC));
+        assert!(!<String as FromSql>::accepts(&Type::NUMERIC));
+        assert_eq!(numeric_do_banco("1234.50"), json!("1234.50"));
+        assert_eq!(numeric_para_json(None), json!(null));
+    }
+
+    #[test]
+    fn numeric_chega_ao_csv_e_ao_parquet() {
+        let colunas = vec![Coluna { nome: "preco".to_string(), tipo: TipoColuna::Decimal(Some((12, 2))) }];
+        let valores = [numeric_do_banco("1234.50")];
+
+        let mut csv = EscritorCsv::new(b';', true);
+        csv.cabecalho(&colunas);
+        assert_eq!(csv.linha(&valores), b"1234,50\r\n");
+
+        let mut parquet = EscritorParquet::new(&colunas).unwrap();
+        let mut arquivo = parquet.linha(&valores).unwrap();
+        arquivo.extend(parquet.linha(&[numeric_para_json(None)]).unwrap());
+        arquivo.extend(parquet.finalizar(false).unwrap());
+
+        let lote = ParquetRecordBatchReaderBuilder::try_new(Bytes::from(arquivo)).unwrap()
+            .build().unwrap()
+            .next().unwrap().unwrap();
+        let precos = lote.column(0).as_any().downcast_ref::<Decimal128Array>().unwrap();
+        assert_eq!(precos.value(0), 123450);
+        assert!(precos.is_null(1));
+    }
+}
@@ -0,0 +1,310 @@
+//! 🛠️ Administração de usuários, tokens e chaves de API - escopo /admin (papel admin)
+
+use actix_web::{web, HttpResponse};
+use deadpool_postgres::Pool;
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{chaves_api, repositorio, tokens};
+use crate::auth::{apenas_digitos, Claims, Papel, TokensRevogados};
+use crate::config::Settings;
+use crate::errors::ApiError;
+
+const SENHA_TAMANHO_MINIMO: usize = 8;
+
+#[derive(Debug, Deserialize)]
+pub struct CriarUsuarioRequest {
+    pub username: String,
+    pub password: String,
+    pub roles: Vec<Papel>,
+    #[serde(default)]
+    pub cnpjs: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetarSenhaRequest {
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DefinirPapeisRequest {
+    pub roles: Vec<Papel>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DefinirCnpjsRequest {
+    pub cnpjs: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CriarChaveApiRequest {
+    pub name: String,
+    pub roles: Vec<Papel>,
+    #[serde(default)]
+    pub cnpjs: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevogarTokenRequest {
+    pub jti: String,
+}
+
+/// Lista todos os usuários (sem o hash da senha)
+pub async fn listar_usuarios(
+    pool: web::Data<Pool>,
+    _claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    let client = pool.get().await?;
+    let usuarios = repositorio::listar(&client).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "data": usuarios,
+        "count": usuarios.len()
+    })))
+}
+
+/// Cria um usuário ativo com senha bcrypt
+pub async fn criar_usuario(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    req: web::Json<CriarUsuarioRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let req = req.into_inner();
+    let username = req.username.trim().to_string();
+    if username.is_empty() || username.len() > 100 {
+        return Err(ApiError::BadRequest("username deve ter entre 1 e 100 caracteres".to_string()));
+    }
+    validar_senha(&req.password)?;
+    validar_papeis(&req.roles)?;
+    let cnpjs = normalizar_cnpjs(&req.cnpjs)?;
+    if req.roles.contains(&Papel::Franqueado) && cnpjs.is_empty() {
+        return Err(ApiError::BadRequest("Usuários franqueado exigem ao menos um CNPJ".to_string()));
+    }
+
+    let hash = repositorio::gerar_hash(req.password).await?;
+    let client = pool.get().await?;
+    let usuario = repositorio::criar(&client, &username, &hash, &req.roles, &cnpjs).await?;
+
+    log::info!("👤 Usuário '{}' criado por '{}'", usuario.username, claims.sub);
+
+    Ok(HttpResponse::Created().json(json!({
+        "success": true,
+        "data": usuario
+    })))
+}
+
+/// Desativa um usuário e encerra suas sessões; access tokens já emitidos valem até expirar
+pub async fn desativar_usuario(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    id: web::Path<uuid::Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let client = pool.get().await?;
+    if !repositorio::desativar(&client, id).await? {
+        return Err(ApiError::NotFound);
+    }
+    tokens::revogar_do_usuario(&client, id).await?;
+
+    log::info!("🚫 Usuário {} desativado por '{}'", id, claims.sub);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "message": "Usuário desativado"
+    })))
+}
+
+/// Define uma nova senha para o usuário e encerra suas sessões (refresh tokens); access tokens valem até expirar
+pub async fn resetar_senha(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    id: web::Path<uuid::Uuid>,
+    req: web::Json<ResetarSenhaRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let req = req.into_inner();
+    validar_senha(&req.password)?;
+
+    let id = id.into_inner();
+    let hash = repositorio::gerar_hash(req.password).await?;
+    let client = pool.get().await?;
+    if !repositorio::atualizar_senha(&client, id, &hash).await? {
+        return Err(ApiError::NotFound);
+    }
+    tokens::revogar_do_usuario(&client, id).await?;
+
+    log::info!("🔑 Senha do usuário {} redefinida por '{}'", id, claims.sub);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "message": "Senha redefinida"
+    })))
+}
+
+/// Substitui os papéis do usuário; vale a partir do próximo login
+pub async fn definir_papeis(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    id: web::Path<uuid::Uuid>,
+    req: web::Json<DefinirPapeisRequest>,
+) -> Result<HttpResponse, ApiError> {
+    validar_papeis(&req.roles)?;
+
+    let id = id.into_inner();
+    let client = pool.get().await?;
+    if !repositorio::atualizar_papeis(&client, id, &req.roles).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    log::info!("🎭 Papéis do usuário {} definidos como {:?} por '{}'", id, req.roles, claims.sub);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "roles": req.roles
+    })))
+}
+
+/// Vincula o usuário aos CNPJs informados (lista vazia remove o vínculo); vale a partir do próximo login
+pub async fn definir_cnpjs(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    id: web::Path<uuid::Uuid>,
+    req: web::Json<DefinirCnpjsRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let cnpjs = normalizar_cnpjs(&req.cnpjs)?;
+
+    let id = id.into_inner();
+    let client = pool.get().await?;
+    if !repositorio::atualizar_cnpjs(&client, id, &cnpjs).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    log::info!("🏪 CNPJs do usuário {} definidos como {:?} por '{}'", id, cnpjs, claims.sub);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "cnpjs": cnpjs
+    })))
+}
+
+/// Revoga um access token pelo `jti` (ex.: token vazado).
+/// O token não dura mais que `JWT_ACCESS_EXPIRATION_MINUTES`, então a revogação expira junto.
+pub async fn revogar_token(
+    pool: web::Data<Pool>,
+    settings: web::Data<Settings>,
+    revogados: web::Data<TokensRevogados>,
+    claims: Claims,
+    req: web::Json<RevogarTokenRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let jti = req.jti.trim();
+    if jti.is_empty() || jti.len() > 64 {
+        return Err(ApiError::BadRequest("jti inválido".to_string()));
+    }
+
+    let exp = (chrono::Utc::now() + chrono::Duration::minutes(settings.jwt.access_expiration_minutes)).timestamp();
+    let client = pool.get().await?;
+    tokens::revogar_jti(&client, jti, &claims.sub, exp).await?;
+    revogados.revogar(jti, exp);
+
+    log::info!("🚫 Token {} revogado por '{}'", jti, claims.sub);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "message": "Token revogado"
+    })))
+}
+
+/// Lista as chaves de API (sem o hash), com último uso e revogação
+pub async fn listar_chaves_api(
+    pool: web::Data<Pool>,
+    _claims: Claims,
+) -> Result<HttpResponse, ApiError> {
+    let client = pool.get().await?;
+    let chaves = chaves_api::listar(&client).await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "data": chaves,
+        "count": chaves.len()
+    })))
+}
+
+/// Emite uma chave de API; o valor em claro só aparece nesta resposta
+pub async fn criar_chave_api(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    req: web::Json<CriarChaveApiRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let req = req.into_inner();
+    let nome = req.name.trim().to_string();
+    if nome.is_empty() || nome.len() > 100 {
+        return Err(ApiError::BadRequest("name deve ter entre 1 e 100 caracteres".to_string()));
+    }
+    validar_papeis(&req.roles)?;
+    let cnpjs = normalizar_cnpjs(&req.cnpjs)?;
+    if req.roles.contains(&Papel::Franqueado) && cnpjs.is_empty() {
+        return Err(ApiError::BadRequest("Chaves franqueado exigem ao menos um CNPJ".to_string()));
+    }
+
+    let client = pool.get().await?;
+    let (chave, valor) = chaves_api::criar(&client, &nome, &req.roles, &cnpjs, &claims.sub).await?;
+
+    log::info!("🔑 Chave de API '{}' criada por '{}'", chave.name, claims.sub);
+
+    Ok(HttpResponse::Created().json(json!({
+        "success": true,
+        "data": chave,
+        "key": valor
+    })))
+}
+
+/// Revoga uma chave de API; vale imediatamente
+pub async fn revogar_chave_api(
+    pool: web::Data<Pool>,
+    claims: Claims,
+    id: web::Path<uuid::Uuid>,
+) -> Result<HttpResponse, ApiError> {
+    let id = id.into_inner();
+    let client = pool.get().await?;
+    if !chaves_api::revogar(&client, id).await? {
+        return Err(ApiError::NotFound);
+    }
+
+    log::info!("🚫 Chave de API {} revogada por '{}'", id, claims.sub);
+
+    Ok(HttpResponse::Ok().json(json!({
+        "success": true,
+        "message": "Chave de API revogada"
+    })))
+}
+
+/// CNPJs são gravados apenas com dígitos, sem duplicados
+fn normalizar_cnpjs(cnpjs: &[String]) -> Result<Vec<String>, ApiError> {
+    let mut normalizados: Vec<String> = Vec::new();
+    for cnpj in cnpjs {
+        let digitos = apenas_digitos(cnpj);
+        if digitos.len() != 14 {
+            return Err(ApiError::BadRequest(format!("CNPJ inválido: {}", cnpj)));
+        }
+        if !normalizados.contains(&digitos) {
+            normalizados.push(digitos);
+        }
+    }
+    Ok(normalizados)
+}
+
+fn validar_papeis(roles: &[Papel]) -> Result<(), ApiError> {
+    if roles.is_empty() {
+        return Err(ApiError::BadRequest("Informe ao menos um papel em roles".to_string()));
+    }
+    Ok(())
+}
+
+fn validar_senha(senha: &str) -> Result<(), ApiError> {
+    if senha.chars().count() < SENHA_TAMANHO_MINIMO {
+        return Err(ApiError::BadRequest(format!(
+            "A senha deve ter pelo menos {} caracteres", SENHA_TAMANHO_MINIMO
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,141 @@
+//! 🔑 Chaves de API - integrações máquina a máquina (ETL, portal)
+//!
+//! - Chave longa e opaca enviada no header `X-API-Key`, guardada apenas como hash SHA-256
+//! - Cada chave tem nome, papéis (escopos) e, opcionalmente, CNPJs vinculados
+//! - `last_used_at` é atualizado no máximo uma vez por minuto para não gerar escrita a cada request
+
+use deadpool_postgres::Client;
+use serde::Serialize;
+use tokio_postgres::error::SqlState;
+use tokio_postgres::Row;
+
+use super::tokens::hash_token;
+use crate::auth::{Claims, Papel};
+use crate::errors::ApiError;
+
+/// Prefixo das chaves geradas, facilita identificar uma chave vazada em logs ou repositórios
+const PREFIXO_CHAVE: &str = "fck_";
+
+const CHAVE_COLUNAS: &str = "id, name, key_prefix, roles, cnpjs, created_by, created_at, last_used_at, revoked_at";
+
+/// Chave de API (sem o hash)
+#[derive(Debug, Serialize)]
+pub struct ChaveApi {
+    pub id: uuid::Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub roles: Vec<String>,
+    pub cnpjs: Vec<String>,
+    pub created_by: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Cria a tabela `api_keys`
+pub async fn inicializar(client: &Client) -> Result<(), ApiError> {
+    client.batch_execute(r#"
+        CREATE TABLE IF NOT EXISTS api_keys (
+            id UUID PRIMARY KEY,
+            name VARCHAR(100) NOT NULL UNIQUE,
+            key_prefix VARCHAR(16) NOT NULL,
+            key_hash CHAR(64) NOT NULL UNIQUE,
+            roles TEXT[] NOT NULL DEFAULT '{}',
+            cnpjs TEXT[] NOT NULL DEFAULT '{}',
+            created_by VARCHAR(100) NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            last_used_at TIMESTAMPTZ,
+            revoked_at TIMESTAMPTZ
+        );
+    "#).await?;
+    Ok(())
+}
+
+pub async fn listar(client: &Client) -> Result<Vec<ChaveApi>, ApiError> {
+    let sql = format!("SELECT {} FROM api_keys ORDER BY name", CHAVE_COLUNAS);
+    let rows = client.query(&sql, &[]).await?;
+    Ok(rows.iter().map(chave_from_row).collect())
+}
+
+/// Gera e grava uma chave. Retorna a chave em claro, que só existe nesta resposta.
+/// Nome duplicado retorna `ApiError::Conflict`.
+pub async fn criar(
+    client: &Client,
+    nome: &str,
+    roles: &[Papel],
+    cnpjs: &[String],
+    criado_por: &str,
+) -> Result<(ChaveApi, String), ApiError> {
+    let chave = format!("{}{}{}", PREFIXO_CHAVE, uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    let prefixo: String = chave.chars().take(12).collect();
+    let roles: Vec<String> = roles.iter().map(|p| p.as_str().to_string()).collect();
+
+    let sql = format!(r#"
+        INSERT INTO api_keys (id, name, key_prefix, key_hash, roles, cnpjs, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        RETURNING {}
+    "#, CHAVE_COLUNAS);
+
+    match client.query_one(&sql, &[
+        &uuid::Uuid::new_v4(), &nome, &prefixo, &hash_token(&chave), &roles, &cnpjs, &criado_por,
+    ]).await {
+        Ok(row) => Ok((chave_from_row(&row), chave)),
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+            Err(ApiError::Conflict(format!("Chave de API '{}' já existe", nome)))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Revoga a chave. Retorna `false` se o id não existir ou já estiver revogada.
+pub async fn revogar(client: &Client, id: uuid::Uuid) -> Result<bool, ApiError> {
+    let alterados = client.execute(
+        "UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL",
+        &[&id],
+    ).await?;
+    Ok(alterados > 0)
+}
+
+/// Valida a chave recebida no header e monta as claims equivalentes às de um JWT.
+/// Chave desconhecida ou revogada retorna `None`.
+pub async fn autenticar(client: &Client, chave: &str) -> Result<Option<Claims>, ApiError> {
+    let row = client.query_opt(r#"
+        SELECT id, name, roles, cnpjs, EXTRACT(EPOCH FROM created_at)::bigint,
+               last_used_at IS NULL OR last_used_at < NOW() - INTERVAL '1 minute'
+        FROM api_keys
+        WHERE key_hash = $1 AND revoked_at IS NULL
+    "#, &[&hash_token(chave.trim())]).await?;
+
+    let Some(row) = row else {
+        return Ok(None);
+    };
+
+    if row.get::<_, bool>(5) {
+        let id: uuid::Uuid = row.get(0);
+        client.execute("UPDATE api_keys SET last_used_at = NOW() WHERE id = $1", &[&id]).await?;
+    }
+
+    let roles: Vec<String> = row.get(2);
+    Ok(Some(Claims {
+        sub: format!("apikey:{}", row.get::<_, String>(1)),
+        exp: 0,
+        iat: row.get(4),
+        roles: roles.iter().filter_map(|r| Papel::from_nome(r)).collect(),
+        cnpjs: row.get(3),
+        jti: String::new(),
+    }))
+}
+
+fn chave_from_row(row: &Row) -> ChaveApi {
+    ChaveApi {
+        id: row.get(0),
+        name: row.get(1),
+        key_prefix: row.get(2),
+        roles: row.get(3),
+        cnpjs: row.get(4),
+        created_by: row.get(5),
+        created_at: row.get(6),
+        last_used_at: row.get(7),
+        revoked_at: row.get(8),
+    }
+}
@@ -0,0 +1,15 @@
+//! 👤 Usuários - Credenciais individuais no PostgreSQL FC (tabela `api_users`)
+//!
+//! - Repositório: criação da tabela, consultas e seed do admin do .env
+//! - Tokens: refresh tokens com rotação e lista de access tokens revogados
+//! - Chaves de API: credenciais de integrações enviadas em `X-API-Key`
+//! - Admin: criar, listar, desativar, resetar senha e definir papéis e CNPJs de usuários
+
+pub mod repositorio;
+pub mod admin;
+pub mod tokens;
+pub mod chaves_api;
+
+pub use repositorio::inicializar;
+pub use admin::{listar_usuarios, criar_usuario, desativar_usuario, resetar_senha, definir_papeis, definir_cnpjs, revogar_token};
+pub use admin::{listar_chaves_api, criar_chave_api, revogar_chave_api};
@@ -0,0 +1,149 @@
+//! 🗄️ Repositório de usuários - PostgreSQL FC
+//!
+//! A tabela é criada na inicialização se não existir. Se estiver vazia,
+//! o admin do .env (`ADMIN_USERNAME`/`ADMIN_PASSWORD`) é gravado com hash bcrypt
+//! para que o primeiro acesso continue funcionando.
+
+use actix_web::web;
+use deadpool_postgres::{Client, Pool};
+use tokio_postgres::error::SqlState;
+use tokio_postgres::Row;
+
+use crate::auth::Papel;
+use crate::config::AdminConfig;
+use crate::errors::ApiError;
+use crate::handlers::auth_handlers::{hash_password, verify_password, User};
+
+pub(super) const USER_COLUNAS: &str = "id, username, password_hash, created_at, updated_at, active, roles, cnpjs";
+
+/// Cria a tabela `api_users` e grava o admin inicial se ainda não houver usuários
+pub async fn inicializar(pool: &Pool, admin: &AdminConfig) -> Result<(), ApiError> {
+    let client = pool.get().await?;
+
+    client.batch_execute(r#"
+        CREATE TABLE IF NOT EXISTS api_users (
+            id UUID PRIMARY KEY,
+            username VARCHAR(100) NOT NULL UNIQUE,
+            password_hash VARCHAR(255) NOT NULL,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            active BOOLEAN NOT NULL DEFAULT TRUE,
+            roles TEXT[] NOT NULL DEFAULT '{}',
+            cnpjs TEXT[] NOT NULL DEFAULT '{}'
+        );
+    "#).await?;
+    super::tokens::inicializar(&client).await?;
+    super::chaves_api::inicializar(&client).await?;
+
+    let total: i64 = client.query_one("SELECT COUNT(*) FROM api_users", &[]).await?.get(0);
+    if total == 0 {
+        let hash = gerar_hash(admin.password.clone()).await?;
+        criar(&client, &admin.username, &hash, &[Papel::Admin], &[]).await?;
+        log::info!("👤 Usuário admin inicial '{}' criado a partir do .env", admin.username);
+    }
+
+    Ok(())
+}
+
+pub async fn buscar_por_username(client: &Client, username: &str) -> Result<Option<User>, ApiError> {
+    let sql = format!("SELECT {} FROM api_users WHERE username = $1", USER_COLUNAS);
+    let row = client.query_opt(&sql, &[&username]).await?;
+    Ok(row.as_ref().map(user_from_row))
+}
+
+pub async fn listar(client: &Client) -> Result<Vec<User>, ApiError> {
+    let sql = format!("SELECT {} FROM api_users ORDER BY username", USER_COLUNAS);
+    let rows = client.query(&sql, &[]).await?;
+    Ok(rows.iter().map(user_from_row).collect())
+}
+
+/// Insere um usuário ativo. Username duplicado retorna `ApiError::Conflict`.
+pub async fn criar(
+    client: &Client,
+    username: &str,
+    password_hash: &str,
+    roles: &[Papel],
+    cnpjs: &[String],
+) -> Result<User, ApiError> {
+    let sql = format!(
+        "INSERT INTO api_users (id, username, password_hash, roles, cnpjs) VALUES ($1, $2, $3, $4, $5) RETURNING {}",
+        USER_COLUNAS
+    );
+    let roles = nomes_papeis(roles);
+
+    match client.query_one(&sql, &[&uuid::Uuid::new_v4(), &username, &password_hash, &roles, &cnpjs]).await {
+        Ok(row) => Ok(user_from_row(&row)),
+        Err(e) if e.code() == Some(&SqlState::UNIQUE_VIOLATION) => {
+            Err(ApiError::Conflict(format!("Usuário '{}' já existe", username)))
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Desativa o usuário. Retorna `false` se o id não existir.
+pub async fn desativar(client: &Client, id: uuid::Uuid) -> Result<bool, ApiError> {
+    let alterados = client.execute(
+        "UPDATE api_users SET active = FALSE, updated_at = NOW() WHERE id = $1",
+        &[&id],
+    ).await?;
+    Ok(alterados > 0)
+}
+
+/// Troca o hash da senha. Retorna `false` se o id não existir.
+pub async fn atualizar_senha(client: &Client, id: uuid::Uuid, password_hash: &str) -> Result<bool, ApiError> {
+    let alterados = client.execute(
+        "UPDATE api_users SET password_hash = $2, updated_at = NOW() WHERE id = $1",
+        &[&id, &password_hash],
+    ).await?;
+    Ok(alterados > 0)
+}
+
+/// Substitui os papéis do usuário. Retorna `false` se o id não existir.
+pub async fn atualizar_papeis(client: &Client, id: uuid::Uuid, roles: &[Papel]) -> Result<bool, ApiError> {
+    let alterados = client.execute(
+        "UPDATE api_users SET roles = $2, updated_at = NOW() WHERE id = $1",
+        &[&id, &nomes_papeis(roles)],
+    ).await?;
+    Ok(alterados > 0)
+}
+
+/// Substitui os CNPJs vinculados ao usuário. Retorna `false` se o id não existir.
+pub async fn atualizar_cnpjs(client: &Client, id: uuid::Uuid, cnpjs: &[String]) -> Result<bool, ApiError> {
+    let alterados = client.execute(
+        "UPDATE api_users SET cnpjs = $2, updated_at = NOW() WHERE id = $1",
+        &[&id, &cnpjs],
+    ).await?;
+    Ok(alterados > 0)
+}
+
+/// bcrypt é custoso: roda fora das threads do servidor
+pub async fn gerar_hash(senha: String) -> Result<String, ApiError> {
+    web::block(move || hash_password(&senha)).await
+        .map_err(|e| ApiError::InternalError(format!("Erro ao gerar hash de senha: {}", e)))?
+}
+
+/// Hash bcrypt (custo padrão) de uma senha descartável. O login verifica contra ele quando o usuário
+/// não existe ou está desativado, para o tempo de resposta não revelar quais usernames existem.
+pub const HASH_FICTICIO: &str = "$2b$12$Rni6xXw8XJEC04BVwkmw/OoMeS6EYNUNH71krV6Q8.1TuDGrPZg/u";
+
+pub async fn verificar_hash(senha: String, hash: String) -> Result<bool, ApiError> {
+    web::block(move || verify_password(&senha, &hash)).await
+        .map_err(|e| ApiError::InternalError(format!("Erro ao verificar senha: {}", e)))?
+}
+
+pub(super) fn user_from_row(row: &Row) -> User {
+    User {
+        id: row.get(0),
+        username: row.get(1),
+        password_hash: row.get(2),
+        created_at: row.get(3),
+        updated_at: row.get(4),
+        active: row.get(5),
+        roles: row.get(6),
+        cnpjs: row.get(7),
+    }
+}
+
+fn nomes_papeis(roles: &[Papel]) -> Vec<String> {
+    roles.iter().map(|p| p.as_str().to_string()).collect()
+}
@@ -0,0 +1,162 @@
+//! 🔄 Refresh tokens e revogação - PostgreSQL FC
+//!
+//! - Refresh tokens opacos, guardados apenas como hash SHA-256
+//! - Rotação: cada uso gera um novo token na mesma família e invalida o anterior
+//! - Reuso de um token já rotacionado derruba a família inteira (possível vazamento)
+//! - `api_revoked_tokens`: `jti` de access tokens revogados até a expiração
+
+use std::collections::HashMap;
+
+use deadpool_postgres::{Client, GenericClient};
+use sha2::{Digest, Sha256};
+
+use super::repositorio::{user_from_row, USER_COLUNAS};
+use crate::errors::ApiError;
+use crate::handlers::auth_handlers::User;
+
+/// Cria as tabelas de refresh tokens e de tokens revogados
+pub async fn inicializar(client: &Client) -> Result<(), ApiError> {
+    client.batch_execute(r#"
+        CREATE TABLE IF NOT EXISTS api_refresh_tokens (
+            id UUID PRIMARY KEY,
+            user_id UUID NOT NULL REFERENCES api_users(id) ON DELETE CASCADE,
+            family_id UUID NOT NULL,
+            token_hash CHAR(64) NOT NULL UNIQUE,
+            created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+            expires_at TIMESTAMPTZ NOT NULL,
+            revoked_at TIMESTAMPTZ,
+            replaced_by UUID
+        );
+        CREATE INDEX IF NOT EXISTS idx_api_refresh_tokens_family ON api_refresh_tokens (family_id);
+        CREATE TABLE IF NOT EXISTS api_revoked_tokens (
+            jti VARCHAR(64) PRIMARY KEY,
+            revoked_by VARCHAR(100) NOT NULL,
+            expires_at TIMESTAMPTZ NOT NULL,
+            revoked_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+        );
+    "#).await?;
+    Ok(())
+}
+
+/// Emite um refresh token para o usuário. Sem `familia`, inicia uma nova (login).
+/// Retorna `(id, token)`; o token em claro só existe na resposta.
+pub async fn emitir(
+    client: &impl GenericClient,
+    user_id: uuid::Uuid,
+    familia: Option<uuid::Uuid>,
+    validade_dias: i64,
+) -> Result<(uuid::Uuid, String), ApiError> {
+    let id = uuid::Uuid::new_v4();
+    let token = format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple());
+    let expira_em = chrono::Utc::now() + chrono::Duration::days(validade_dias);
+
+    client.execute(
+        "INSERT INTO api_refresh_tokens (id, user_id, family_id, token_hash, expires_at) VALUES ($1, $2, $3, $4, $5)",
+        &[&id, &user_id, &familia.unwrap_or_else(uuid::Uuid::new_v4), &hash_token(&token), &expira_em],
+    ).await?;
+
+    Ok((id, token))
+}
+
+/// Troca um refresh token válido por um novo da mesma família e retorna o usuário dono
+pub async fn rotacionar(
+    client: &mut Client,
+    token: &str,
+    validade_dias: i64,
+) -> Result<(User, String), ApiError> {
+    let tx = client.transaction().await?;
+
+    let row = tx.query_opt(r#"
+        SELECT id, family_id, user_id, expires_at < NOW(), revoked_at IS NOT NULL
+        FROM api_refresh_tokens
+        WHERE token_hash = $1
+        FOR UPDATE
+    "#, &[&hash_token(token)]).await?
+        .ok_or(ApiError::InvalidToken)?;
+
+    let id: uuid::Uuid = row.get(0);
+    let familia: uuid::Uuid = row.get(1);
+    let user_id: uuid::Uuid = row.get(2);
+    let expirado: bool = row.get(3);
+    let revogado: bool = row.get(4);
+
+    if revogado {
+        // Token já usado: quem o apresentou não é o dono legítimo ou a sessão foi encerrada
+        tx.execute(
+            "UPDATE api_refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL",
+            &[&familia],
+        ).await?;
+        tx.commit().await?;
+        log::warn!("🚨 Reuso de refresh token revogado (família {}); sessão encerrada", familia);
+        return Err(ApiError::InvalidToken);
+    }
+
+    if expirado {
+        return Err(ApiError::ExpiredToken);
+    }
+
+    let sql = format!("SELECT {} FROM api_users WHERE id = $1", USER_COLUNAS);
+    let usuario = user_from_row(&tx.query_one(&sql, &[&user_id]).await?);
+    if !usuario.active {
+        return Err(ApiError::InvalidCredentials);
+    }
+
+    let (novo_id, novo_token) = emitir(&tx, user_id, Some(familia), validade_dias).await?;
+    tx.execute(
+        "UPDATE api_refresh_tokens SET revoked_at = NOW(), replaced_by = $2 WHERE id = $1",
+        &[&id, &novo_id],
+    ).await?;
+    tx.commit().await?;
+
+    Ok((usuario, novo_token))
+}
+
+/// Encerra a sessão (família) do refresh token informado
+pub async fn revogar_sessao(client: &Client, token: &str) -> Result<u64, ApiError> {
+    Ok(client.execute(r#"
+        UPDATE api_refresh_tokens SET revoked_at = NOW()
+        WHERE revoked_at IS NULL
+          AND family_id = (SELECT family_id FROM api_refresh_tokens WHERE token_hash = $1)
+    "#, &[&hash_token(token)]).await?)
+}
+
+/// Encerra todas as sessões do usuário (ex.: ao desativar)
+pub async fn revogar_do_usuario(client: &Client, user_id: uuid::Uuid) -> Result<u64, ApiError> {
+    Ok(client.execute(
+        "UPDATE api_refresh_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+        &[&user_id],
+    ).await?)
+}
+
+/// Registra o `jti` de um access token como revogado até `exp`
+pub async fn revogar_jti(client: &Client, jti: &str, revogado_por: &str, exp: i64) -> Result<(), ApiError> {
+    client.execute(r#"
+        INSERT INTO api_revoked_tokens (jti, revoked_by, expires_at)
+        VALUES ($1, $2, to_timestamp($3::bigint))
+        ON CONFLICT (jti) DO NOTHING
+    "#, &[&jti, &revogado_por, &exp]).await?;
+    Ok(())
+}
+
+/// Carrega os `jti` revogados ainda não expirados e apaga os vencidos
+pub async fn carregar_revogados(client: &Client) -> Result<HashMap<String, i64>, ApiError> {
+    client.execute("DELETE FROM api_revoked_tokens WHERE expires_at <= NOW()", &[]).await?;
+    client.execute(
+        "DELETE FROM api_refresh_tokens WHERE expires_at <= NOW() - INTERVAL '7 days'",
+        &[],
+    ).await?;
+
+    let rows = client.query(
+        "SELECT jti, EXTRACT(EPOCH FROM expires_at)::bigint FROM api_revoked_tokens",
+        &[],
+    ).await?;
+
+    Ok(rows.iter().map(|r| (r.get::<_, String>(0), r.get::<_, i64>(1))).collect())
+}
+
+pub(super) fn hash_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
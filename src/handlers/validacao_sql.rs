@@ -0,0 +1,297 @@
+// src/handlers/validacao_sql.rs
+// 🛡️ Validação de SQL livre (somente leitura) compartilhada por /data, /portal, /protheus e consultas salvas
+
+use std::collections::BTreeSet;
+use std::ops::ControlFlow;
+
+use actix_web::HttpResponse;
+use serde_json::json;
+use sqlparser::ast::{visit_expressions, Expr, ObjectName, Query, SetExpr, Statement, TableFactor, Value, Visit, Visitor};
+use sqlparser::dialect::{Dialect, MsSqlDialect, PostgreSqlDialect};
+use sqlparser::parser::Parser;
+
+/// Banco de destino da consulta (define o dialeto do parser)
+#[derive(Debug, Clone, Copy)]
+pub enum Dialeto {
+    Postgres,
+    SqlServer,
+}
+
+impl Dialeto {
+    fn dialect(&self) -> &'static dyn Dialect {
+        match self {
+            Dialeto::Postgres => &PostgreSqlDialect {},
+            Dialeto::SqlServer => &MsSqlDialect {},
+        }
+    }
+}
+
+/// Funções proibidas mesmo dentro de um SELECT, com o motivo exibido ao usuário.
+/// Entradas terminadas em `*` bloqueiam o prefixo inteiro.
+const FUNCOES_PROIBIDAS: &[(&str, &str)] = &[
+    ("pg_sleep*", "suspende a conexão e prende o pool"),
+    ("pg_read_file", "lê arquivos do servidor"),
+    ("pg_read_binary_file", "lê arquivos do servidor"),
+    ("pg_ls_dir", "lista diretórios do servidor"),
+    ("pg_stat_file", "inspeciona arquivos do servidor"),
+    ("pg_file_write", "grava arquivos no servidor"),
+    ("lo_*", "manipula large objects e arquivos do servidor"),
+    ("dblink*", "executa SQL em outro banco"),
+    ("query_to_xml*", "executa SQL arbitrário passado como texto"),
+    ("cursor_to_xml", "executa SQL arbitrário passado como texto"),
+    ("pg_terminate_backend", "derruba conexões de outros usuários"),
+    ("pg_cancel_backend", "cancela consultas de outros usuários"),
+    ("pg_reload_conf", "altera a configuração do servidor"),
+    ("pg_rotate_logfile", "altera a configuração do servidor"),
+    ("pg_switch_wal", "altera o estado do servidor"),
+    ("pg_create_restore_point", "altera o estado do servidor"),
+    ("pg_promote", "altera o estado do servidor"),
+    ("set_config", "altera parâmetros da sessão"),
+    ("pg_advisory*", "adquire locks que sobrevivem à consulta"),
+    ("pg_try_advisory*", "adquire locks que sobrevivem à consulta"),
+    ("pg_notify", "dispara notificações para outros clientes"),
+    ("nextval", "altera sequências"),
+    ("setval", "altera sequências"),
+    ("openrowset", "acessa fontes de dados externas"),
+    ("openquery", "executa SQL em servidor vinculado"),
+    ("opendatasource", "acessa fontes de dados externas"),
+    ("xp_*", "procedimento estendido do SQL Server"),
+    ("sp_*", "procedimento de sistema do SQL Server"),
+];
+
+/// Aceita apenas uma única instrução SELECT sem efeitos colaterais.
+/// Em caso de rejeição, retorna a explicação para o usuário.
+pub fn validar_somente_leitura(sql: &str, dialeto: Dialeto) -> Result<(), String> {
+    let instrucoes = Parser::parse_sql(dialeto.dialect(), sql)
+        .map_err(|e| format!("Não foi possível interpretar a consulta: {}", e))?;
+
+    let instrucao = match instrucoes.as_slice() {
+        [] => return Err("Nenhuma instrução SQL informada".to_string()),
+        [unica] => unica,
+        _ => return Err(format!(
+            "Apenas uma instrução por requisição é permitida ({} encontradas)",
+            instrucoes.len()
+        )),
+    };
+
+    match instrucao.visit(&mut ValidadorLeitura) {
+        ControlFlow::Break(motivo) => Err(motivo),
+        ControlFlow::Continue(()) => Ok(()),
+    }
+}
+
+/// Índices dos parâmetros posicionais usados na consulta (`$1` no PostgreSQL, `@P1` no SQL Server)
+pub fn parametros_posicionais(sql: &str, dialeto: Dialeto) -> Result<BTreeSet<usize>, String> {
+    let instrucoes = Parser::parse_sql(dialeto.dialect(), sql)
+        .map_err(|e| format!("Não foi possível interpretar a consulta: {}", e))?;
+
+    let mut indices = BTreeSet::new();
+    let _ = visit_expressions(&instrucoes, |expr| {
+        let nome = match (dialeto, expr) {
+            (Dialeto::Postgres, Expr::Value(Value::Placeholder(p))) => p.strip_prefix('$'),
+            (Dialeto::SqlServer, Expr::Identifier(ident)) => ident.value.strip_prefix("@P")
+                .or_else(|| ident.value.strip_prefix("@p")),
+            _ => None,
+        };
+        if let Some(indice) = nome.and_then(|n| n.parse().ok()) {
+            indices.insert(indice);
+        }
+        ControlFlow::<()>::Continue(())
+    });
+    Ok(indices)
+}
+
+/// Resposta 400 padrão para SQL rejeitado pelo validador
+pub fn resposta_restricao(motivo: &str, exemplos: &[&str]) -> HttpResponse {
+    HttpResponse::BadRequest().json(json!({
+        "success": false,
+        "error": "SECURITY_RESTRICTION",
+        "message": "Apenas consultas SELECT são permitidas por motivos de segurança",
+        "reason": motivo,
+        "allowed_examples": exemplos
+    }))
+}
+
+/// Percorre a árvore da consulta (incluindo CTEs e subconsultas) procurando efeitos colaterais
+struct ValidadorLeitura;
+
+impl Visitor for ValidadorLeitura {
+    type Break = String;
+
+    fn pre_visit_statement(&mut self, instrucao: &Statement) -> ControlFlow<String> {
+        if matches!(instrucao, Statement::Query(_)) {
+            return ControlFlow::Continue(());
+        }
+        let comando = instrucao.to_string()
+            .split_whitespace()
+            .next()
+            .unwrap_or_default()
+            .to_uppercase();
+        ControlFlow::Break(format!("Instrução {} não é permitida; apenas SELECT", comando))
+    }
+
+    fn pre_visit_query(&mut self, consulta: &Query) -> ControlFlow<String> {
+        if !consulta.locks.is_empty() {
+            return ControlFlow::Break(
+                "SELECT ... FOR UPDATE/SHARE não é permitido (bloqueia linhas)".to_string(),
+            );
+        }
+        verificar_corpo(&consulta.body)
+    }
+
+    fn pre_visit_table_factor(&mut self, tabela: &TableFactor) -> ControlFlow<String> {
+        match tabela {
+            TableFactor::Table { name, args: Some(_), .. } | TableFactor::Function { name, .. } => {
+                verificar_funcao(name)
+            }
+            _ => ControlFlow::Continue(()),
+        }
+    }
+
+    fn pre_visit_expr(&mut self, expr: &Expr) -> ControlFlow<String> {
+        match expr {
+            Expr::Function(funcao) => verificar_funcao(&funcao.name),
+            _ => ControlFlow::Continue(()),
+        }
+    }
+}
+
+/// `SELECT ... INTO` cria tabelas (`WITH ... INSERT/UPDATE` cai em `pre_visit_statement`)
+fn verificar_corpo(corpo: &SetExpr) -> ControlFlow<String> {
+    match corpo {
+        SetExpr::Select(select) if select.into.is_some() => ControlFlow::Break(
+            "SELECT ... INTO não é permitido (cria ou grava tabelas)".to_string(),
+        ),
+        SetExpr::SetOperation { left, right, .. } => {
+            verificar_corpo(left)?;
+            verificar_corpo(right)
+        }
+        _ => ControlFlow::Continue(()),
+    }
+}
+
+fn verificar_funcao(nome: &ObjectName) -> ControlFlow<String> {
+    let funcao = match nome.0.last() {
+        Some(ident) => ident.value.to_lowercase(),
+        None => return ControlFlow::Continue(()),
+    };
+
+    let proibida = FUNCOES_PROIBIDAS.iter().find(|(padrao, _)| match padrao.strip_suffix('*') {
+        Some(prefixo) => funcao.starts_with(prefixo),
+        None => funcao == *padrao,
+    });
+
+    match proibida {
+        Some((_, motivo)) => ControlFlow::Break(format!("A função {}() não é permitida: {}", funcao, motivo)),
+        None => ControlFlow::Continue(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aceito(sql: &str, dialeto: Dialeto) {
+        if let Err(motivo) = validar_somente_leitura(sql, dialeto) {
+            panic!("deveria aceitar {:?}: {}", sql, motivo);
+        }
+    }
+
+    fn recusado(sql: &str, dialeto: Dialeto) -> String {
+        match validar_somente_leitura(sql, dialeto) {
+            Ok(()) => panic!("deveria recusar {:?}", sql),
+            Err(motivo) => motivo,
+        }
+    }
+
+    #[test]
+    fn aceita_consultas_de_leitura() {
+        aceito("SELECT id, nome FROM clientes WHERE ativo = true ORDER BY nome LIMIT 10", Dialeto::Postgres);
+        aceito("WITH ultimos AS (SELECT * FROM pedidos) SELECT COUNT(*) FROM ultimos", Dialeto::Postgres);
+        aceito("SELECT a FROM t1 UNION ALL SELECT b FROM t2", Dialeto::Postgres);
+        aceito("SELECT * FROM x WHERE id IN (SELECT id FROM y WHERE z = $1)", Dialeto::Postgres);
+        aceito("SELECT TOP 10 ZC7_NUM FROM ZC7010 WHERE D_E_L_E_T_ = ' ' AND ZC7_NUM = @P1", Dialeto::SqlServer);
+        aceito("SELECT LEFT(nome, 3), GETDATE() FROM clientes", Dialeto::SqlServer);
+    }
+
+    #[test]
+    fn recusa_escrita_e_ddl() {
+        for sql in [
+            "INSERT INTO t VALUES (1)",
+            "UPDATE t SET a = 1",
+            "DELETE FROM t",
+            "DROP TABLE t",
+            "CREATE TABLE t (a INT)",
+            "TRUNCATE t",
+            "COPY t TO '/tmp/x'",
+        ] {
+            assert!(recusado(sql, Dialeto::Postgres).contains("não é permitida"), "{}", sql);
+        }
+        assert!(recusado("EXEC sp_who", Dialeto::SqlServer).contains("não é permitida"));
+    }
+
+    #[test]
+    fn recusa_dml_dentro_de_cte() {
+        recusado("WITH x AS (DELETE FROM t RETURNING *) SELECT * FROM x", Dialeto::Postgres);
+        recusado("WITH x AS (SELECT 1 AS a) INSERT INTO t SELECT a FROM x", Dialeto::Postgres);
+    }
+
+    #[test]
+    fn recusa_select_into_e_locks() {
+        assert!(recusado("SELECT * INTO copia FROM t", Dialeto::SqlServer).contains("INTO"));
+        assert!(recusado("SELECT a FROM t UNION SELECT b INTO copia FROM u", Dialeto::Postgres).contains("INTO"));
+        assert!(recusado("SELECT * FROM t FOR UPDATE", Dialeto::Postgres).contains("FOR UPDATE"));
+    }
+
+    #[test]
+    fn recusa_multiplas_instrucoes() {
+        assert!(recusado("SELECT 1; DELETE FROM t", Dialeto::Postgres).contains("Apenas uma instrução"));
+        assert!(recusado("SELECT 1; SELECT 2", Dialeto::SqlServer).contains("Apenas uma instrução"));
+        assert!(recusado("", Dialeto::Postgres).contains("Nenhuma instrução"));
+    }
+
+    #[test]
+    fn recusa_funcoes_perigosas() {
+        assert!(recusado("SELECT pg_sleep(10)", Dialeto::Postgres).contains("pg_sleep"));
+        assert!(recusado("SELECT pg_catalog.pg_sleep(10)", Dialeto::Postgres).contains("pg_sleep"));
+        assert!(recusado("SELECT PG_SLEEP_FOR('5 minutes')", Dialeto::Postgres).contains("pg_sleep_for"));
+        assert!(recusado("SELECT * FROM t WHERE id = (SELECT pg_sleep(1))", Dialeto::Postgres).contains("pg_sleep"));
+        assert!(recusado("SELECT * FROM dblink('host=x', 'SELECT 1') AS r(a int)", Dialeto::Postgres).contains("dblink"));
+        assert!(recusado("SELECT public.dblink_exec('x', 'DROP TABLE t')", Dialeto::Postgres).contains("dblink_exec"));
+        assert!(recusado("SELECT pg_read_file('/etc/passwd')", Dialeto::Postgres).contains("pg_read_file"));
+        assert!(recusado("SELECT nextval('seq')", Dialeto::Postgres).contains("nextval"));
+    }
+
+    #[test]
+    fn recusa_fontes_externas_no_sql_server() {
+        assert!(recusado(
+            "SELECT * FROM OPENROWSET('SQLNCLI', 'Server=x;', 'SELECT 1')",
+            Dialeto::SqlServer,
+        ).contains("openrowset"));
+        assert!(recusado("SELECT * FROM OPENQUERY(LINKED, 'SELECT 1')", Dialeto::SqlServer).contains("openquery"));
+    }
+
+    #[test]
+    fn recusa_sql_invalido() {
+        assert!(recusado("SELEC * FROM t", Dialeto::Postgres).contains("interpretar"));
+    }
+
+    #[test]
+    fn parametros_posicionais_do_postgres() {
+        let indices = parametros_posicionais(
+            "SELECT * FROM t WHERE a = $1 AND b IN (SELECT c FROM u WHERE d = $3) OR e = $1 OR f = $2",
+            Dialeto::Postgres,
+        ).unwrap();
+        assert_eq!(indices.into_iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn parametros_posicionais_do_sql_server() {
+        let indices = parametros_posicionais(
+            "WITH x AS (SELECT * FROM t WHERE a = @P2) SELECT * FROM x WHERE b = @p1",
+            Dialeto::SqlServer,
+        ).unwrap();
+        assert_eq!(indices.into_iter().collect::<Vec<_>>(), vec![1, 2]);
+        assert!(parametros_posicionais("SELECT * FROM t WHERE a = @filial", Dialeto::SqlServer).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,503 @@
+//! 🔄 Integração Portal → Protheus
+//!
+//! Worker em background que envia pedidos confirmados e ainda não integrados
+//! para as tabelas de staging do Protheus (ZC7010 cabeçalho / ZC8010 itens).
+//!
+//! - Sucesso: grava `numero_pedido`, marca `integrado = 1` e status `integrado`.
+//!   A gravação no Protheus só é confirmada se o pedido ainda estiver `confirmado`
+//!   e não excluído no Portal; caso contrário é desfeita
+//! - Falha: incrementa `tentativas_integracao`; a próxima tentativa respeita
+//!   backoff exponencial a partir de `updated_at`. Uma falha não interrompe o lote
+//! - Idempotente: o id do Portal fica em `ZC7_IDPORT`, então um pedido já gravado
+//!   no Protheus (ex.: falha ao atualizar o Portal) não é duplicado
+//! - Numeração: `ZC7_NUM` e `R_E_C_N_O_` vêm de sequências do SQL Server
+//!   (`PROTHEUS_SEQUENCIAS_INTEGRACAO.sql`), sem varrer nem travar ZC7010/ZC8010
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::TryStreamExt;
+use serde::Serialize;
+use tiberius::{Query, QueryItem};
+
+use crate::config::IntegracaoConfig;
+use crate::database::DatabasePools;
+use crate::errors::ApiError;
+use crate::handlers::pedidos::transacao;
+use crate::models::StatusPedido;
+
+type Conexao<'a> = bb8::PooledConnection<'a, bb8_tiberius::ConnectionManager>;
+
+/// Filtro da fila de integração (bind: @P2 = status confirmado)
+pub const PENDENTES_WHERE: &str =
+    "p.status_pedido = @P2 AND p.integrado = 0 AND p.deleted_at IS NULL";
+
+/// Expressão SQL com o momento da próxima tentativa de um pedido da fila.
+/// Sem tentativas: imediato. Depois: base * 2^(tentativas-1) minutos após `updated_at`.
+pub fn proxima_tentativa_sql(backoff_base_minutos: i32) -> String {
+    format!(
+        "CASE WHEN ISNULL(p.tentativas_integracao, 0) = 0 THEN p.updated_at \
+         ELSE DATEADD(MINUTE, {} * POWER(2, CASE WHEN p.tentativas_integracao > 10 THEN 9 \
+         ELSE p.tentativas_integracao - 1 END), p.updated_at) END",
+        backoff_base_minutos.max(1)
+    )
+}
+
+/// Estado do worker exposto no endpoint administrativo
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct EstadoIntegracao {
+    pub habilitada: bool,
+    pub em_execucao: bool,
+    pub ultima_execucao: Option<String>,
+    pub integrados_total: u64,
+    pub falhas_total: u64,
+    /// Último erro por pedido (removido quando o pedido é integrado)
+    pub ultimos_erros: HashMap<i32, ErroIntegracao>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErroIntegracao {
+    pub mensagem: String,
+    pub ocorrido_em: String,
+}
+
+pub type EstadoCompartilhado = Arc<Mutex<EstadoIntegracao>>;
+
+/// Sequências exigidas no banco do Protheus (criadas por `PROTHEUS_SEQUENCIAS_INTEGRACAO.sql`)
+const SEQUENCIAS: [&str; 3] = ["SEQ_API_ZC7_NUM", "SEQ_API_ZC7_RECNO", "SEQ_API_ZC8_RECNO"];
+
+struct PedidoPendente {
+    id: i32,
+    codigo_cliente: String,
+    loja_cliente: String,
+    emissao: String,
+    condicao_pagamento: String,
+    tipo_frete: String,
+    mensagem: String,
+    natureza: String,
+}
+
+struct ItemPendente {
+    codigo_produto: String,
+    quantidade: i32,
+    preco_unitario: f64,
+}
+
+/// Inicia o worker em uma task tokio. Não faz nada se a integração estiver desabilitada.
+pub fn iniciar(pools: DatabasePools, config: IntegracaoConfig, estado: EstadoCompartilhado) {
+    if let Ok(mut e) = estado.lock() {
+        e.habilitada = config.habilitada;
+    }
+
+    if !config.habilitada {
+        log::info!("⏸️ Integração Portal → Protheus desabilitada (defina INTEGRACAO_HABILITADA=true para ativar)");
+        return;
+    }
+
+    log::info!("🔄 Worker de integração Portal → Protheus iniciado (intervalo {}s, lote {})",
+        config.intervalo_segundos, config.lote);
+
+    tokio::spawn(async move {
+        if let Err(e) = verificar_sequencias(&pools).await {
+            log::error!("❌ Integração Portal → Protheus não iniciada: {}", e);
+            if let Ok(mut e) = estado.lock() {
+                e.habilitada = false;
+            }
+            return;
+        }
+
+        let mut intervalo = tokio::time::interval(Duration::from_secs(config.intervalo_segundos.max(5)));
+        intervalo.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            intervalo.tick().await;
+
+            if let Ok(mut e) = estado.lock() {
+                e.em_execucao = true;
+            }
+
+            if let Err(e) = processar_fila(&pools, &config, &estado).await {
+                log::error!("❌ Erro no ciclo de integração: {}", e);
+            }
+
+            if let Ok(mut e) = estado.lock() {
+                e.em_execucao = false;
+                e.ultima_execucao = Some(agora());
+            }
+        }
+    });
+}
+
+/// Processa um lote da fila: integra cada pedido elegível e registra o resultado
+async fn processar_fila(
+    pools: &DatabasePools,
+    config: &IntegracaoConfig,
+    estado: &EstadoCompartilhado,
+) -> Result<(), ApiError> {
+    let mut portal = pools.sqlserver_portal.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
+
+    let pendentes = buscar_pendentes(&mut portal, config).await?;
+    if pendentes.is_empty() {
+        return Ok(());
+    }
+
+    log::info!("📤 {} pedido(s) na fila de integração", pendentes.len());
+
+    let mut protheus = pools.sqlserver_protheus.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Protheus: {}", e)))?;
+
+    for pedido in pendentes {
+        match integrar_pedido(&mut portal, &mut protheus, &pedido, &config.filial_protheus).await {
+            Ok(Some(numero)) => {
+                log::info!("✅ Pedido {} integrado no Protheus como {}", pedido.id, numero);
+
+                if let Ok(mut e) = estado.lock() {
+                    e.integrados_total += 1;
+                    e.ultimos_erros.remove(&pedido.id);
+                }
+            }
+            Ok(None) => {
+                log::warn!("⚠️ Pedido {} excluído ou fora de '{}' durante a integração; gravação no Protheus desfeita",
+                    pedido.id, StatusPedido::Confirmado.as_str());
+            }
+            Err(erro) => {
+                log::warn!("⚠️ Falha ao integrar pedido {}: {}", pedido.id, erro);
+                if let Err(e) = registrar_falha(&mut portal, pedido.id).await {
+                    log::error!("❌ {}", e);
+                }
+
+                if let Ok(mut e) = estado.lock() {
+                    e.falhas_total += 1;
+                    e.ultimos_erros.insert(pedido.id, ErroIntegracao {
+                        mensagem: erro.to_string(),
+                        ocorrido_em: agora(),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn buscar_pendentes(
+    conn: &mut Conexao<'_>,
+    config: &IntegracaoConfig,
+) -> Result<Vec<PedidoPendente>, ApiError> {
+    let sql = format!(r#"
+        SELECT TOP (@P1)
+            p.id,
+            p.codigo_cliente,
+            p.loja_cliente,
+            CONVERT(varchar(8), ISNULL(p.emissao, GETDATE()), 112) as emissao,
+            p.condicao_pagamento,
+            p.tipo_frete,
+            p.mensagem,
+            p.natureza
+        FROM pedidos p
+        WHERE {}
+          AND ISNULL(p.tentativas_integracao, 0) < @P3
+          AND {} <= GETDATE()
+        ORDER BY p.id
+    "#, PENDENTES_WHERE, proxima_tentativa_sql(config.backoff_base_minutos));
+
+    let mut query = Query::new(sql);
+    query.bind(config.lote.max(1));
+    query.bind(StatusPedido::Confirmado.as_str());
+    query.bind(config.max_tentativas);
+
+    let mut stream = query.query(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar fila de integração: {}", e)))?;
+
+    let texto = |row: &tiberius::Row, i: usize| row.get::<&str, _>(i).unwrap_or("").trim().to_string();
+
+    let mut pendentes = Vec::new();
+    while let Some(item) = stream.try_next().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler fila de integração: {}", e)))? {
+        if let QueryItem::Row(row) = item {
+            pendentes.push(PedidoPendente {
+                id: row.get::<i32, _>(0).unwrap_or(0),
+                codigo_cliente: texto(&row, 1),
+                loja_cliente: texto(&row, 2),
+                emissao: texto(&row, 3),
+                condicao_pagamento: texto(&row, 4),
+                tipo_frete: texto(&row, 5),
+                mensagem: texto(&row, 6),
+                natureza: texto(&row, 7),
+            });
+        }
+    }
+
+    Ok(pendentes)
+}
+
+async fn buscar_items(conn: &mut Conexao<'_>, pedido_id: i32) -> Result<Vec<ItemPendente>, ApiError> {
+    let mut query = Query::new(r#"
+        SELECT
+            pr.codigo,
+            i.quantidade,
+            CAST(i.preco_unitario AS FLOAT) as preco_unitario
+        FROM items i
+        INNER JOIN produtos pr ON pr.id = i.produto_id
+        WHERE i.pedido_id = @P1
+        ORDER BY i.id
+    "#);
+    query.bind(pedido_id);
+
+    let mut stream = query.query(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar itens do pedido: {}", e)))?;
+
+    let mut items = Vec::new();
+    while let Some(item) = stream.try_next().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler itens do pedido: {}", e)))? {
+        if let QueryItem::Row(row) = item {
+            items.push(ItemPendente {
+                codigo_produto: row.get::<&str, _>(0).unwrap_or("").trim().to_string(),
+                quantidade: row.get::<i32, _>(1).unwrap_or(0),
+                preco_unitario: row.get::<f64, _>(2).unwrap_or(0.0),
+            });
+        }
+    }
+
+    if items.is_empty() {
+        return Err(ApiError::BadRequest(format!("Pedido {} não possui itens", pedido_id)));
+    }
+
+    Ok(items)
+}
+
+/// Grava cabeçalho e itens no Protheus e marca o pedido como integrado no Portal.
+/// A transação do Protheus só é confirmada depois que o Portal aceita a marcação;
+/// retorna `None` (e desfaz a gravação) se o pedido foi excluído ou mudou de status nesse meio tempo.
+async fn integrar_pedido(
+    portal: &mut Conexao<'_>,
+    protheus: &mut Conexao<'_>,
+    pedido: &PedidoPendente,
+    filial: &str,
+) -> Result<Option<String>, ApiError> {
+    let items = buscar_items(portal, pedido.id).await?;
+
+    if let Some(numero) = buscar_numero_existente(protheus, pedido.id, filial).await? {
+        log::info!("ℹ️ Pedido {} já existia no Protheus como {}", pedido.id, numero);
+        let marcado = marcar_integrado(portal, pedido.id, &numero).await?;
+        if !marcado {
+            log::error!("❌ Pedido {} já está no Protheus como {}, mas mudou no Portal; verifique manualmente",
+                pedido.id, numero);
+        }
+        return Ok(marcado.then_some(numero));
+    }
+
+    transacao::iniciar(protheus).await?;
+
+    let resultado = match inserir_cabecalho_e_items(protheus, pedido, &items, filial).await {
+        Ok(numero) => marcar_integrado(portal, pedido.id, &numero).await
+            .map(|marcado| marcado.then_some(numero)),
+        Err(e) => Err(e),
+    };
+
+    match resultado {
+        Ok(Some(numero)) => {
+            if let Err(e) = transacao::confirmar(protheus).await {
+                // O Portal já aponta para o número: volta o pedido para a fila
+                desmarcar_integrado(portal, pedido.id, &numero).await?;
+                return Err(e);
+            }
+            Ok(Some(numero))
+        }
+        Ok(None) => {
+            transacao::desfazer(protheus).await;
+            Ok(None)
+        }
+        Err(e) => {
+            transacao::desfazer(protheus).await;
+            Err(e)
+        }
+    }
+}
+
+async fn buscar_numero_existente(
+    conn: &mut Conexao<'_>,
+    pedido_id: i32,
+    filial: &str,
+) -> Result<Option<String>, ApiError> {
+    let mut query = Query::new(r#"
+        SELECT TOP 1 ZC7_NUM
+        FROM ZC7010
+        WHERE ZC7_FILIAL = @P1 AND ZC7_IDPORT = @P2 AND D_E_L_E_T_ = ' '
+    "#);
+    query.bind(filial);
+    query.bind(pedido_id);
+
+    let row = query.query(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao consultar ZC7010: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler ZC7010: {}", e)))?;
+
+    Ok(row.and_then(|r| r.get::<&str, _>(0).map(|s| s.trim().to_string())))
+}
+
+async fn inserir_cabecalho_e_items(
+    conn: &mut Conexao<'_>,
+    pedido: &PedidoPendente,
+    items: &[ItemPendente],
+    filial: &str,
+) -> Result<String, ApiError> {
+    // Sequências não participam da transação: um rollback só deixa um número sem uso
+    let proximo = Query::new(r#"
+        SELECT RIGHT('000000' + CAST(NEXT VALUE FOR dbo.SEQ_API_ZC7_NUM AS VARCHAR(6)), 6)
+    "#);
+
+    let numero = proximo.query(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao gerar número do pedido: {}", e)))?
+        .into_row().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler número do pedido: {}", e)))?
+        .and_then(|r| r.get::<&str, _>(0).map(|s| s.to_string()))
+        .ok_or_else(|| ApiError::Database("Número do pedido não gerado".to_string()))?;
+
+    let mut cabecalho = Query::new(r#"
+        INSERT INTO ZC7010 (
+            ZC7_FILIAL, ZC7_NUM, ZC7_CLIENT, ZC7_LOJA, ZC7_EMISSA,
+            ZC7_CONDPG, ZC7_TPFRET, ZC7_MENSAG, ZC7_NATURE, ZC7_IDPORT,
+            D_E_L_E_T_, R_E_C_N_O_
+        ) VALUES (
+            @P1, @P2, @P3, @P4, @P5,
+            @P6, @P7, @P8, @P9, @P10,
+            ' ', NEXT VALUE FOR dbo.SEQ_API_ZC7_RECNO
+        )
+    "#);
+    cabecalho.bind(filial);
+    cabecalho.bind(numero.as_str());
+    cabecalho.bind(pedido.codigo_cliente.as_str());
+    cabecalho.bind(pedido.loja_cliente.as_str());
+    cabecalho.bind(pedido.emissao.as_str());
+    cabecalho.bind(pedido.condicao_pagamento.as_str());
+    cabecalho.bind(pedido.tipo_frete.as_str());
+    cabecalho.bind(pedido.mensagem.as_str());
+    cabecalho.bind(pedido.natureza.as_str());
+    cabecalho.bind(pedido.id);
+
+    cabecalho.execute(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao inserir ZC7010: {}", e)))?;
+
+    for (i, item) in items.iter().enumerate() {
+        let mut linha = Query::new(r#"
+            INSERT INTO ZC8010 (
+                ZC8_FILIAL, ZC8_NUM, ZC8_ITEM, ZC8_PRODUT,
+                ZC8_QUANT, ZC8_PRCVEN, ZC8_VALOR,
+                D_E_L_E_T_, R_E_C_N_O_
+            ) VALUES (
+                @P1, @P2, @P3, @P4,
+                @P5, @P6, @P7,
+                ' ', NEXT VALUE FOR dbo.SEQ_API_ZC8_RECNO
+            )
+        "#);
+        linha.bind(filial);
+        linha.bind(numero.as_str());
+        linha.bind(format!("{:02}", i + 1));
+        linha.bind(item.codigo_produto.as_str());
+        linha.bind(item.quantidade as f64);
+        linha.bind(item.preco_unitario);
+        linha.bind(item.preco_unitario * item.quantidade as f64);
+
+        linha.execute(&mut **conn).await
+            .map_err(|e| ApiError::Database(format!("Erro ao inserir ZC8010 (item {}): {}", i + 1, e)))?;
+    }
+
+    Ok(numero)
+}
+
+/// Marca o pedido como integrado se ele ainda estiver confirmado e não excluído.
+/// Retorna `false` quando nenhuma linha foi alterada.
+async fn marcar_integrado(conn: &mut Conexao<'_>, pedido_id: i32, numero: &str) -> Result<bool, ApiError> {
+    let mut update = Query::new(r#"
+        UPDATE pedidos
+        SET integrado = 1,
+            numero_pedido = @P2,
+            status_pedido = @P3,
+            updated_at = GETDATE()
+        WHERE id = @P1
+          AND integrado = 0
+          AND status_pedido = @P4
+          AND deleted_at IS NULL
+    "#);
+    update.bind(pedido_id);
+    update.bind(numero);
+    update.bind(StatusPedido::Integrado.as_str());
+    update.bind(StatusPedido::Confirmado.as_str());
+
+    let result = update.execute(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao marcar pedido {} como integrado: {}", pedido_id, e)))?;
+
+    Ok(result.total() > 0)
+}
+
+/// Desfaz `marcar_integrado` quando a gravação no Protheus não pôde ser confirmada
+async fn desmarcar_integrado(conn: &mut Conexao<'_>, pedido_id: i32, numero: &str) -> Result<(), ApiError> {
+    let mut update = Query::new(r#"
+        UPDATE pedidos
+        SET integrado = 0,
+            numero_pedido = NULL,
+            status_pedido = @P3,
+            updated_at = GETDATE()
+        WHERE id = @P1 AND numero_pedido = @P2 AND integrado = 1
+    "#);
+    update.bind(pedido_id);
+    update.bind(numero);
+    update.bind(StatusPedido::Confirmado.as_str());
+
+    update.execute(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao devolver pedido {} para a fila: {}", pedido_id, e)))?;
+
+    Ok(())
+}
+
+async fn registrar_falha(conn: &mut Conexao<'_>, pedido_id: i32) -> Result<(), ApiError> {
+    let mut update = Query::new(r#"
+        UPDATE pedidos
+        SET tentativas_integracao = ISNULL(tentativas_integracao, 0) + 1,
+            updated_at = GETDATE()
+        WHERE id = @P1
+    "#);
+    update.bind(pedido_id);
+
+    update.execute(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao registrar falha do pedido {}: {}", pedido_id, e)))?;
+
+    Ok(())
+}
+
+/// Confere se as sequências de numeração existem no banco do Protheus
+async fn verificar_sequencias(pools: &DatabasePools) -> Result<(), ApiError> {
+    let mut conn = pools.sqlserver_protheus.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Protheus: {}", e)))?;
+
+    let mut stream = Query::new("SELECT name FROM sys.sequences WHERE name LIKE 'SEQ[_]API[_]%'")
+        .query(&mut *conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao consultar sequências do Protheus: {}", e)))?;
+
+    let mut existentes = Vec::new();
+    while let Some(item) = stream.try_next().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler sequências do Protheus: {}", e)))? {
+        if let QueryItem::Row(row) = item {
+            existentes.push(row.get::<&str, _>(0).unwrap_or("").to_string());
+        }
+    }
+
+    let faltando: Vec<&str> = SEQUENCIAS.iter().copied()
+        .filter(|nome| !existentes.iter().any(|e| e.eq_ignore_ascii_case(nome)))
+        .collect();
+    if !faltando.is_empty() {
+        return Err(ApiError::Configuration(format!(
+            "sequências ausentes no Protheus: {} (execute PROTHEUS_SEQUENCIAS_INTEGRACAO.sql)",
+            faltando.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+fn agora() -> String {
+    chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string()
+}
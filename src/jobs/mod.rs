@@ -0,0 +1,9 @@
+//! ⚙️ Jobs - Tarefas em background executadas dentro do servidor
+//!
+//! - Integração de pedidos confirmados do Portal no Protheus
+//! - Sincronização de faturamento e expedição do Protheus no Portal
+//! - Recarga da lista de tokens revogados
+
+pub mod integracao_protheus;
+pub mod sincronizacao_status;
+pub mod tokens_revogados;
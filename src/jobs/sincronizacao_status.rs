@@ -0,0 +1,252 @@
+//! 🔁 Sincronização de status Protheus → Portal
+//!
+//! Job periódico que lê faturamento e expedição dos pedidos integrados no
+//! Protheus e atualiza `status_liberacao`, `numero_nota_fiscal`, `transportadora`,
+//! `rastreio_carga` e `status_pedido` no Portal.
+//!
+//! - Percorre os pedidos integrados ainda não expedidos em lotes por id (cursor circular)
+//! - Só avança o status (integrado → Faturado → Expedido), nunca retrocede
+//! - Campos vazios no Protheus não apagam o que já está no Portal
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::TryStreamExt;
+use serde::Serialize;
+use tiberius::{Query, QueryItem};
+
+use crate::config::SincronizacaoConfig;
+use crate::database::DatabasePools;
+use crate::errors::ApiError;
+use crate::handlers::protheus_handlers::{buscar_pedido_protheus, PedidoProtheus};
+use crate::models::StatusPedido;
+
+type Conexao<'a> = bb8::PooledConnection<'a, bb8_tiberius::ConnectionManager>;
+
+/// Estado do job exposto no endpoint administrativo
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct EstadoSincronizacao {
+    pub habilitada: bool,
+    pub em_execucao: bool,
+    pub ultima_execucao: Option<String>,
+    pub ultimo_resultado: Option<ResumoSincronizacao>,
+    pub ultimo_erro: Option<String>,
+    pub alterados_total: u64,
+    /// Último id processado; volta a 0 quando a fila é percorrida inteira
+    pub cursor_id: i32,
+}
+
+/// Resultado de uma execução
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ResumoSincronizacao {
+    pub verificados: usize,
+    pub alterados: u64,
+    pub nao_encontrados: usize,
+    pub falhas: usize,
+}
+
+pub type EstadoCompartilhado = Arc<Mutex<EstadoSincronizacao>>;
+
+struct PedidoIntegrado {
+    id: i32,
+    numero_pedido: String,
+    status_pedido: String,
+}
+
+/// Inicia o job em uma task tokio. Não faz nada se a sincronização estiver desabilitada.
+pub fn iniciar(pools: DatabasePools, config: SincronizacaoConfig, estado: EstadoCompartilhado) {
+    if let Ok(mut e) = estado.lock() {
+        e.habilitada = config.habilitada;
+    }
+
+    if !config.habilitada {
+        log::info!("⏸️ Sincronização Protheus → Portal desabilitada (defina SINCRONIZACAO_HABILITADA=true para ativar)");
+        return;
+    }
+
+    log::info!("🔁 Sincronização de status Protheus → Portal iniciada (intervalo {}s, lote {})",
+        config.intervalo_segundos, config.lote);
+
+    tokio::spawn(async move {
+        let mut intervalo = tokio::time::interval(Duration::from_secs(config.intervalo_segundos.max(30)));
+        intervalo.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            intervalo.tick().await;
+
+            let cursor = match estado.lock() {
+                Ok(mut e) => {
+                    e.em_execucao = true;
+                    e.cursor_id
+                }
+                Err(_) => 0,
+            };
+
+            let resultado = sincronizar_lote(&pools, &config, cursor).await;
+
+            if let Ok(mut e) = estado.lock() {
+                e.em_execucao = false;
+                e.ultima_execucao = Some(chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string());
+
+                match resultado {
+                    Ok((resumo, proximo_cursor)) => {
+                        log::info!("🔁 Sincronização Protheus: {} verificados, {} alterados, {} não encontrados, {} falhas",
+                            resumo.verificados, resumo.alterados, resumo.nao_encontrados, resumo.falhas);
+                        e.alterados_total += resumo.alterados;
+                        e.cursor_id = proximo_cursor;
+                        e.ultimo_resultado = Some(resumo);
+                        e.ultimo_erro = None;
+                    }
+                    Err(erro) => {
+                        log::error!("❌ Erro na sincronização Protheus → Portal: {}", erro);
+                        e.ultimo_erro = Some(erro.to_string());
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// Sincroniza um lote a partir de `cursor` e retorna o resumo e o próximo cursor
+async fn sincronizar_lote(
+    pools: &DatabasePools,
+    config: &SincronizacaoConfig,
+    cursor: i32,
+) -> Result<(ResumoSincronizacao, i32), ApiError> {
+    let mut portal = pools.sqlserver_portal.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Portal: {}", e)))?;
+
+    let lote = config.lote.max(1);
+    let pedidos = buscar_integrados(&mut portal, cursor, lote).await?;
+
+    let proximo_cursor = if (pedidos.len() as i32) < lote {
+        0
+    } else {
+        pedidos.last().map(|p| p.id).unwrap_or(0)
+    };
+
+    let mut resumo = ResumoSincronizacao::default();
+    if pedidos.is_empty() {
+        return Ok((resumo, proximo_cursor));
+    }
+
+    let mut protheus = pools.sqlserver_protheus.get().await
+        .map_err(|e| ApiError::Database(format!("Erro ao conectar no Protheus: {}", e)))?;
+
+    for pedido in pedidos {
+        resumo.verificados += 1;
+
+        let dados = match buscar_pedido_protheus(&mut protheus, &config.filial_protheus, &pedido.numero_pedido).await {
+            Ok(Some(dados)) => dados,
+            Ok(None) => {
+                log::debug!("Pedido {} ({}) não encontrado no Protheus", pedido.id, pedido.numero_pedido);
+                resumo.nao_encontrados += 1;
+                continue;
+            }
+            Err(e) => {
+                log::warn!("⚠️ Erro ao consultar pedido {} no Protheus: {}", pedido.numero_pedido, e);
+                resumo.falhas += 1;
+                continue;
+            }
+        };
+
+        match atualizar_portal(&mut portal, &pedido, &dados).await {
+            Ok(alterados) => resumo.alterados += alterados,
+            Err(e) => {
+                log::warn!("⚠️ Erro ao atualizar pedido {} no Portal: {}", pedido.id, e);
+                resumo.falhas += 1;
+            }
+        }
+    }
+
+    Ok((resumo, proximo_cursor))
+}
+
+async fn buscar_integrados(
+    conn: &mut Conexao<'_>,
+    cursor: i32,
+    lote: i32,
+) -> Result<Vec<PedidoIntegrado>, ApiError> {
+    let sql = format!(r#"
+        SELECT TOP (@P1)
+            p.id,
+            p.numero_pedido,
+            p.status_pedido
+        FROM pedidos p
+        WHERE p.id > @P2
+          AND p.integrado = 1
+          AND p.numero_pedido IS NOT NULL
+          AND p.deleted_at IS NULL
+          AND p.status_pedido IN ({})
+        ORDER BY p.id
+    "#, StatusPedido::lista_sql(&[StatusPedido::Integrado, StatusPedido::Faturado]));
+
+    let mut query = Query::new(sql);
+    query.bind(lote);
+    query.bind(cursor);
+
+    let mut stream = query.query(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao buscar pedidos integrados: {}", e)))?;
+
+    let mut pedidos = Vec::new();
+    while let Some(item) = stream.try_next().await
+        .map_err(|e| ApiError::Database(format!("Erro ao ler pedidos integrados: {}", e)))? {
+        if let QueryItem::Row(row) = item {
+            pedidos.push(PedidoIntegrado {
+                id: row.get::<i32, _>(0).unwrap_or(0),
+                numero_pedido: row.get::<&str, _>(1).unwrap_or("").trim().to_string(),
+                status_pedido: row.get::<&str, _>(2).unwrap_or("").to_string(),
+            });
+        }
+    }
+
+    Ok(pedidos)
+}
+
+/// Atualiza o pedido no Portal apenas se algo mudou; retorna as linhas alteradas (0 ou 1)
+async fn atualizar_portal(
+    conn: &mut Conexao<'_>,
+    pedido: &PedidoIntegrado,
+    dados: &PedidoProtheus,
+) -> Result<u64, ApiError> {
+    // Status só avança; status desconhecido ou retrocesso mantém o valor atual
+    let destino = dados.status_portal();
+    let status = match StatusPedido::from_banco(&pedido.status_pedido) {
+        Some(atual) if atual.alcanca(destino) => destino.as_str(),
+        _ => pedido.status_pedido.as_str(),
+    };
+
+    // Só grava se o status e a exclusão continuam como lidos em `buscar_integrados`;
+    // pedidos alterados no meio do caminho são reavaliados no próximo ciclo
+    let mut update = Query::new(r#"
+        UPDATE pedidos
+        SET status_liberacao = COALESCE(@P2, status_liberacao),
+            numero_nota_fiscal = COALESCE(@P3, numero_nota_fiscal),
+            transportadora = COALESCE(@P4, transportadora),
+            rastreio_carga = COALESCE(@P5, rastreio_carga),
+            status_pedido = @P6,
+            updated_at = GETDATE()
+        WHERE id = @P1
+          AND status_pedido = @P7
+          AND deleted_at IS NULL
+          AND (
+               (@P2 IS NOT NULL AND ISNULL(status_liberacao, '') <> @P2)
+            OR (@P3 IS NOT NULL AND ISNULL(numero_nota_fiscal, '') <> @P3)
+            OR (@P4 IS NOT NULL AND ISNULL(transportadora, '') <> @P4)
+            OR (@P5 IS NOT NULL AND ISNULL(rastreio_carga, '') <> @P5)
+            OR status_pedido <> @P6
+          )
+    "#);
+    update.bind(pedido.id);
+    update.bind(dados.status_liberacao.as_deref());
+    update.bind(dados.numero_nota_fiscal.as_deref());
+    update.bind(dados.transportadora.as_deref());
+    update.bind(dados.rastreio_carga.as_deref());
+    update.bind(status);
+    update.bind(pedido.status_pedido.as_str());
+
+    let result = update.execute(&mut **conn).await
+        .map_err(|e| ApiError::Database(format!("Erro ao atualizar pedido: {}", e)))?;
+
+    Ok(result.total())
+}
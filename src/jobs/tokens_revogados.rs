@@ -0,0 +1,41 @@
+//! 🚫 Recarga da lista de tokens revogados
+//!
+//! A lista em memória consultada pelo `JwtMiddleware` é recarregada periodicamente
+//! de `api_revoked_tokens`, para que revogações feitas em outra instância da API
+//! passem a valer aqui e para descartar entradas já expiradas.
+
+use std::time::Duration;
+
+use deadpool_postgres::Pool;
+
+use crate::auth::TokensRevogados;
+use crate::errors::ApiError;
+use crate::handlers::usuarios::tokens;
+
+const INTERVALO_SEGUNDOS: u64 = 60;
+
+/// Carrega a lista do banco uma vez
+pub async fn recarregar(pool: &Pool, revogados: &TokensRevogados) -> Result<usize, ApiError> {
+    let client = pool.get().await?;
+    let lista = tokens::carregar_revogados(&client).await?;
+    let total = lista.len();
+    revogados.substituir(lista);
+    Ok(total)
+}
+
+/// Inicia a recarga periódica em uma task tokio
+pub fn iniciar(pool: Pool, revogados: TokensRevogados) {
+    tokio::spawn(async move {
+        let mut intervalo = tokio::time::interval(Duration::from_secs(INTERVALO_SEGUNDOS));
+        intervalo.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            intervalo.tick().await;
+
+            match recarregar(&pool, &revogados).await {
+                Ok(total) => log::debug!("🚫 Lista de tokens revogados recarregada ({} ativos)", total),
+                Err(e) => log::error!("❌ Erro ao recarregar tokens revogados: {}", e),
+            }
+        }
+    });
+}
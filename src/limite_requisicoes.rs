@@ -0,0 +1,262 @@
+// src/limite_requisicoes.rs
+// Limite de requisições por cliente (token bucket), por escopo de rotas
+//
+// - Chave: `Claims.sub` (usuário ou `apikey:<nome>`), então precisa rodar depois do `JwtMiddleware`
+// - Cada cliente tem um balde por escopo com capacidade = limite por minuto, reabastecido continuamente
+// - Balde vazio → 429 com `Retry-After`
+// - Contadores por escopo e por cliente expostos em `/admin/metricas`
+
+use std::collections::HashMap;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web, Error, HttpMessage,
+};
+use futures_util::future::LocalBoxFuture;
+use serde::Serialize;
+
+use crate::auth::Claims;
+use crate::errors::ApiError;
+
+/// Acima disso, baldes cheios e ociosos são descartados
+const LIMPEZA_A_PARTIR_DE: usize = 5000;
+const OCIOSIDADE_LIMPEZA: Duration = Duration::from_secs(600);
+
+#[derive(Debug, Clone, Copy)]
+struct Balde {
+    fichas: f64,
+    atualizado: Instant,
+}
+
+/// Contadores de um escopo
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ContadoresEscopo {
+    pub limite_por_minuto: u32,
+    pub permitidas: u64,
+    pub limitadas: u64,
+}
+
+#[derive(Debug, Default)]
+struct Estado {
+    baldes: HashMap<(&'static str, String), Balde>,
+    escopos: HashMap<&'static str, ContadoresEscopo>,
+    /// Requisições recusadas por cliente (todas as rotas)
+    limitadas_por_cliente: HashMap<String, u64>,
+}
+
+/// Estado compartilhado entre os workers (registrado como `web::Data`)
+#[derive(Debug)]
+pub struct LimitadorRequisicoes {
+    habilitado: bool,
+    estado: Mutex<Estado>,
+}
+
+impl LimitadorRequisicoes {
+    pub fn new(habilitado: bool) -> Self {
+        LimitadorRequisicoes { habilitado, estado: Mutex::new(Estado::default()) }
+    }
+
+    /// Consome uma ficha do balde do cliente; sem fichas retorna os segundos até a próxima
+    fn consumir(&self, escopo: &'static str, por_minuto: u32, cliente: &str) -> Result<(), u64> {
+        let agora = Instant::now();
+        let Ok(mut estado) = self.estado.lock() else {
+            return Ok(());
+        };
+
+        let capacidade = por_minuto as f64;
+        let taxa = capacidade / 60.0;
+
+        if estado.baldes.len() > LIMPEZA_A_PARTIR_DE {
+            estado.baldes.retain(|_, b| agora - b.atualizado < OCIOSIDADE_LIMPEZA);
+        }
+
+        let balde = estado.baldes.entry((escopo, cliente.to_string())).or_insert(Balde {
+            fichas: capacidade,
+            atualizado: agora,
+        });
+        balde.fichas = (balde.fichas + (agora - balde.atualizado).as_secs_f64() * taxa).min(capacidade);
+        balde.atualizado = agora;
+
+        let resultado = if balde.fichas >= 1.0 {
+            balde.fichas -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - balde.fichas) / taxa).ceil().max(1.0) as u64)
+        };
+
+        let contadores = estado.escopos.entry(escopo).or_default();
+        contadores.limite_por_minuto = por_minuto;
+        match resultado {
+            Ok(()) => contadores.permitidas += 1,
+            Err(_) => {
+                contadores.limitadas += 1;
+                *estado.limitadas_por_cliente.entry(cliente.to_string()).or_default() += 1;
+            }
+        }
+
+        resultado
+    }
+
+    /// Snapshot dos contadores para o endpoint de métricas
+    pub fn metricas(&self) -> serde_json::Value {
+        let Ok(estado) = self.estado.lock() else {
+            return serde_json::json!({ "erro": "estado indisponível" });
+        };
+
+        let mut clientes: Vec<(&String, &u64)> = estado.limitadas_por_cliente.iter().collect();
+        clientes.sort_by(|a, b| b.1.cmp(a.1));
+
+        serde_json::json!({
+            "habilitado": self.habilitado,
+            "clientes_ativos": estado.baldes.len(),
+            "escopos": estado.escopos,
+            "mais_limitados": clientes.iter().take(20)
+                .map(|(cliente, total)| serde_json::json!({ "cliente": cliente, "limitadas": total }))
+                .collect::<Vec<_>>(),
+        })
+    }
+}
+
+/// Middleware de limite por escopo. Registrar com `.wrap()` ANTES do `JwtMiddleware`
+/// (o último `.wrap()` roda primeiro), para que as claims já estejam no request.
+pub struct LimiteRequisicoes {
+    escopo: &'static str,
+    por_minuto: u32,
+}
+
+impl LimiteRequisicoes {
+    pub fn new(escopo: &'static str, por_minuto: u32) -> Self {
+        LimiteRequisicoes { escopo, por_minuto }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for LimiteRequisicoes
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = LimiteRequisicoesService<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(LimiteRequisicoesService {
+            service: Rc::new(service),
+            escopo: self.escopo,
+            por_minuto: self.por_minuto,
+        }))
+    }
+}
+
+/// Serviço do middleware de limite
+pub struct LimiteRequisicoesService<S> {
+    service: Rc<S>,
+    escopo: &'static str,
+    por_minuto: u32,
+}
+
+impl<S, B> Service<ServiceRequest> for LimiteRequisicoesService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let escopo = self.escopo;
+        let por_minuto = self.por_minuto;
+
+        Box::pin(async move {
+            let limitador = req.app_data::<web::Data<LimitadorRequisicoes>>().cloned();
+            let cliente = req.extensions().get::<Claims>().map(|c| c.sub.clone());
+
+            if let (Some(limitador), Some(cliente)) = (limitador, cliente) {
+                if limitador.habilitado && por_minuto > 0 {
+                    if let Err(segundos) = limitador.consumir(escopo, por_minuto, &cliente) {
+                        log::warn!("🚦 Limite de {}/min excedido por '{}' em {}", por_minuto, cliente, req.path());
+                        return Err(ApiError::LimiteRequisicoes { segundos }.into());
+                    }
+                }
+            }
+
+            service.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Simula a passagem do tempo recuando o último abastecimento dos baldes
+    fn recuar(limitador: &LimitadorRequisicoes, segundos: u64) {
+        let mut estado = limitador.estado.lock().unwrap();
+        for balde in estado.baldes.values_mut() {
+            balde.atualizado -= Duration::from_secs(segundos);
+        }
+    }
+
+    #[test]
+    fn permite_ate_a_capacidade_e_depois_recusa() {
+        let limitador = LimitadorRequisicoes::new(true);
+        for _ in 0..3 {
+            assert!(limitador.consumir("data", 3, "ana").is_ok());
+        }
+        // 3/min = uma ficha a cada 20s
+        assert_eq!(limitador.consumir("data", 3, "ana"), Err(20));
+    }
+
+    #[test]
+    fn reabastece_com_o_tempo_sem_passar_da_capacidade() {
+        let limitador = LimitadorRequisicoes::new(true);
+        for _ in 0..3 {
+            limitador.consumir("data", 3, "ana").unwrap();
+        }
+
+        recuar(&limitador, 20);
+        assert!(limitador.consumir("data", 3, "ana").is_ok());
+        assert!(limitador.consumir("data", 3, "ana").is_err());
+
+        recuar(&limitador, 100);
+        for _ in 0..3 {
+            assert!(limitador.consumir("data", 3, "ana").is_ok());
+        }
+        assert!(limitador.consumir("data", 3, "ana").is_err());
+    }
+
+    #[test]
+    fn baldes_separados_por_cliente_e_escopo() {
+        let limitador = LimitadorRequisicoes::new(true);
+        assert!(limitador.consumir("data", 1, "ana").is_ok());
+        assert!(limitador.consumir("data", 1, "ana").is_err());
+        assert!(limitador.consumir("data", 1, "bruno").is_ok());
+        assert!(limitador.consumir("portal", 1, "ana").is_ok());
+    }
+
+    #[test]
+    fn metricas_contam_permitidas_e_limitadas() {
+        let limitador = LimitadorRequisicoes::new(true);
+        limitador.consumir("data", 1, "ana").unwrap();
+        limitador.consumir("data", 1, "ana").unwrap_err();
+        limitador.consumir("data", 1, "ana").unwrap_err();
+
+        let metricas = limitador.metricas();
+        assert_eq!(metricas["escopos"]["data"]["permitidas"], 1);
+        assert_eq!(metricas["escopos"]["data"]["limitadas"], 2);
+        assert_eq!(metricas["mais_limitados"][0]["cliente"], "ana");
+        assert_eq!(metricas["mais_limitados"][0]["limitadas"], 2);
+    }
+}
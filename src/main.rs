@@ -4,16 +4,26 @@
 use actix_cors::Cors;
 use actix_web::{middleware::Logger, web, App, HttpServer};
 
+mod auditoria;
 mod auth;
+mod chaves_jwt;
 mod config;
 mod database;
 mod errors;
 mod handlers;
+mod jobs;
+mod limite_requisicoes;
 mod models;
 mod logging;
+mod protecao_login;
 
+use crate::auditoria::AuditoriaRequisicoes;
+use crate::auth::{JwtMiddleware, Papel, TokensRevogados};
+use crate::chaves_jwt::ChavesJwt;
 use crate::config::Settings;
 use crate::database::DatabasePools;
+use crate::limite_requisicoes::{LimitadorRequisicoes, LimiteRequisicoes};
+use crate::protecao_login::ProtecaoLogin;
 use crate::handlers::{auth_handlers, data_handlers};
 
 #[actix_web::main]
@@ -39,6 +49,15 @@ async fn main() -> std::io::Result<()> {
         }
     };
 
+    // 🔏 Chaves de assinatura JWT (falha na inicialização se a configuração estiver incompleta)
+    let chaves_jwt = match ChavesJwt::carregar(&settings.jwt) {
+        Ok(chaves) => web::Data::new(chaves),
+        Err(e) => {
+            log::error!("❌ ERRO ao carregar chaves JWT: {}", e);
+            panic!("Não foi possível carregar as chaves JWT: {}", e);
+        }
+    };
+
     log::info!("📊 Conectando aos bancos de dados...");
     
     // Criar pools de conexão para os 3 bancos
@@ -50,6 +69,57 @@ async fn main() -> std::io::Result<()> {
     log::info!("  - SQL Server Portal: ✓");
     log::info!("  - SQL Server Protheus: ✓");
 
+    // 👤 Tabela de usuários (cria e grava o admin do .env se estiver vazia)
+    if let Err(e) = handlers::usuarios::inicializar(&db_pools.postgres_fc, &settings.admin).await {
+        log::error!("❌ Erro ao inicializar tabela de usuários: {}", e);
+    }
+
+    // 🧾 Trilha de auditoria
+    match db_pools.postgres_fc.get().await {
+        Ok(client) => {
+            if let Err(e) = auditoria::inicializar(&client).await {
+                log::error!("❌ Erro ao inicializar tabela de auditoria: {}", e);
+            }
+        }
+        Err(e) => log::error!("❌ Erro ao conectar para inicializar auditoria: {}", e),
+    }
+
+    // 📚 Catálogo de consultas salvas
+    match db_pools.postgres_fc.get().await {
+        Ok(client) => {
+            if let Err(e) = handlers::consultas_salvas::inicializar(&client).await {
+                log::error!("❌ Erro ao inicializar catálogo de consultas salvas: {}", e);
+            }
+        }
+        Err(e) => log::error!("❌ Erro ao conectar para inicializar consultas salvas: {}", e),
+    }
+
+    // 🔒 Proteção do login contra força bruta (compartilhada entre os workers)
+    let protecao_login = web::Data::new(ProtecaoLogin::new(settings.login.clone()));
+
+    // 🚦 Limite de requisições por cliente (token bucket por escopo)
+    let limitador = web::Data::new(LimitadorRequisicoes::new(settings.limite_requisicoes.habilitado));
+
+    // 🚫 Lista de tokens revogados (logout / revogação administrativa)
+    let tokens_revogados = TokensRevogados::default();
+    jobs::tokens_revogados::iniciar(db_pools.postgres_fc.clone(), tokens_revogados.clone());
+
+    // 🔄 Worker de integração Portal → Protheus
+    let estado_integracao = jobs::integracao_protheus::EstadoCompartilhado::default();
+    jobs::integracao_protheus::iniciar(
+        db_pools.clone(),
+        settings.integracao.clone(),
+        estado_integracao.clone(),
+    );
+
+    // 🔁 Sincronização de status Protheus → Portal
+    let estado_sincronizacao = jobs::sincronizacao_status::EstadoCompartilhado::default();
+    jobs::sincronizacao_status::iniciar(
+        db_pools.clone(),
+        settings.sincronizacao.clone(),
+        estado_sincronizacao.clone(),
+    );
+
     let bind_address = format!("{}:{}", settings.server.host, settings.server.port);
     log::info!("🌐 Servidor rodando em http://{}", bind_address);
     log::info!("📍 API disponível em http://{}{}", bind_address, settings.api.prefix);
@@ -67,32 +137,51 @@ async fn main() -> std::io::Result<()> {
                         .any(|allowed| origin.as_bytes() == allowed.as_bytes())
                 }
             })
-            .allowed_methods(vec!["GET", "POST"])
-            .allowed_headers(vec!["Authorization", "Content-Type"])
+            .allowed_methods(vec!["GET", "POST", "PUT", "DELETE"])
+            .allowed_headers(vec!["Authorization", "Content-Type", auth::HEADER_CHAVE_API])
             .max_age(3600);
 
         App::new()
             .app_data(web::Data::new(db_pools.clone()))
             .app_data(web::Data::new(db_pools.postgres_fc.clone()))
             .app_data(web::Data::new(settings.clone()))
+            .app_data(web::Data::new(estado_integracao.clone()))
+            .app_data(web::Data::new(estado_sincronizacao.clone()))
+            .app_data(web::Data::new(tokens_revogados.clone()))
+            .app_data(chaves_jwt.clone())
+            .app_data(protecao_login.clone())
+            .app_data(limitador.clone())
             .wrap(cors)
             .wrap(Logger::default())
+            // 🔏 JWKS público (chaves de verificação dos tokens)
+            .route("/.well-known/jwks.json", web::get().to(auth_handlers::jwks))
             .service(
                 web::scope(&settings.api.prefix)
                     // Rotas de autenticação
                     .route("/auth/login", web::post().to(auth_handlers::login))
+                    .route("/auth/refresh", web::post().to(auth_handlers::refresh))
+                    .route("/.well-known/jwks.json", web::get().to(auth_handlers::jwks))
+                    
+                    .service(
+                        web::resource("/auth/logout")
+                            .wrap(JwtMiddleware::autenticado())
+                            .wrap(AuditoriaRequisicoes)
+                            .route(web::post().to(auth_handlers::logout))
+                    )
                     
                     // Rota de validação (protegida por JWT)
                     .service(
                         web::resource("/auth/validate")
-                            .wrap(auth::JwtMiddleware)
+                            .wrap(JwtMiddleware::autenticado())
                             .route(web::get().to(auth_handlers::validate_token))
                     )
                     
                     // Rotas de dados (protegidas por JWT)
                     .service(
                         web::scope("/data")
-                            .wrap(auth::JwtMiddleware)
+                            .wrap(LimiteRequisicoes::new("data", settings.limite_requisicoes.data))
+                            .wrap(JwtMiddleware::papeis(&[Papel::Admin, Papel::Analista]))
+                            .wrap(AuditoriaRequisicoes)
                             .route("/vendas", web::get().to(data_handlers::get_vendas))
                             .route("/vendas/detalhes", web::get().to(data_handlers::get_vendas_detalhadas))
                             .route("/query", web::post().to(handlers::query_handlers::execute_custom_query))
@@ -101,7 +190,9 @@ async fn main() -> std::io::Result<()> {
                     // Rotas do Portal (protegidas por JWT)
                     .service(
                         web::scope("/portal")
-                            .wrap(auth::JwtMiddleware)
+                            .wrap(LimiteRequisicoes::new("portal", settings.limite_requisicoes.portal))
+                            .wrap(JwtMiddleware::papeis(&[Papel::Admin, Papel::Analista, Papel::Franqueado, Papel::Integrador]))
+                            .wrap(AuditoriaRequisicoes)
                             // 🎯 NOVOS ENDPOINTS CRÍTICOS DO PORTAL
                             .route("/franqueados", web::get().to(handlers::portal_endpoints::listar_franqueados))
                             .route("/franqueados/buscar", web::get().to(handlers::portal_endpoints::buscar_franqueados))
@@ -110,14 +201,21 @@ async fn main() -> std::io::Result<()> {
                             .route("/produtos/buscar", web::get().to(handlers::portal_endpoints::buscar_produtos))
                             
                             // ✅ ENDPOINTS EXISTENTES
-                            .route("/query", web::post().to(handlers::portal_handlers::query_portal))
+                            .service(
+                                // SQL livre apenas para admin e analistas
+                                web::resource("/query")
+                                    .wrap(JwtMiddleware::papeis(&[Papel::Admin, Papel::Analista]))
+                                    .route(web::post().to(handlers::portal_handlers::query_portal))
+                            )
                             .route("/produtos", web::get().to(handlers::portal_handlers::listar_produtos_por_grupo))
                     )
                     
                     // Rotas do Protheus (protegidas por JWT)
                     .service(
                         web::scope("/protheus")
-                            .wrap(auth::JwtMiddleware)
+                            .wrap(LimiteRequisicoes::new("protheus", settings.limite_requisicoes.protheus))
+                            .wrap(JwtMiddleware::papeis(&[Papel::Admin, Papel::Analista, Papel::Integrador]))
+                            .wrap(AuditoriaRequisicoes)
                             .route("/query", web::post().to(handlers::protheus_handlers::query_protheus))
                             .route("/pedidos/{numero}/status", web::get().to(handlers::protheus_handlers::status_pedido_protheus))
                     )
@@ -125,7 +223,9 @@ async fn main() -> std::io::Result<()> {
                     // Rotas de Analytics (protegidas por JWT)
                     .service(
                         web::scope("/analytics")
-                            .wrap(auth::JwtMiddleware)
+                            .wrap(LimiteRequisicoes::new("analytics", settings.limite_requisicoes.analytics))
+                            .wrap(JwtMiddleware::papeis(&[Papel::Admin, Papel::Analista, Papel::Franqueado]))
+                            .wrap(AuditoriaRequisicoes)
                             // 🎯 NOVOS ENDPOINTS CRÍTICOS - Estrutura modular
                             .route("/pedido/oportunidades", web::post().to(handlers::analytics::analisar_pedido_oportunidades))
                             .route("/efetividade-sugestoes", web::get().to(handlers::analytics::buscar_efetividade_sugestoes))
@@ -143,17 +243,56 @@ async fn main() -> std::io::Result<()> {
                     // 🛒 Rotas de Pedidos (protegidas por JWT) 
                     .service(
                         web::scope("/pedidos")
-                            .wrap(auth::JwtMiddleware)
+                            .wrap(LimiteRequisicoes::new("pedidos", settings.limite_requisicoes.pedidos))
+                            .wrap(JwtMiddleware::papeis(&[Papel::Admin, Papel::Analista, Papel::Franqueado, Papel::Integrador]))
+                            .wrap(AuditoriaRequisicoes)
                             // 🎯 NOVOS ENDPOINTS CRÍTICOS
                             .route("/gerar-com-oportunidades", web::post().to(handlers::pedidos::gerar_pedido_com_oportunidades))
                             .route("/{id}/items/marcar-sugestao", web::post().to(handlers::pedidos::marcar_item_sugestao))
                             
                             // ✅ ENDPOINTS EXISTENTES CRUD
+                            .route("", web::get().to(handlers::pedidos::listar_pedidos))
                             .route("", web::post().to(handlers::pedidos::criar_pedido))
                             .route("/{id}", web::get().to(handlers::pedidos::buscar_pedido))
                             .route("/{id}", web::put().to(handlers::pedidos::atualizar_pedido))
                             .route("/{id}", web::delete().to(handlers::pedidos::deletar_pedido))
                             .route("/{id}/confirmar", web::post().to(handlers::pedidos::confirmar_pedido))
+                            .route("/{id}/restaurar", web::post().to(handlers::pedidos::restaurar_pedido))
+                    )
+                    
+                    // 📚 Consultas salvas (papéis definidos por consulta)
+                    .service(
+                        web::scope("/queries")
+                            .wrap(LimiteRequisicoes::new("queries", settings.limite_requisicoes.queries))
+                            .wrap(JwtMiddleware::papeis(&[Papel::Admin, Papel::Analista, Papel::Franqueado, Papel::Integrador]))
+                            .wrap(AuditoriaRequisicoes)
+                            .route("", web::get().to(handlers::consultas_salvas::listar_disponiveis))
+                            .route("/{name}/run", web::post().to(handlers::consultas_salvas::executar_consulta))
+                    )
+                    
+                    // 🛠️ Rotas administrativas (apenas admin)
+                    .service(
+                        web::scope("/admin")
+                            .wrap(LimiteRequisicoes::new("admin", settings.limite_requisicoes.admin))
+                            .wrap(JwtMiddleware::papeis(&[Papel::Admin]))
+                            .wrap(AuditoriaRequisicoes)
+                            .route("/integracao/fila", web::get().to(handlers::admin_handlers::fila_integracao))
+                            .route("/sincronizacao", web::get().to(handlers::admin_handlers::status_sincronizacao))
+                            .route("/metricas", web::get().to(handlers::admin_handlers::metricas))
+                            .route("/auditoria", web::get().to(handlers::admin_handlers::consultar_auditoria))
+                            .route("/usuarios", web::get().to(handlers::usuarios::listar_usuarios))
+                            .route("/usuarios", web::post().to(handlers::usuarios::criar_usuario))
+                            .route("/usuarios/{id}/desativar", web::post().to(handlers::usuarios::desativar_usuario))
+                            .route("/usuarios/{id}/resetar-senha", web::post().to(handlers::usuarios::resetar_senha))
+                            .route("/usuarios/{id}/papeis", web::post().to(handlers::usuarios::definir_papeis))
+                            .route("/usuarios/{id}/cnpjs", web::post().to(handlers::usuarios::definir_cnpjs))
+                            .route("/tokens/revogar", web::post().to(handlers::usuarios::revogar_token))
+                            .route("/chaves-api", web::get().to(handlers::usuarios::listar_chaves_api))
+                            .route("/chaves-api", web::post().to(handlers::usuarios::criar_chave_api))
+                            .route("/chaves-api/{id}/revogar", web::post().to(handlers::usuarios::revogar_chave_api))
+                            .route("/consultas-salvas", web::get().to(handlers::consultas_salvas::listar_consultas))
+                            .route("/consultas-salvas/{name}", web::put().to(handlers::consultas_salvas::salvar_consulta))
+                            .route("/consultas-salvas/{name}", web::delete().to(handlers::consultas_salvas::remover_consulta))
                     )
                     
                     // Health check público
@@ -162,7 +301,8 @@ async fn main() -> std::io::Result<()> {
                     // 🔍 DEBUG: Endpoints de debug 
                     .service(
                         web::scope("/debug")
-                            .wrap(auth::JwtMiddleware)
+                            .wrap(JwtMiddleware::papeis(&[Papel::Admin]))
+                            .wrap(AuditoriaRequisicoes)
                             .route("/logs", web::get().to(handlers::debug_handlers::visualizar_logs_cards))
                             .route("/logs/rotate", web::post().to(handlers::debug_handlers::rotacionar_logs))
                             .route("/logs/status", web::get().to(handlers::debug_handlers::status_logging))
@@ -119,6 +119,104 @@ pub struct Pedido {
     pub updated_at: Option<String>,
 }
 
+/// Status do pedido no Portal (`pedidos.status_pedido`)
+/// Fluxo: a confirmar → confirmado → integrado → Faturado → Expedido, com cancelamento até a integração
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusPedido {
+    Rascunho,
+    Confirmado,
+    Integrado,
+    Faturado,
+    Expedido,
+    Cancelado,
+}
+
+impl StatusPedido {
+    /// Valor gravado pela API em `status_pedido`
+    pub fn as_str(&self) -> &'static str {
+        self.valores_banco()[0]
+    }
+    
+    /// Valores aceitos na leitura, incluindo os status intermediários gravados pelo ERP
+    pub fn valores_banco(&self) -> &'static [&'static str] {
+        match self {
+            StatusPedido::Rascunho => &["a confirmar"],
+            StatusPedido::Confirmado => &["confirmado"],
+            StatusPedido::Integrado => &["integrado", "Confirmado ERP", "Em Separação"],
+            StatusPedido::Faturado => &["Faturado"],
+            StatusPedido::Expedido => &["Expedido", "Pronto pra Coleta"],
+            StatusPedido::Cancelado => &["cancelado"],
+        }
+    }
+    
+    pub fn from_banco(valor: &str) -> Option<Self> {
+        let valor = valor.trim();
+        Self::todos().iter().copied().find(|status| {
+            status.valores_banco().iter().any(|v| v.eq_ignore_ascii_case(valor))
+        })
+    }
+    
+    pub fn todos() -> &'static [StatusPedido] {
+        &[
+            StatusPedido::Rascunho,
+            StatusPedido::Confirmado,
+            StatusPedido::Integrado,
+            StatusPedido::Faturado,
+            StatusPedido::Expedido,
+            StatusPedido::Cancelado,
+        ]
+    }
+    
+    /// Próximos status permitidos a partir deste
+    pub fn transicoes(&self) -> &'static [StatusPedido] {
+        match self {
+            StatusPedido::Rascunho => &[StatusPedido::Confirmado, StatusPedido::Cancelado],
+            StatusPedido::Confirmado => &[StatusPedido::Integrado, StatusPedido::Cancelado],
+            StatusPedido::Integrado => &[StatusPedido::Faturado, StatusPedido::Cancelado],
+            StatusPedido::Faturado => &[StatusPedido::Expedido],
+            StatusPedido::Expedido | StatusPedido::Cancelado => &[],
+        }
+    }
+    
+    pub fn pode_transitar_para(&self, destino: StatusPedido) -> bool {
+        self.transicoes().contains(&destino)
+    }
+    
+    /// Apenas rascunhos podem ser editados ou excluídos
+    pub fn permite_edicao(&self) -> bool {
+        *self == StatusPedido::Rascunho
+    }
+    
+    /// Indica se `destino` é alcançável a partir deste status por uma ou mais transições
+    /// (ex.: integrado → Expedido quando o faturamento e a expedição chegam juntos)
+    pub fn alcanca(&self, destino: StatusPedido) -> bool {
+        self.transicoes().iter().any(|t| *t == destino || t.alcanca(destino))
+    }
+    
+    /// Valores de `status_pedido` que os Cards de analytics contam como compra efetivada.
+    /// Recorte próprio dos Cards, mais estreito que os grupos do enum: `Em Separação` e a
+    /// expedição ficam de fora, preservando os números já publicados.
+    pub const VALORES_ANALYTICS: &'static [&'static str] = &["integrado", "Confirmado ERP", "Faturado"];
+    
+    /// Lista SQL (`N'a', N'b'`) com todos os valores gravados no banco para os status informados.
+    /// O prefixo `N` mantém os acentos (`Em Separação`) na comparação com colunas NVARCHAR.
+    pub fn lista_sql(status: &[StatusPedido]) -> String {
+        literais_sql(status.iter().flat_map(|status| status.valores_banco().iter()))
+    }
+    
+    /// Lista SQL de `VALORES_ANALYTICS`, usada pelos Cards
+    pub fn efetivados_sql() -> String {
+        literais_sql(Self::VALORES_ANALYTICS.iter())
+    }
+}
+
+fn literais_sql<'a>(valores: impl Iterator<Item = &'a &'static str>) -> String {
+    valores
+        .map(|v| format!("N'{}'", v))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Item {
     pub id: i32,
@@ -201,15 +299,10 @@ pub struct Cliente {
 pub struct CriarPedidoRequest {
     pub codigo_cliente: String,
     pub loja_cliente: String,
-    #[allow(dead_code)] // TODO: Implementar lógica de data de emissão
     pub emissao: String,
-    #[allow(dead_code)] // TODO: Implementar diferentes naturezas de pedido
     pub natureza: Option<String>, // Default: "10212"
-    #[allow(dead_code)] // TODO: Implementar sistema de mensagens
     pub mensagem: Option<String>,
-    #[allow(dead_code)] // TODO: Implementar regras de condição de pagamento
     pub regra_condicao_pagamento_id: i32,
-    #[allow(dead_code)] // TODO: Implementar cálculo de frete
     pub regra_frete_id: i32,
     pub items: Vec<ItemPedidoRequest>,
 }
@@ -230,6 +323,28 @@ pub struct CriarPedidoResponse {
     pub errors: Option<Vec<String>>,
 }
 
+/// Item do pedido com os dados do produto e o valor calculado
+#[derive(Debug, Serialize)]
+pub struct ItemDetalhado {
+    #[serde(flatten)]
+    pub item: Item,
+    pub produto: Produto,
+    pub valor_total: Decimal,
+}
+
+/// Pedido completo retornado por GET /pedidos/{id}
+#[derive(Debug, Serialize)]
+pub struct PedidoDetalhado {
+    #[serde(flatten)]
+    pub pedido: Pedido,
+    pub items: Vec<ItemDetalhado>,
+    pub total_itens: usize,
+    pub quantidade_total: i32,
+    pub valor_total: Decimal,
+    pub regra_frete: Option<RegraFrete>,
+    pub regra_parcelamento: Option<RegraParcelamento>,
+}
+
 #[derive(Debug, Serialize)]
 pub struct ProdutoComPreco {
     pub id: i32,
@@ -258,3 +373,73 @@ impl Default for CriarPedidoRequest {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::StatusPedido;
+
+    #[test]
+    fn fluxo_principal_segue_a_maquina_de_estados() {
+        assert!(StatusPedido::Rascunho.pode_transitar_para(StatusPedido::Confirmado));
+        assert!(StatusPedido::Confirmado.pode_transitar_para(StatusPedido::Integrado));
+        assert!(StatusPedido::Integrado.pode_transitar_para(StatusPedido::Faturado));
+        assert!(StatusPedido::Faturado.pode_transitar_para(StatusPedido::Expedido));
+    }
+
+    #[test]
+    fn transicoes_fora_do_fluxo_sao_recusadas() {
+        assert!(!StatusPedido::Rascunho.pode_transitar_para(StatusPedido::Integrado));
+        assert!(!StatusPedido::Confirmado.pode_transitar_para(StatusPedido::Rascunho));
+        assert!(!StatusPedido::Faturado.pode_transitar_para(StatusPedido::Cancelado));
+        assert!(StatusPedido::Expedido.transicoes().is_empty());
+        assert!(StatusPedido::Cancelado.transicoes().is_empty());
+    }
+
+    #[test]
+    fn apenas_rascunho_permite_edicao() {
+        for status in StatusPedido::todos() {
+            assert_eq!(status.permite_edicao(), *status == StatusPedido::Rascunho, "{:?}", status);
+        }
+    }
+
+    #[test]
+    fn alcanca_segue_transicoes_encadeadas() {
+        assert!(StatusPedido::Integrado.alcanca(StatusPedido::Expedido));
+        assert!(StatusPedido::Rascunho.alcanca(StatusPedido::Expedido));
+        assert!(StatusPedido::Confirmado.alcanca(StatusPedido::Cancelado));
+        assert!(!StatusPedido::Faturado.alcanca(StatusPedido::Cancelado));
+        assert!(!StatusPedido::Expedido.alcanca(StatusPedido::Faturado));
+        assert!(!StatusPedido::Integrado.alcanca(StatusPedido::Integrado));
+    }
+
+    #[test]
+    fn from_banco_aceita_status_do_erp() {
+        assert_eq!(StatusPedido::from_banco(" a confirmar "), Some(StatusPedido::Rascunho));
+        assert_eq!(StatusPedido::from_banco("CONFIRMADO ERP"), Some(StatusPedido::Integrado));
+        assert_eq!(StatusPedido::from_banco("Em Separação"), Some(StatusPedido::Integrado));
+        assert_eq!(StatusPedido::from_banco("Pronto pra Coleta"), Some(StatusPedido::Expedido));
+        assert_eq!(StatusPedido::from_banco("desconhecido"), None);
+        for status in StatusPedido::todos() {
+            assert_eq!(StatusPedido::from_banco(status.as_str()), Some(*status));
+        }
+    }
+
+    #[test]
+    fn lista_sql_usa_literais_unicode() {
+        assert_eq!(
+            StatusPedido::lista_sql(&[StatusPedido::Integrado, StatusPedido::Faturado]),
+            "N'integrado', N'Confirmado ERP', N'Em Separação', N'Faturado'"
+        );
+    }
+
+    #[test]
+    fn cards_contam_apenas_o_recorte_de_analytics() {
+        assert_eq!(StatusPedido::efetivados_sql(), "N'integrado', N'Confirmado ERP', N'Faturado'");
+        for valor in StatusPedido::VALORES_ANALYTICS {
+            assert!(matches!(
+                StatusPedido::from_banco(valor),
+                Some(StatusPedido::Integrado | StatusPedido::Faturado)
+            ));
+        }
+    }
+}
@@ -0,0 +1,241 @@
+// src/protecao_login.rs
+// Proteção do login contra força bruta (em memória, por usuário e por IP)
+//
+// - Cada falha dentro da janela (`LOGIN_JANELA_MINUTOS`) soma uma tentativa
+// - Atraso progressivo na resposta de falha: base, 2x, 4x... até `LOGIN_ATRASO_MAX_MS`
+// - Ao atingir o limite, o usuário (ou IP) fica bloqueado por `LOGIN_BLOQUEIO_MINUTOS`,
+//   inclusive para a senha correta
+// - Login bem-sucedido zera as tentativas do usuário (as do IP continuam contando)
+// - O IP vem de `X-Forwarded-For` só com `LOGIN_CONFIAR_PROXY=true` e conexão de um
+//   proxy listado em `LOGIN_PROXIES_CONFIAVEIS`
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use actix_web::HttpRequest;
+
+use crate::config::LoginConfig;
+use crate::errors::ApiError;
+
+/// Acima disso as entradas vencidas são descartadas a cada falha
+const LIMPEZA_A_PARTIR_DE: usize = 1000;
+
+#[derive(Debug, Clone, Copy)]
+struct Tentativas {
+    falhas: u32,
+    inicio_janela: Instant,
+    bloqueado_ate: Option<Instant>,
+}
+
+/// Resultado de uma falha de login
+#[derive(Debug)]
+pub struct Falha {
+    /// Atraso a aplicar antes de responder
+    pub atraso: Duration,
+    /// Preenchido quando esta falha provocou bloqueio: ("usuario" | "ip", segundos)
+    pub bloqueio: Option<(&'static str, u64)>,
+}
+
+pub struct ProtecaoLogin {
+    config: LoginConfig,
+    registros: Mutex<HashMap<String, Tentativas>>,
+}
+
+impl ProtecaoLogin {
+    pub fn new(config: LoginConfig) -> Self {
+        ProtecaoLogin { config, registros: Mutex::new(HashMap::new()) }
+    }
+
+    /// Rejeita com `ApiError::LoginBloqueado` se o usuário ou o IP estiverem bloqueados
+    pub fn verificar(&self, username: &str, ip: &str) -> Result<(), ApiError> {
+        let agora = Instant::now();
+        let registros = self.registros.lock()
+            .map_err(|_| ApiError::InternalError("Proteção de login indisponível".to_string()))?;
+
+        let restante = [chave_usuario(username), chave_ip(ip)].iter()
+            .filter_map(|chave| registros.get(chave)?.bloqueado_ate)
+            .filter(|ate| *ate > agora)
+            .map(|ate| ate - agora)
+            .max();
+
+        match restante {
+            Some(restante) => Err(ApiError::LoginBloqueado { segundos: restante.as_secs().max(1) }),
+            None => Ok(()),
+        }
+    }
+
+    /// Conta a falha para o usuário e para o IP
+    pub fn registrar_falha(&self, username: &str, ip: &str) -> Falha {
+        let agora = Instant::now();
+        let janela = Duration::from_secs(self.config.janela_minutos * 60);
+        let bloqueio = Duration::from_secs(self.config.bloqueio_minutos * 60);
+
+        let Ok(mut registros) = self.registros.lock() else {
+            return Falha { atraso: Duration::from_millis(self.config.atraso_max_ms), bloqueio: None };
+        };
+
+        if registros.len() > LIMPEZA_A_PARTIR_DE {
+            registros.retain(|_, t| {
+                t.bloqueado_ate.is_some_and(|ate| ate > agora) || agora - t.inicio_janela < janela
+            });
+        }
+
+        let mut maior_contagem = 0;
+        let mut bloqueou = None;
+
+        for (tipo, chave, limite) in [
+            ("usuario", chave_usuario(username), self.config.max_tentativas_usuario),
+            ("ip", chave_ip(ip), self.config.max_tentativas_ip),
+        ] {
+            let t = registros.entry(chave).or_insert(Tentativas {
+                falhas: 0,
+                inicio_janela: agora,
+                bloqueado_ate: None,
+            });
+
+            if agora - t.inicio_janela >= janela {
+                t.falhas = 0;
+                t.inicio_janela = agora;
+            }
+            t.falhas += 1;
+            maior_contagem = maior_contagem.max(t.falhas);
+
+            if limite > 0 && t.falhas >= limite {
+                t.bloqueado_ate = Some(agora + bloqueio);
+                t.falhas = 0;
+                t.inicio_janela = agora;
+                bloqueou.get_or_insert((tipo, bloqueio.as_secs()));
+            }
+        }
+
+        Falha { atraso: self.atraso(maior_contagem), bloqueio: bloqueou }
+    }
+
+    /// Login correto: esquece as falhas do usuário
+    pub fn registrar_sucesso(&self, username: &str) {
+        if let Ok(mut registros) = self.registros.lock() {
+            registros.remove(&chave_usuario(username));
+        }
+    }
+
+    fn atraso(&self, falhas: u32) -> Duration {
+        let fator = 1u64 << falhas.saturating_sub(1).min(16);
+        Duration::from_millis(self.config.atraso_base_ms.saturating_mul(fator).min(self.config.atraso_max_ms))
+    }
+}
+
+/// IP do cliente. Atrás do Apache, o último item de `X-Forwarded-For` é o que o
+/// próprio proxy adicionou; os anteriores vêm do cliente e não são confiáveis.
+/// O header só é lido quando a conexão vem de um proxy em `LOGIN_PROXIES_CONFIAVEIS`;
+/// quem acessa o servidor direto não escolhe o próprio IP.
+pub fn ip_cliente(req: &HttpRequest, config: &LoginConfig) -> String {
+    let encaminhado = req.headers().get("X-Forwarded-For").and_then(|v| v.to_str().ok());
+    resolver_ip(req.peer_addr().map(|addr| addr.ip()), encaminhado, config)
+}
+
+fn resolver_ip(origem: Option<IpAddr>, encaminhado: Option<&str>, config: &LoginConfig) -> String {
+    let Some(origem) = origem else {
+        return "desconhecido".to_string();
+    };
+
+    if config.confiar_proxy && config.proxies_confiaveis.contains(&origem) {
+        let ultimo = encaminhado
+            .and_then(|v| v.rsplit(',').next())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty());
+        if let Some(ip) = ultimo {
+            return ip;
+        }
+    }
+
+    origem.to_string()
+}
+
+fn chave_usuario(username: &str) -> String {
+    format!("u:{}", username.trim().to_lowercase())
+}
+
+fn chave_ip(ip: &str) -> String {
+    format!("ip:{}", ip)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> LoginConfig {
+        LoginConfig {
+            max_tentativas_usuario: 3,
+            max_tentativas_ip: 5,
+            janela_minutos: 15,
+            bloqueio_minutos: 15,
+            atraso_base_ms: 250,
+            atraso_max_ms: 1000,
+            confiar_proxy: true,
+            proxies_confiaveis: vec!["127.0.0.1".parse().unwrap()],
+        }
+    }
+
+    #[test]
+    fn atraso_dobra_a_cada_falha_ate_o_maximo() {
+        let protecao = ProtecaoLogin::new(config());
+        let atrasos: Vec<u64> = (0..3)
+            .map(|_| protecao.registrar_falha("ana", "10.0.0.1").atraso.as_millis() as u64)
+            .collect();
+        assert_eq!(atrasos, vec![250, 500, 1000]);
+    }
+
+    #[test]
+    fn bloqueia_usuario_ao_atingir_o_limite() {
+        let protecao = ProtecaoLogin::new(config());
+        assert!(protecao.registrar_falha("ana", "10.0.0.1").bloqueio.is_none());
+        assert!(protecao.registrar_falha("ana", "10.0.0.2").bloqueio.is_none());
+        assert!(protecao.verificar("ana", "10.0.0.3").is_ok());
+
+        let falha = protecao.registrar_falha("ANA ", "10.0.0.3");
+        assert_eq!(falha.bloqueio, Some(("usuario", 15 * 60)));
+        assert!(matches!(protecao.verificar("ana", "10.0.0.9"), Err(ApiError::LoginBloqueado { .. })));
+        assert!(protecao.verificar("bruno", "10.0.0.9").is_ok());
+    }
+
+    #[test]
+    fn bloqueia_ip_com_varios_usuarios() {
+        let protecao = ProtecaoLogin::new(config());
+        for i in 0..4 {
+            assert!(protecao.registrar_falha(&format!("usuario{}", i), "10.0.0.1").bloqueio.is_none());
+        }
+        assert_eq!(protecao.registrar_falha("usuario4", "10.0.0.1").bloqueio, Some(("ip", 15 * 60)));
+        assert!(protecao.verificar("outro", "10.0.0.1").is_err());
+        assert!(protecao.verificar("outro", "10.0.0.2").is_ok());
+    }
+
+    #[test]
+    fn sucesso_zera_apenas_as_falhas_do_usuario() {
+        let protecao = ProtecaoLogin::new(config());
+        protecao.registrar_falha("ana", "10.0.0.1");
+        protecao.registrar_falha("ana", "10.0.0.1");
+        protecao.registrar_sucesso("ana");
+
+        // Contagem do usuário recomeça; a do IP continua (3 de 5)
+        assert!(protecao.registrar_falha("ana", "10.0.0.1").bloqueio.is_none());
+        assert!(protecao.registrar_falha("ana", "10.0.0.1").bloqueio.is_none());
+        assert_eq!(protecao.registrar_falha("bruno", "10.0.0.1").bloqueio, Some(("ip", 15 * 60)));
+    }
+
+    #[test]
+    fn x_forwarded_for_so_vale_para_proxy_confiavel() {
+        let config = config();
+        let proxy = "127.0.0.1".parse().ok();
+        let direto = "203.0.113.7".parse().ok();
+
+        assert_eq!(resolver_ip(proxy, Some("1.1.1.1, 198.51.100.4"), &config), "198.51.100.4");
+        assert_eq!(resolver_ip(direto, Some("198.51.100.4"), &config), "203.0.113.7");
+        assert_eq!(resolver_ip(proxy, None, &config), "127.0.0.1");
+        assert_eq!(resolver_ip(None, Some("198.51.100.4"), &config), "desconhecido");
+
+        let sem_proxy = LoginConfig { confiar_proxy: false, ..config };
+        assert_eq!(resolver_ip(proxy, Some("198.51.100.4"), &sem_proxy), "127.0.0.1");
+    }
+}